@@ -0,0 +1,466 @@
+// file for heuristics used to adjudicate games before they end by the rules,
+// and for tracking the status of a live game from the events that happen in it
+
+use std::{fmt, time::Duration};
+
+use crate::{
+    AttackMasks, ZobristValues, color, moves,
+    piece::{
+        self,
+        bb::{self, BitboardUtil},
+    },
+    pos, termination,
+};
+
+/// how sure a heuristic is about its verdict
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+/// the kind of structure that made a position look drawn
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum DrawPattern {
+    /// neither side has enough material to checkmate
+    InsufficientMaterial,
+    /// king and rook pawn(s), possibly with a bishop that doesn't control the promotion square,
+    /// against a bare king that reached the promotion corner
+    WrongRookPawn,
+    /// only kings and pawns are left, every pawn is blocked by an enemy pawn,
+    /// and neither king can get to a pawn it could capture
+    BlockedPawns,
+}
+
+/// result of `adjudication::likely_draw()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct LikelyDraw {
+    pub pattern: DrawPattern,
+    pub confidence: Confidence,
+}
+
+/// returns `Some` if the position has a structure that's known to be (almost always) drawn,
+/// regardless of how long the game goes on
+///
+/// meant for cutting off hopeless games earlier than the 50 move rule,
+/// it only recognizes a few trivial patterns and never claims a draw that isn't there
+/// with `Confidence::High`
+pub fn likely_draw(pos: &pos::Position) -> Option<LikelyDraw> {
+    if pos.insufficient_material(color::WHITE) && pos.insufficient_material(color::BLACK) {
+        return Some(LikelyDraw {
+            pattern: DrawPattern::InsufficientMaterial,
+            confidence: Confidence::High,
+        });
+    }
+
+    if blocked_pawns(pos) {
+        return Some(LikelyDraw {
+            pattern: DrawPattern::BlockedPawns,
+            confidence: Confidence::High,
+        });
+    }
+
+    wrong_rook_pawn(pos, color::WHITE)
+        .or_else(|| wrong_rook_pawn(pos, color::BLACK))
+        .map(|confidence| LikelyDraw {
+            pattern: DrawPattern::WrongRookPawn,
+            confidence,
+        })
+}
+
+/// squares a king standing on any square of `bb` attacks
+#[inline(always)]
+fn king_span(bb: bb::Bitboard) -> bb::Bitboard {
+    bb::north(bb)
+        | bb::south(bb)
+        | bb::west(bb & !bb::FILE_A_MASK)
+        | bb::east(bb & !bb::FILE_H_MASK)
+        | bb::no_we(bb & !bb::FILE_A_MASK)
+        | bb::no_ea(bb & !bb::FILE_H_MASK)
+        | bb::so_we(bb & !bb::FILE_A_MASK)
+        | bb::so_ea(bb & !bb::FILE_H_MASK)
+}
+
+/// squares attacked by the pawns in `pawns` of `color`
+#[inline(always)]
+fn pawn_span(pawns: bb::Bitboard, color: color::Color) -> bb::Bitboard {
+    match color {
+        color::WHITE => bb::no_we(pawns & !bb::FILE_A_MASK) | bb::no_ea(pawns & !bb::FILE_H_MASK),
+        _ => bb::so_we(pawns & !bb::FILE_A_MASK) | bb::so_ea(pawns & !bb::FILE_H_MASK),
+    }
+}
+
+/// number of king moves between two squares
+#[inline(always)]
+fn distance(a: pos::Square, b: pos::Square) -> isize {
+    let (file_a, rank_a) = pos::make_tuple(a);
+    let (file_b, rank_b) = pos::make_tuple(b);
+
+    (file_a - file_b).abs().max((rank_a - rank_b).abs())
+}
+
+fn blocked_pawns(pos: &pos::Position) -> bool {
+    let white_pawns = pos.piece_bb(piece::WHITE_PAWN);
+    let black_pawns = pos.piece_bb(piece::BLACK_PAWN);
+    let kings = pos.piece_bb(piece::WHITE_KING) | pos.piece_bb(piece::BLACK_KING);
+
+    if white_pawns == bb::EMPTY || pos.occupied_bb() != white_pawns | black_pawns | kings {
+        return false;
+    }
+
+    let white_attacks = pawn_span(white_pawns, color::WHITE);
+    let black_attacks = pawn_span(black_pawns, color::BLACK);
+
+    // every pawn has to be stuck behind an enemy pawn, with nothing to capture
+    if bb::north(white_pawns) & !black_pawns != bb::EMPTY
+        || bb::south(black_pawns) & !white_pawns != bb::EMPTY
+        || white_attacks & black_pawns != bb::EMPTY
+        || black_attacks & white_pawns != bb::EMPTY
+    {
+        return false;
+    }
+
+    for (side, enemy_attacks, enemy_pawns) in [
+        (color::WHITE, black_attacks, black_pawns),
+        (color::BLACK, white_attacks, white_pawns),
+    ] {
+        let walkable = !(white_pawns | black_pawns) & !enemy_attacks;
+        let mut region = pos.piece_bb(piece::KING | side);
+
+        // flood fill every square the king can walk to without stepping into a pawn's attack
+        loop {
+            let next = (region | king_span(region)) & walkable;
+            if next == region {
+                break;
+            }
+            region = next;
+        }
+
+        let undefended = enemy_pawns
+            & !match side {
+                color::WHITE => black_attacks,
+                _ => white_attacks,
+            };
+
+        if king_span(region) & undefended != bb::EMPTY {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn wrong_rook_pawn(pos: &pos::Position, strong: color::Color) -> Option<Confidence> {
+    let weak = color::other(strong);
+    let pawns = pos.piece_bb(piece::PAWN | strong);
+    let bishops = pos.piece_bb(piece::BISHOP | strong);
+
+    if pawns == bb::EMPTY
+        || pos.color_bb(weak) != pos.piece_bb(piece::KING | weak)
+        || pos.color_bb(strong) != pawns | bishops | pos.piece_bb(piece::KING | strong)
+    {
+        return None;
+    }
+
+    let file = if pawns & !bb::FILE_A_MASK == bb::EMPTY {
+        pos::FILE_A
+    } else if pawns & !bb::FILE_H_MASK == bb::EMPTY {
+        pos::FILE_H
+    } else {
+        return None;
+    };
+
+    let promo_sq = pos::make_sq(
+        file,
+        match strong {
+            color::WHITE => pos::RANK_8,
+            _ => pos::RANK_1,
+        },
+    );
+
+    let promo_color = if bb::LIGHT_SQUARES & bb::square_bb(promo_sq) != bb::EMPTY {
+        bb::LIGHT_SQUARES
+    } else {
+        bb::DARK_SQUARES
+    };
+
+    // a bishop that controls the promotion square wins
+    if bishops & promo_color != bb::EMPTY {
+        return None;
+    }
+
+    let weak_king = pos.piece_bb(piece::KING | weak).serialize_once();
+
+    if distance(weak_king, promo_sq) > 1 {
+        return None;
+    }
+
+    if bishops != bb::EMPTY {
+        Some(Confidence::High)
+    } else {
+        // rook pawns without a bishop are drawn with the king in the corner,
+        // unless the defending king gets shouldered out before it's settled
+        Some(Confidence::Medium)
+    }
+}
+
+/// something that happened in a game, fed to `Adjudicator::push()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Event {
+    /// the side to move played a move
+    Move(moves::Move),
+    /// the time `side` has left on its clock, a side with no time left loses
+    /// unless the opponent can't possibly checkmate
+    Clock {
+        side: color::Color,
+        remaining: Duration,
+    },
+    /// `side` claims a draw by threefold repetition or the 50 move rule
+    ClaimDraw(color::Color),
+    /// both sides agreed to a draw
+    DrawAgreed,
+    /// `side` offers a draw, which stays open until the opponent accepts or declines it,
+    /// or declines it implicitly by making a move (FIDE rule 9.1.2)
+    OfferDraw(color::Color),
+    /// `side` accepts the draw offered by the opponent
+    AcceptDraw(color::Color),
+    /// `side` declines the draw offered by the opponent
+    DeclineDraw(color::Color),
+    /// `side` resigned
+    Resign(color::Color),
+    /// `side` left the game or stopped responding
+    Abandon(color::Color),
+}
+
+/// how a finished game ended
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Verdict {
+    /// the side that won, or `None` if the game is drawn
+    pub winner: Option<color::Color>,
+    pub termination: termination::Termination,
+}
+
+/// the status of a game after an event
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Status {
+    Ongoing,
+    /// the game goes on, but the side to move may claim a draw for this reason,
+    /// only reported when `AdjudicatorOptions::claims_required` is set
+    DrawClaimable(termination::Termination),
+    Finished(Verdict),
+}
+
+/// settings for an `Adjudicator`, by default draws don't have to be claimed
+/// and nothing is adjudicated
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct AdjudicatorOptions {
+    /// whether threefold repetition and the 50 move rule have to be claimed with `Event::ClaimDraw`,
+    /// otherwise they end the game right away like `Position::outcome()` does,
+    /// fivefold repetition and the 75 move rule always end the game
+    pub claims_required: bool,
+    /// ends the game as a draw once `adjudication::likely_draw()` reports a draw
+    /// with at least this confidence, `None` to never adjudicate
+    pub likely_draw: Option<Confidence>,
+}
+
+/// error returned by `Adjudicator::push()`, the event is ignored
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AdjudicationError {
+    /// the start position of a game log isn't a valid FEN, only returned by `GameLog::replay()`
+    InvalidFen,
+    /// the game already finished
+    GameOver,
+    /// the move isn't legal in the current position
+    IllegalMove(moves::Move),
+    /// a draw was claimed, but neither repetition nor the 50 move rule apply
+    InvalidClaim,
+    /// a draw offer was accepted or declined, but the opponent hasn't offered one
+    NoDrawOffer,
+}
+
+/// keeps track of a single game and decides when and how it ends,
+/// combining `Position::outcome()`, draw claims, `adjudication::likely_draw()` and clock forfeits
+///
+/// meant to be run for every live game on a server, pushing an event doesn't allocate
+/// apart from the position's history growing with every move
+pub struct Adjudicator {
+    pos: pos::Position,
+    options: AdjudicatorOptions,
+    status: Status,
+    draw_offer: Option<color::Color>,
+}
+
+impl Adjudicator {
+    /// creates an adjudicator for a game starting at `pos`, which may already be over
+    pub fn new(
+        pos: pos::Position,
+        options: AdjudicatorOptions,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Self {
+        let mut adjudicator = Adjudicator {
+            pos,
+            options,
+            status: Status::Ongoing,
+            draw_offer: None,
+        };
+
+        adjudicator.status = adjudicator.board_status(masks, zb);
+
+        adjudicator
+    }
+
+    /// the current position of the game
+    #[inline(always)]
+    pub fn position(&self) -> &pos::Position {
+        &self.pos
+    }
+
+    /// the status after the last event
+    #[inline(always)]
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// the side whose draw offer is still open, if any
+    #[inline(always)]
+    pub fn draw_offer(&self) -> Option<color::Color> {
+        self.draw_offer
+    }
+
+    /// the verdict if the game is over
+    #[inline(always)]
+    pub fn verdict(&self) -> Option<Verdict> {
+        match self.status {
+            Status::Finished(verdict) => Some(verdict),
+            _ => None,
+        }
+    }
+
+    /// applies an event to the game and returns the new status
+    pub fn push(
+        &mut self,
+        event: Event,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Result<Status, AdjudicationError> {
+        if let Status::Finished(_) = self.status {
+            return Err(AdjudicationError::GameOver);
+        }
+
+        let finish = |winner, termination| {
+            Status::Finished(Verdict {
+                winner,
+                termination,
+            })
+        };
+
+        self.status = match event {
+            Event::Move(mov) => {
+                if !self.pos.is_legal(mov, masks, zb) {
+                    return Err(AdjudicationError::IllegalMove(mov));
+                }
+
+                // moving instead of answering declines the opponent's offer,
+                // an offer made before moving stays open for the opponent
+                if self.draw_offer == Some(color::other(self.pos.side_to_move())) {
+                    self.draw_offer = None;
+                }
+
+                self.pos.make_move(mov, zb);
+                self.board_status(masks, zb)
+            }
+            Event::Clock { side, remaining } => {
+                if !remaining.is_zero() {
+                    return Ok(self.status);
+                }
+
+                let opponent = color::other(side);
+
+                if self.pos.insufficient_material(opponent) {
+                    finish(None, termination::Termination::InsufficientMaterial)
+                } else {
+                    finish(Some(opponent), termination::Termination::TimeForfeit)
+                }
+            }
+            Event::ClaimDraw(_) => match self.status {
+                Status::DrawClaimable(termination) => finish(None, termination),
+                _ => return Err(AdjudicationError::InvalidClaim),
+            },
+            Event::DrawAgreed => finish(None, termination::Termination::Agreement),
+            // offering a draw while the opponent's offer is open accepts it
+            Event::OfferDraw(side) | Event::AcceptDraw(side)
+                if self.draw_offer == Some(color::other(side)) =>
+            {
+                finish(None, termination::Termination::Agreement)
+            }
+            Event::OfferDraw(side) => {
+                self.draw_offer = Some(side);
+                self.status
+            }
+            Event::DeclineDraw(side) if self.draw_offer == Some(color::other(side)) => {
+                self.draw_offer = None;
+                self.status
+            }
+            Event::AcceptDraw(_) | Event::DeclineDraw(_) => {
+                return Err(AdjudicationError::NoDrawOffer);
+            }
+            Event::Resign(side) => finish(
+                Some(color::other(side)),
+                termination::Termination::Resignation,
+            ),
+            Event::Abandon(side) => finish(
+                Some(color::other(side)),
+                termination::Termination::Abandonment,
+            ),
+        };
+
+        Ok(self.status)
+    }
+
+    /// the status of the game as decided by the board alone
+    fn board_status(&mut self, masks: &AttackMasks, zb: &ZobristValues) -> Status {
+        if let Some(outcome) = self.pos.outcome(masks, zb) {
+            return match outcome {
+                // fivefold repetition and the 75 move rule don't have to be claimed
+                pos::Outcome::Repetition | pos::Outcome::FiftyMoveRule
+                    if self.options.claims_required
+                        && !self.pos.is_5_rep()
+                        && !self.pos.is_75_move_rule() =>
+                {
+                    Status::DrawClaimable(outcome.termination())
+                }
+                _ => Status::Finished(Verdict {
+                    winner: outcome.winner(),
+                    termination: outcome.termination(),
+                }),
+            };
+        }
+
+        if let Some(min) = self.options.likely_draw
+            && likely_draw(&self.pos).is_some_and(|draw| draw.confidence >= min)
+        {
+            return Status::Finished(Verdict {
+                winner: None,
+                termination: termination::Termination::AdjudicatedDraw,
+            });
+        }
+
+        Status::Ongoing
+    }
+}
+
+impl fmt::Display for AdjudicationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdjudicationError::InvalidFen => write!(f, "the game doesn't start with a valid fen"),
+            AdjudicationError::GameOver => write!(f, "the game is already over"),
+            AdjudicationError::IllegalMove(mov) => write!(f, "illegal move {}", mov.to_uci_fmt()),
+            AdjudicationError::InvalidClaim => write!(f, "there is no draw to claim"),
+            AdjudicationError::NoDrawOffer => write!(f, "there is no draw offer to answer"),
+        }
+    }
+}
+
+impl std::error::Error for AdjudicationError {}
@@ -0,0 +1,132 @@
+// file for chess960 starting positions, both the symmetric ones and double fischer random chess (DFRC),
+// where white's and black's back ranks are chosen independently
+
+use crate::{color, piece, pos};
+
+/// the number of chess960 back ranks, numbered from 0 to 959 (Scharnagl numbering)
+pub const NUM_POSITIONS: u16 = 960;
+
+/// the number of the standard starting position, `RNBQKBNR`
+pub const STANDARD_INDEX: u16 = 518;
+
+/// pairs of empty squares the knights are placed on, counting only the 5 squares left
+/// after placing the bishops and the queen
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// returns the piece types of back rank number `index`, from the a file to the h file, without colors
+///
+/// panics if `index` isn't less than `NUM_POSITIONS`
+pub fn back_rank(index: u16) -> [piece::Piece; 8] {
+    assert!(index < NUM_POSITIONS, "no chess960 position {index}");
+
+    let mut rank = [piece::NONE; 8];
+    let mut n = index as usize;
+
+    rank[(n % 4) * 2 + 1] = piece::BISHOP;
+    n /= 4;
+    rank[(n % 4) * 2] = piece::BISHOP;
+    n /= 4;
+
+    let empty = |rank: &[piece::Piece; 8], nth: usize| {
+        (0..8)
+            .filter(|&file| rank[file] == piece::NONE)
+            .nth(nth)
+            .unwrap()
+    };
+
+    let queen = empty(&rank, n % 6);
+    rank[queen] = piece::QUEEN;
+    n /= 6;
+
+    // the second knight goes first, so the index of the first one isn't shifted
+    let (a, b) = KNIGHTS[n];
+    let second = empty(&rank, b);
+    rank[second] = piece::KNIGHT;
+    let first = empty(&rank, a);
+    rank[first] = piece::KNIGHT;
+
+    for kind in [piece::ROOK, piece::KING, piece::ROOK] {
+        let square = empty(&rank, 0);
+        rank[square] = kind;
+    }
+
+    rank
+}
+
+/// returns the number of a back rank, see `chess960::back_rank()`,
+/// `None` if the pieces aren't a valid chess960 back rank
+pub fn index_of(rank: &[piece::Piece; 8]) -> Option<u16> {
+    (0..NUM_POSITIONS).find(|&index| back_rank(index) == *rank)
+}
+
+/// returns the FEN string of chess960 position number `index`, where both sides have the same back rank
+///
+/// `chess960::fen(chess960::STANDARD_INDEX)` is the standard starting position
+pub fn fen(index: u16) -> String {
+    dfrc_fen(index, index)
+}
+
+/// returns the FEN string of the double fischer random chess position with back rank number `white`
+/// for white and `black` for black, both sides can castle with both rooks
+///
+/// castling rights are written as `KQkq`, which is unambiguous here since each side
+/// only has one rook on either side of its king
+pub fn dfrc_fen(white: u16, black: u16) -> String {
+    let rank_str = |index: u16, side: color::Color| {
+        back_rank(index)
+            .iter()
+            .map(|&kind| piece::as_char(kind | side))
+            .collect::<String>()
+    };
+
+    format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+        rank_str(black, color::BLACK),
+        rank_str(white, color::WHITE)
+    )
+}
+
+/// returns the back rank numbers of white and black if `pos` has the piece placement of a
+/// chess960 or DFRC starting position, otherwise `None`
+pub fn start_indices(pos: &pos::Position) -> Option<(u16, u16)> {
+    let rank_of = |rank: pos::Rank, side: color::Color| {
+        let mut kinds = [piece::NONE; 8];
+
+        for file in pos::FILE_A..=pos::FILE_H {
+            let p = pos.piece_on_fr(file, rank);
+
+            if p == piece::NONE || color::of(p) != side {
+                return None;
+            }
+
+            kinds[file as usize] = piece::of(p);
+        }
+
+        Some(kinds)
+    };
+
+    let pawns = (pos::FILE_A..=pos::FILE_H).all(|file| {
+        pos.piece_on_fr(file, pos::RANK_2) == piece::WHITE_PAWN
+            && pos.piece_on_fr(file, pos::RANK_7) == piece::BLACK_PAWN
+    });
+
+    if !pawns || pos.occupied_bb().count_ones() != 32 {
+        return None;
+    }
+
+    Some((
+        index_of(&rank_of(pos::RANK_1, color::WHITE)?)?,
+        index_of(&rank_of(pos::RANK_8, color::BLACK)?)?,
+    ))
+}
@@ -0,0 +1,202 @@
+// file for preparing large collections of positions, e.g. training data for evaluation tuning
+
+use std::{
+    collections::HashSet,
+    fs,
+    hash::{BuildHasherDefault, Hasher},
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use crate::{AttackMasks, ZobristValues, epd, moves, pos, zobrist};
+
+/// options for `dataset::normalize()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct NormalizeOptions {
+    /// keep the halfmove clock and fullmove number of lines that have them,
+    /// otherwise only the first four FEN fields are written
+    pub keep_clocks: bool,
+    /// only write the first line of every position, positions are compared by zobrist key
+    pub dedup: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        NormalizeOptions {
+            keep_clocks: false,
+            dedup: true,
+        }
+    }
+}
+
+/// line counts returned by `dataset::normalize()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct NormalizeStats {
+    /// number of non-empty lines read
+    pub read: u64,
+    /// number of lines written
+    pub written: u64,
+    /// number of lines skipped because their position was already written
+    pub duplicates: u64,
+    /// number of lines skipped because they don't start with a valid FEN string
+    pub invalid: u64,
+}
+
+/// normalizes a single line that starts with a FEN or EPD position, returning the normalized line
+/// and the key of its position, or `None` if the line doesn't start with a valid position
+///
+/// the en passant square is dropped unless an en passant capture is actually legal,
+/// castling rights without a matching rook are dropped, the clocks are dropped unless `keep_clocks` is true,
+/// and everything after the position (e.g. EPD operations or a game result) is kept, separated by single spaces
+pub fn normalize_line(
+    line: &str,
+    keep_clocks: bool,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> Option<(String, zobrist::Key)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+
+    if fields.len() < 4 || !epd::is_well_formed(&fields[..4]) {
+        return None;
+    }
+
+    let has_clocks = fields.len() >= 6
+        && fields[4].parse::<u8>().is_ok()
+        && fields[5].parse::<u16>().is_ok_and(|fullmove| fullmove > 0);
+
+    let (clocks, rest) = if has_clocks {
+        ((fields[4], fields[5]), &fields[6..])
+    } else {
+        (("0", "1"), &fields[4..])
+    };
+
+    let fen = format!(
+        "{} {} {} {} {} {}",
+        fields[0], fields[1], fields[2], fields[3], clocks.0, clocks.1
+    );
+    let mut pos = pos::Position::from_fen(&fen, zb);
+
+    if let Some(ep) = pos.ep_square()
+        && !(pos.ep_square_is_consistent(ep)
+            && moves::gen_legal(&mut pos, masks, zb)
+                .iter()
+                .any(|m| m.type_of() == moves::MoveType::EnPassant))
+    {
+        let fen = format!(
+            "{} {} {} - {} {}",
+            fields[0], fields[1], fields[2], clocks.0, clocks.1
+        );
+        pos = pos::Position::from_fen(&fen, zb);
+    }
+
+    let mut normalized = pos.to_fen();
+
+    if !(keep_clocks && has_clocks) {
+        // cut off the two clock fields
+        for _ in 0..2 {
+            normalized.truncate(normalized.rfind(' ').unwrap());
+        }
+    }
+
+    for field in rest {
+        normalized.push(' ');
+        normalized.push_str(field);
+    }
+
+    Some((normalized, pos.key()))
+}
+
+/// reads lines starting with FEN or EPD positions from `reader`, normalizes them with
+/// `dataset::normalize_line()`, and writes them to `writer`, skipping empty and invalid lines
+///
+/// deduplication keeps the keys of all written positions in memory (8 bytes each, plus overhead),
+/// distinct positions that happen to share a key are treated as duplicates
+pub fn normalize(
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+    options: NormalizeOptions,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> io::Result<NormalizeStats> {
+    let mut stats = NormalizeStats::default();
+    let mut seen = HashSet::with_hasher(BuildHasherDefault::<KeyHasher>::default());
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+
+        let Ok(line) = std::str::from_utf8(&buf) else {
+            stats.read += 1;
+            stats.invalid += 1;
+            continue;
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        stats.read += 1;
+
+        let Some((normalized, key)) = normalize_line(line, options.keep_clocks, masks, zb) else {
+            stats.invalid += 1;
+            continue;
+        };
+
+        if options.dedup && !seen.insert(key) {
+            stats.duplicates += 1;
+            continue;
+        }
+
+        writer.write_all(normalized.as_bytes())?;
+        writer.write_all(b"\n")?;
+        stats.written += 1;
+    }
+
+    writer.flush()?;
+
+    Ok(stats)
+}
+
+/// same as `dataset::normalize()`, but reads from and writes to files
+pub fn normalize_file(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    options: NormalizeOptions,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> io::Result<NormalizeStats> {
+    normalize(
+        io::BufReader::with_capacity(1 << 20, fs::File::open(input)?),
+        io::BufWriter::with_capacity(1 << 20, fs::File::create(output)?),
+        options,
+        masks,
+        zb,
+    )
+}
+
+/// zobrist keys are already uniformly distributed, so they're used as their own hash
+#[derive(Default)]
+struct KeyHasher(u64);
+
+impl Hasher for KeyHasher {
+    #[inline(always)]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline(always)]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 << 8) | byte as u64;
+        }
+    }
+
+    #[inline(always)]
+    fn write_u64(&mut self, key: u64) {
+        self.0 = key;
+    }
+}
@@ -0,0 +1,222 @@
+// file for read-only position databases stored on disk and probed through a memory map
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use memmap2::Mmap;
+
+use crate::{color, piece, pos, zobrist};
+
+/// bytes at the start of every database file
+pub const MAGIC: &[u8; 4] = b"LCDB";
+
+/// version of the file format written by `PositionDbBuilder`
+pub const VERSION: u32 = 1;
+
+/// size of the header: magic, version (u32) and number of records (u64)
+pub const HEADER_SIZE: usize = 16;
+
+/// size of a single record: key (u64), packed board (32 bytes), packed state (u16) and metadata (u64)
+pub const RECORD_SIZE: usize = 8 + PACKED_SIZE + 8;
+
+/// size of a packed position, 4 bits per square plus 2 bytes for the side to move,
+/// castling rights and en passant file
+pub const PACKED_SIZE: usize = 34;
+
+/// a position packed into a fixed amount of bytes, used to tell apart positions that share a key
+pub type PackedPosition = [u8; PACKED_SIZE];
+
+/// packs the piece placement, side to move, castling rights and en passant file of `pos`
+pub fn pack(pos: &pos::Position) -> PackedPosition {
+    let mut packed = [0; PACKED_SIZE];
+
+    for sq in 0..64 {
+        packed[sq / 2] |= piece::to_code(pos.piece_on(sq)) << (4 * (sq % 2));
+    }
+
+    let mut state: u16 = 0;
+
+    if pos.side_to_move() == color::BLACK {
+        state |= 1;
+    }
+
+    for (i, right) in [
+        pos::WK_CASTLE,
+        pos::WQ_CASTLE,
+        pos::BK_CASTLE,
+        pos::BQ_CASTLE,
+    ]
+    .iter()
+    .enumerate()
+    {
+        if pos.castle_rights() & right != 0 {
+            state |= 1 << (1 + i);
+        }
+    }
+
+    if let Some(square) = pos.ep_square() {
+        state |= (pos::file_of(square) as u16 + 1) << 5;
+    }
+
+    packed[32..].copy_from_slice(&state.to_le_bytes());
+
+    packed
+}
+
+/// collects positions with metadata in memory and writes them out as a database file
+///
+/// the keys stored in the file are the positions' zobrist keys, so a database can only be probed
+/// with positions hashed using the same zobrist values it was built with
+/// (see `libchess::init_with_seed()`)
+#[derive(Debug, Clone, Default)]
+pub struct PositionDbBuilder {
+    entries: BTreeMap<(zobrist::Key, PackedPosition), u64>,
+}
+
+impl PositionDbBuilder {
+    pub fn new() -> Self {
+        PositionDbBuilder {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// adds a position with some metadata (e.g. packed game statistics or a tablebase result),
+    /// replacing the metadata if the position was already added
+    pub fn insert(&mut self, pos: &pos::Position, metadata: u64) {
+        self.entries.insert((pos.key(), pack(pos)), metadata);
+    }
+
+    /// the number of distinct positions added
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// returns true if no positions have been added
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// writes all positions sorted by key to `writer`
+    pub fn write_to(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+
+        for ((key, packed), metadata) in &self.entries {
+            writer.write_all(&key.to_le_bytes())?;
+            writer.write_all(packed)?;
+            writer.write_all(&metadata.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// writes all positions sorted by key to a file at `path`
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        self.write_to(&mut writer)?;
+        io::Write::flush(&mut writer)
+    }
+}
+
+/// a read-only database of positions, memory mapped so that only the pages touched by probes
+/// are ever loaded into memory
+pub struct PositionDb {
+    mmap: Mmap,
+    len: usize,
+}
+
+impl PositionDb {
+    /// opens a database file written by `PositionDbBuilder`
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+
+        // SAFETY: the file is only ever read through the map, modifying or truncating it
+        // while a `PositionDb` is open is not supported
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        if mmap.len() < HEADER_SIZE || &mmap[0..4] != MAGIC {
+            return Err(invalid("not a position database"));
+        }
+
+        if u32::from_le_bytes(mmap[4..8].try_into().unwrap()) != VERSION {
+            return Err(invalid("unsupported position database version"));
+        }
+
+        let len = u64::from_le_bytes(mmap[8..16].try_into().unwrap()) as usize;
+
+        if mmap.len() != HEADER_SIZE + len * RECORD_SIZE {
+            return Err(invalid("position database is truncated"));
+        }
+
+        Ok(PositionDb { mmap, len })
+    }
+
+    /// the number of positions in the database
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// returns true if the database contains no positions
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// returns the metadata stored for `pos`, if the database contains it
+    pub fn probe(&self, pos: &pos::Position) -> Option<u64> {
+        let packed = pack(pos);
+
+        (self.lower_bound(pos.key())..self.len)
+            .take_while(|&i| self.key_at(i) == pos.key())
+            .find(|&i| self.packed_at(i) == packed)
+            .map(|i| self.metadata_at(i))
+    }
+
+    /// returns the metadata of every position stored under `key`,
+    /// more than one value is only returned when different positions share a key
+    pub fn probe_key(&self, key: zobrist::Key) -> Vec<u64> {
+        (self.lower_bound(key)..self.len)
+            .take_while(|&i| self.key_at(i) == key)
+            .map(|i| self.metadata_at(i))
+            .collect()
+    }
+
+    /// index of the first record with a key not less than `key`
+    fn lower_bound(&self, key: zobrist::Key) -> usize {
+        let (mut lo, mut hi) = (0, self.len);
+
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+
+            if self.key_at(mid) < key {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        lo
+    }
+
+    fn record(&self, i: usize) -> &[u8] {
+        let start = HEADER_SIZE + i * RECORD_SIZE;
+        &self.mmap[start..start + RECORD_SIZE]
+    }
+
+    fn key_at(&self, i: usize) -> zobrist::Key {
+        u64::from_le_bytes(self.record(i)[0..8].try_into().unwrap())
+    }
+
+    fn packed_at(&self, i: usize) -> &[u8] {
+        &self.record(i)[8..8 + PACKED_SIZE]
+    }
+
+    fn metadata_at(&self, i: usize) -> u64 {
+        u64::from_le_bytes(self.record(i)[8 + PACKED_SIZE..].try_into().unwrap())
+    }
+}
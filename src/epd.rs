@@ -0,0 +1,334 @@
+// file for reading and writing extended position descriptions (EPD), a position followed by operations,
+// e.g. `r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5; id "test 1";`
+
+use std::{fmt, str::FromStr};
+
+use crate::{AttackMasks, ZobristValues, moves, pos};
+
+/// an error found while parsing EPD
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EpdError {
+    /// the first four fields aren't a valid position
+    InvalidPosition,
+    /// an opcode that doesn't start with a letter, or has characters other than letters, digits and underscores
+    InvalidOpcode(String),
+    /// a string operand without a closing quote
+    UnterminatedString,
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpdError::InvalidPosition => write!(f, "invalid position in EPD"),
+            EpdError::InvalidOpcode(opcode) => write!(f, "invalid EPD opcode {opcode:?}"),
+            EpdError::UnterminatedString => write!(f, "unterminated string in EPD"),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+/// a position and its operations, an opcode followed by its operands
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Epd {
+    /// the position, with the clocks of the `hmvc` and `fmvn` operations if there are any
+    pub pos: pos::Position,
+    /// the operations in the order they're written
+    pub ops: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    /// an EPD record of `pos` without any operations
+    pub fn new(pos: pos::Position) -> Self {
+        Epd {
+            pos,
+            ops: Vec::new(),
+        }
+    }
+
+    /// parses a single EPD record
+    ///
+    /// the clocks of a full FEN string are accepted between the position and the operations,
+    /// as are operations starting with a `;`, so perft suites like `<fen> 0 1 ;D1 20 ;D2 400` can be read
+    pub fn parse(epd: &str, zb: &ZobristValues) -> Result<Self, EpdError> {
+        let mut fields = Vec::new();
+        let mut rest = epd.trim();
+
+        while fields.len() < 6 {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+            if field.is_empty() {
+                break;
+            }
+
+            // the two clocks are the only fields after the position that start with a digit
+            let field = match fields.len() {
+                0..4 => field,
+                _ => match field.trim_end_matches(';') {
+                    clock if clock.parse::<u16>().is_ok() => clock,
+                    _ => break,
+                },
+            };
+
+            fields.push(field);
+            rest = tail.trim_start();
+        }
+
+        if fields.len() < 4 || !is_well_formed(&fields[..4]) {
+            return Err(EpdError::InvalidPosition);
+        }
+
+        let ops = parse_ops(rest)?;
+        let op = |opcode| {
+            ops.iter()
+                .find(|(op, _): &&(String, Vec<String>)| op == opcode)
+                .and_then(|(_, operands)| operands.first())
+                .map(String::as_str)
+        };
+
+        let rule50 = op("hmvc")
+            .or(fields.get(4).copied())
+            .and_then(|clock| clock.parse::<u8>().ok())
+            .unwrap_or(0);
+
+        let fullmove = op("fmvn")
+            .or(fields.get(5).copied())
+            .and_then(|clock| clock.parse::<u16>().ok())
+            .filter(|&fullmove| fullmove > 0)
+            .unwrap_or(1);
+
+        let fen = format!("{} {rule50} {fullmove}", fields[..4].join(" "));
+
+        Ok(Epd {
+            pos: pos::Position::from_fen(&fen, zb),
+            ops,
+        })
+    }
+
+    /// writes the record as EPD, the position without clocks followed by the operations,
+    /// operands with spaces or semicolons are written as strings
+    pub fn to_epd(&self) -> String {
+        let fen = self.pos.to_fen();
+        let mut epd = fen.split(' ').take(4).collect::<Vec<&str>>().join(" ");
+
+        for (opcode, operands) in &self.ops {
+            epd.push(' ');
+            epd.push_str(opcode);
+
+            for operand in operands {
+                epd.push(' ');
+
+                if operand.is_empty() || operand.contains([' ', '\t', ';', '"']) {
+                    epd.push_str(&format!("\"{}\"", operand.replace('"', "'")));
+                } else {
+                    epd.push_str(operand);
+                }
+            }
+
+            epd.push(';');
+        }
+
+        epd
+    }
+
+    /// the operands of the operation `opcode`, if the record has it
+    pub fn op(&self, opcode: &str) -> Option<&[String]> {
+        self.ops
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// the first operand of `opcode` parsed as `T`, e.g. `epd.op_value::<i64>("D5")` in a perft suite
+    pub fn op_value<T: FromStr>(&self, opcode: &str) -> Option<T> {
+        self.op(opcode)?.first()?.parse().ok()
+    }
+
+    /// sets the operands of `opcode`, replacing the old ones if the record already has the operation
+    pub fn set_op(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.ops.iter_mut().find(|(op, _)| op == opcode) {
+            Some((_, old)) => *old = operands,
+            None => self.ops.push((opcode.to_string(), operands)),
+        }
+    }
+
+    /// removes the operation `opcode`, returning its operands
+    pub fn remove_op(&mut self, opcode: &str) -> Option<Vec<String>> {
+        let i = self.ops.iter().position(|(op, _)| op == opcode)?;
+
+        Some(self.ops.remove(i).1)
+    }
+
+    /// the `id` operation, the name of the record
+    pub fn id(&self) -> Option<&str> {
+        self.op("id")?.first().map(String::as_str)
+    }
+
+    /// the moves of the `bm` (best move) operation
+    pub fn best_moves(
+        &self,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Result<Vec<moves::Move>, moves::SanError> {
+        self.san_moves("bm", masks, zb)
+    }
+
+    /// the moves of the `am` (avoid move) operation
+    pub fn avoid_moves(
+        &self,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Result<Vec<moves::Move>, moves::SanError> {
+        self.san_moves("am", masks, zb)
+    }
+
+    /// the `dm` (direct mate) operation, the number of moves to mate
+    pub fn direct_mate(&self) -> Option<u32> {
+        self.op_value("dm")
+    }
+
+    /// the `ce` (centipawn evaluation) operation, from the side to move's point of view
+    pub fn centipawn_eval(&self) -> Option<i32> {
+        self.op_value("ce")
+    }
+
+    /// the perft results of the `D1`, `D2`, ... operations of a perft suite, as (depth, nodes), ordered by depth
+    pub fn perft_results(&self) -> Vec<(i32, i64)> {
+        let mut results: Vec<(i32, i64)> = self
+            .ops
+            .iter()
+            .filter_map(|(op, operands)| {
+                let depth = op.strip_prefix('D')?.parse().ok()?;
+                let nodes = operands.first()?.parse().ok()?;
+
+                Some((depth, nodes))
+            })
+            .collect();
+
+        results.sort_unstable();
+        results
+    }
+
+    fn san_moves(
+        &self,
+        opcode: &str,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Result<Vec<moves::Move>, moves::SanError> {
+        let mut pos = self.pos.clone();
+
+        self.op(opcode)
+            .unwrap_or_default()
+            .iter()
+            .map(|san| moves::Move::from_san(san, &mut pos, masks, zb))
+            .collect()
+    }
+}
+
+impl fmt::Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_epd())
+    }
+}
+
+/// parses the operations after the position, each one ends with a `;`, except the last one may not
+fn parse_ops(mut rest: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut ops = Vec::new();
+
+    loop {
+        rest = rest.trim_start_matches(|ch: char| ch.is_whitespace() || ch == ';');
+
+        if rest.is_empty() {
+            return Ok(ops);
+        }
+
+        let end = rest
+            .find(|ch: char| ch.is_whitespace() || ch == ';')
+            .unwrap_or(rest.len());
+        let opcode = &rest[..end];
+
+        if !opcode.starts_with(|ch: char| ch.is_ascii_alphabetic())
+            || !opcode
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+        {
+            return Err(EpdError::InvalidOpcode(opcode.to_string()));
+        }
+
+        rest = &rest[end..];
+
+        let mut operands = Vec::new();
+
+        loop {
+            rest = rest.trim_start();
+
+            if rest.is_empty() || rest.starts_with(';') {
+                break;
+            }
+
+            if let Some(string) = rest.strip_prefix('"') {
+                let end = string.find('"').ok_or(EpdError::UnterminatedString)?;
+
+                operands.push(string[..end].to_string());
+                rest = &string[end + 1..];
+            } else {
+                let end = rest
+                    .find(|ch: char| ch.is_whitespace() || ch == ';')
+                    .unwrap_or(rest.len());
+
+                operands.push(rest[..end].to_string());
+                rest = &rest[end..];
+            }
+        }
+
+        ops.push((opcode.to_string(), operands));
+    }
+}
+
+/// checks the first four FEN fields well enough that `Position::from_fen()` won't panic on them,
+/// and that both sides have exactly one king and no pawns on the back ranks (move generation panics on those)
+pub(crate) fn is_well_formed(fields: &[&str]) -> bool {
+    let ranks: Vec<&str> = fields[0].split('/').collect();
+
+    if ranks.len() != 8
+        || [ranks[0], ranks[7]]
+            .iter()
+            .any(|rank| rank.contains(['p', 'P']))
+    {
+        return false;
+    }
+
+    for rank in ranks {
+        let mut files = 0;
+
+        for ch in rank.chars() {
+            match ch {
+                '1'..='8' => files += ch as u32 - '0' as u32,
+                'p' | 'n' | 'b' | 'r' | 'q' | 'k' | 'P' | 'N' | 'B' | 'R' | 'Q' | 'K' => files += 1,
+                _ => return false,
+            }
+        }
+
+        if files != 8 {
+            return false;
+        }
+    }
+
+    let kings = |king| fields[0].chars().filter(|&ch| ch == king).count();
+
+    if kings('K') != 1 || kings('k') != 1 {
+        return false;
+    }
+
+    let castling_ok = fields[2] == "-"
+        || (fields[2].len() <= 4
+            && fields[2]
+                .chars()
+                .all(|ch| matches!(ch, 'K' | 'Q' | 'k' | 'q' | 'A'..='H' | 'a'..='h')));
+
+    let ep = fields[3].as_bytes();
+    let ep_ok = fields[3] == "-"
+        || (ep.len() == 2 && (b'a'..=b'h').contains(&ep[0]) && matches!(ep[1], b'3' | b'6'));
+
+    matches!(fields[1], "w" | "b") && castling_ok && ep_ok
+}
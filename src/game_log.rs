@@ -0,0 +1,224 @@
+// file for append-only logs of the events of a live game, which can be replayed to restore the game
+
+use std::{fmt, io, time::Duration};
+
+use crate::{
+    AttackMasks, ZobristValues,
+    adjudication::{self, Event},
+    color, moves, pos,
+};
+
+/// a single event of a game, and when it happened
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct LogEntry {
+    /// time since the game started
+    pub elapsed: Duration,
+    pub event: Event,
+}
+
+/// the events of a game, in the order they happened
+///
+/// as text, the first line is `fen <FEN>` with the position the game started from,
+/// followed by a line for every event in the format `<milliseconds> <event>`, where the event is one of
+/// `move <uci move>`, `clock <w or b> <milliseconds left>`, `claim <w or b>`, `agree`,
+/// `offer <w or b>`, `accept <w or b>`, `decline <w or b>`, `resign <w or b>` or `abandon <w or b>`,
+/// e.g. `"1520 move e2e4"`
+///
+/// every entry is a single line, so a log can be written to a file one event at a time,
+/// and a line cut off by a crash is simply ignored when the log is read back
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct GameLog {
+    pub start_fen: String,
+    pub entries: Vec<LogEntry>,
+}
+
+/// error returned when parsing a game log from text fails
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ParseGameLogError {
+    /// the first line isn't `fen` followed by a valid FEN string
+    InvalidFen,
+    /// a line isn't a valid entry, contains the line number
+    InvalidEntry(usize),
+    /// a move isn't legal in the position it was played in, contains the line number
+    IllegalMove(usize),
+}
+
+impl GameLog {
+    pub fn new(start_fen: &str) -> Self {
+        GameLog {
+            start_fen: start_fen.to_string(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// adds an event to the end of the log
+    pub fn push(&mut self, elapsed: Duration, event: Event) {
+        self.entries.push(LogEntry { elapsed, event });
+    }
+
+    /// writes the `fen` line that starts a log, meant for starting a log file
+    /// that entries are then appended to with `GameLog::write_entry()`
+    pub fn write_header(writer: &mut impl io::Write, start_fen: &str) -> io::Result<()> {
+        writeln!(writer, "fen {start_fen}")?;
+        writer.flush()
+    }
+
+    /// appends a single entry to `writer` and flushes it, so the entry survives a crash right after
+    pub fn write_entry(writer: &mut impl io::Write, entry: &LogEntry) -> io::Result<()> {
+        writeln!(writer, "{entry}")?;
+        writer.flush()
+    }
+
+    /// parses a log written by `GameLog::write_header()` and `GameLog::write_entry()`, or by `Display`
+    ///
+    /// moves are checked for legality while parsing, since they can only be read in the position
+    /// they were played in, a last line without a newline is ignored as it may have been cut off
+    pub fn parse(
+        text: &str,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Result<GameLog, ParseGameLogError> {
+        let complete = &text[..text.rfind('\n').map_or(0, |i| i + 1)];
+        let mut lines = complete.lines().enumerate();
+
+        let start_fen = lines
+            .next()
+            .and_then(|(_, line)| line.strip_prefix("fen "))
+            .map(str::trim)
+            .filter(|fen| pos::validate_fen(fen).is_ok())
+            .ok_or(ParseGameLogError::InvalidFen)?;
+
+        let mut log = GameLog::new(start_fen);
+        let mut pos = pos::Position::from_fen(&log.start_fen, zb);
+
+        for (i, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let err = ParseGameLogError::InvalidEntry(i + 1);
+            let mut parts = line.split_whitespace();
+
+            let millis: u64 = parts.next().and_then(|m| m.parse().ok()).ok_or(err)?;
+
+            let side = |part: Option<&str>| match part {
+                Some("w") => Ok(color::WHITE),
+                Some("b") => Ok(color::BLACK),
+                _ => Err(err),
+            };
+
+            let event = match parts.next() {
+                Some("move") => {
+                    let uci = parts.next().ok_or(err)?;
+                    let legal = moves::gen_legal(&mut pos, masks, zb);
+
+                    let Some(&mov) = legal.iter().find(|m| m.to_uci_fmt_for(&pos) == uci) else {
+                        return Err(ParseGameLogError::IllegalMove(i + 1));
+                    };
+
+                    pos.make_move(mov, zb);
+                    Event::Move(mov)
+                }
+                Some("clock") => Event::Clock {
+                    side: side(parts.next())?,
+                    remaining: Duration::from_millis(
+                        parts.next().and_then(|m| m.parse().ok()).ok_or(err)?,
+                    ),
+                },
+                Some("claim") => Event::ClaimDraw(side(parts.next())?),
+                Some("agree") => Event::DrawAgreed,
+                Some("offer") => Event::OfferDraw(side(parts.next())?),
+                Some("accept") => Event::AcceptDraw(side(parts.next())?),
+                Some("decline") => Event::DeclineDraw(side(parts.next())?),
+                Some("resign") => Event::Resign(side(parts.next())?),
+                Some("abandon") => Event::Abandon(side(parts.next())?),
+                _ => return Err(err),
+            };
+
+            log.push(Duration::from_millis(millis), event);
+        }
+
+        Ok(log)
+    }
+
+    /// replays every event of the log, returning an adjudicator in the state the game was left in
+    pub fn replay(
+        &self,
+        options: adjudication::AdjudicatorOptions,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Result<adjudication::Adjudicator, adjudication::AdjudicationError> {
+        if pos::validate_fen(&self.start_fen).is_err() {
+            return Err(adjudication::AdjudicationError::InvalidFen);
+        }
+
+        let mut adjudicator = adjudication::Adjudicator::new(
+            pos::Position::from_fen(&self.start_fen, zb),
+            options,
+            masks,
+            zb,
+        );
+
+        for entry in &self.entries {
+            adjudicator.push(entry.event, masks, zb)?;
+        }
+
+        Ok(adjudicator)
+    }
+}
+
+/// first letter of a color in the log format
+fn side_str(side: color::Color) -> &'static str {
+    match side {
+        color::WHITE => "w",
+        _ => "b",
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.elapsed.as_millis())?;
+
+        match self.event {
+            Event::Move(mov) => write!(f, "move {}", mov.to_uci_fmt()),
+            Event::Clock { side, remaining } => {
+                write!(f, "clock {} {}", side_str(side), remaining.as_millis())
+            }
+            Event::ClaimDraw(side) => write!(f, "claim {}", side_str(side)),
+            Event::DrawAgreed => write!(f, "agree"),
+            Event::OfferDraw(side) => write!(f, "offer {}", side_str(side)),
+            Event::AcceptDraw(side) => write!(f, "accept {}", side_str(side)),
+            Event::DeclineDraw(side) => write!(f, "decline {}", side_str(side)),
+            Event::Resign(side) => write!(f, "resign {}", side_str(side)),
+            Event::Abandon(side) => write!(f, "abandon {}", side_str(side)),
+        }
+    }
+}
+
+impl fmt::Display for GameLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "fen {}", self.start_fen)?;
+
+        for entry in &self.entries {
+            writeln!(f, "{entry}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ParseGameLogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseGameLogError::InvalidFen => write!(f, "game log doesn't start with a valid fen"),
+            ParseGameLogError::InvalidEntry(line) => {
+                write!(f, "invalid game log entry on line {line}")
+            }
+            ParseGameLogError::IllegalMove(line) => {
+                write!(f, "illegal move in game log on line {line}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseGameLogError {}
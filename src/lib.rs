@@ -1,13 +1,37 @@
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
 use crate::piece::bb::{self, BitboardUtil};
 
+pub mod adjudication;
+pub mod chess960;
 pub mod color;
+#[cfg(feature = "pgn")]
+pub mod dataset;
+#[cfg(feature = "pgn")]
+pub mod db;
+pub mod epd;
+#[cfg(feature = "pgn")]
+pub mod game_log;
 pub mod moves;
 pub mod perft;
+#[cfg(feature = "pgn")]
+pub mod pgn;
 pub mod piece;
 pub mod pos;
+pub mod report;
+pub mod rules;
+pub mod similarity;
+pub mod square_table;
+pub mod termination;
+pub mod testpos;
+#[cfg(feature = "uci-client")]
 pub mod uci;
 pub mod zobrist;
 
+/// re-exported so that seeded RNGs passed to the library come from the same version of `rand`
+#[cfg(any(feature = "rand-zobrist", feature = "uci-client"))]
+pub use rand;
+
 #[derive(Debug, Clone)]
 /// attack masks for all pieces on all squares
 pub struct AttackMasks {
@@ -16,11 +40,15 @@ pub struct AttackMasks {
     king_attacks: [bb::Bitboard; 64],
     rook_rays: [bb::Bitboard; 64],
     bishop_rays: [bb::Bitboard; 64],
+    between: Box<[[bb::Bitboard; 64]; 64]>,
+    line: Box<[[bb::Bitboard; 64]; 64]>,
 }
 
 #[derive(Debug, Clone)]
 /// values used to generate position keys for transposition tables
 pub struct ZobristValues {
+    /// xored into every key, 0 unless the values come from another hashing scheme
+    base: u64,
     black_to_move: u64,
     wk_castle: u64,
     wq_castle: u64,
@@ -32,31 +60,61 @@ pub struct ZobristValues {
 
 /// initializes lookup tables of attack masks necessary for move generation,
 /// and zobrist values needed for generating position keys
+///
+/// zobrist values are random, so keys differ between runs,
+/// use `init_with_seed()` if you need them to be reproducible
+#[cfg(feature = "rand-zobrist")]
+pub fn init() -> (AttackMasks, ZobristValues) {
+    init_with_rng(&mut rand::rng())
+}
+
+/// initializes lookup tables of attack masks necessary for move generation,
+/// and zobrist values needed for generating position keys
+///
+/// without the `rand-zobrist` feature, the zobrist values are `zobrist::DEFAULT_VALUES`,
+/// built at compile time from `zobrist::DEFAULT_SEED`, so keys are the same between runs and builds
+#[cfg(not(feature = "rand-zobrist"))]
 pub fn init() -> (AttackMasks, ZobristValues) {
+    (init_masks(), zobrist::DEFAULT_VALUES.clone())
+}
+
+/// same as `init()`, but generates the zobrist values from `seed`, see `ZobristValues::from_seed()`,
+/// so keys stay the same between runs, machines and versions of the library,
+/// e.g. for transposition tables, opening books or position databases saved to disk
+pub fn init_with_seed(seed: u64) -> (AttackMasks, ZobristValues) {
+    (init_masks(), ZobristValues::from_seed(seed))
+}
+
+/// same as `init()`, but with the zobrist values of the Polyglot opening book format,
+/// so `Position::key()` can be used to probe Polyglot books, or compared with keys of other tools using them
+pub fn init_polyglot() -> (AttackMasks, ZobristValues) {
+    (init_masks(), zobrist::POLYGLOT_VALUES.clone())
+}
+
+/// same as `init()`, but draws the zobrist values from `rng`,
+/// so the same seed always produces the same position keys
+#[cfg(feature = "rand-zobrist")]
+pub fn init_with_rng<R: rand::Rng + ?Sized>(rng: &mut R) -> (AttackMasks, ZobristValues) {
+    (init_masks(), ZobristValues::from_fn(|| rng.random()))
+}
+
+/// initializes the attack masks
+fn init_masks() -> AttackMasks {
     let mut masks = AttackMasks {
         pawn_attacks: [[bb::EMPTY; 64]; 2],
         knight_attacks: [bb::EMPTY; 64],
         king_attacks: [bb::EMPTY; 64],
         rook_rays: [bb::EMPTY; 64],
         bishop_rays: [bb::EMPTY; 64],
+        between: Box::new([[bb::EMPTY; 64]; 64]),
+        line: Box::new([[bb::EMPTY; 64]; 64]),
     };
 
     bb::init_attack_masks_non_sliding_piece(&mut masks);
     bb::init_attack_masks_sliding_piece_rays(&mut masks);
+    bb::init_line_masks(&mut masks);
 
-    let mut zb = ZobristValues {
-        black_to_move: 0,
-        wk_castle: 0,
-        wq_castle: 0,
-        bk_castle: 0,
-        bq_castle: 0,
-        ep_files: [0; 8],
-        piece_sq: [[0; 64]; 12],
-    };
-
-    zobrist::init_zb_values(&mut zb);
-
-    (masks, zb)
+    masks
 }
 
 impl AttackMasks {
@@ -93,6 +151,20 @@ impl AttackMasks {
         self.rook_rays[square] | self.bishop_rays[square]
     }
 
+    /// returns the squares strictly between `a` and `b` if they share a rank, file or diagonal,
+    /// otherwise an empty bitboard
+    #[inline(always)]
+    pub fn between(&self, a: pos::Square, b: pos::Square) -> bb::Bitboard {
+        self.between[a][b]
+    }
+
+    /// returns the whole rank, file or diagonal going through `a` and `b`, edge to edge,
+    /// or an empty bitboard if they don't share one
+    #[inline(always)]
+    pub fn line(&self, a: pos::Square, b: pos::Square) -> bb::Bitboard {
+        self.line[a][b]
+    }
+
     pub fn rook_attacks_rt(&self, square: pos::Square, occupied: bb::Bitboard) -> bb::Bitboard {
         let blockers = occupied & self.rook_rays(square);
         (bb::walk_to_blocker(square as isize, blockers, bb::RANK_8_MASK, 8)
@@ -1,10 +1,17 @@
+use std::{
+    array, fmt, iter,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    slice,
+};
+
 use crate::{
     AttackMasks, ZobristValues, color,
     piece::{
         self,
         bb::{self, BitboardUtil, EMPTY},
     },
-    pos,
+    pos, rules,
 };
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -24,6 +31,41 @@ pub enum MoveType {
     QueenSideCastle,
 }
 
+/// error returned by `Move::from_san()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SanError {
+    /// the string isn't in SAN format
+    Syntax,
+    /// no legal move matches the string
+    NoMatch,
+    /// more than one legal move matches the string, it's missing a file or rank to tell them apart
+    Ambiguous,
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::Syntax => write!(f, "invalid SAN syntax"),
+            SanError::NoMatch => write!(f, "no legal move matches the SAN move"),
+            SanError::Ambiguous => write!(f, "the SAN move matches more than one legal move"),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+/// which figurines `Move::to_figurine_san()` draws pieces with
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum FigurineStyle {
+    /// white figurines for white's moves and black figurines for black's
+    #[default]
+    Colored,
+    /// the white (outlined) figurines for both sides
+    Outline,
+    /// the black (filled) figurines for both sides
+    Solid,
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 /// a move
 pub struct Move {
@@ -33,6 +75,13 @@ pub struct Move {
     pub(crate) is_reversible: bool,
 }
 
+impl fmt::Display for Move {
+    /// writes the move in UCI format, see `Move::to_uci_fmt()`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_uci_fmt())
+    }
+}
+
 impl Move {
     /// ***panics*** on debug if either square goes out of bounds (> 63)
     pub fn new(from_sq: pos::Square, to_sq: pos::Square, type_of: MoveType) -> Move {
@@ -77,13 +126,14 @@ impl Move {
     /// converts the move to a string in the following format: `"e2e4"`   
     ///
     /// for promotions, no equal sign is used: `"e7e8q"`
+    ///
+    /// castling is written as the king moving to its destination, see `Move::to_uci_fmt_for()` for chess960
     pub fn to_uci_fmt(self) -> String {
         let mut uci = format!("{}{}", pos::to_algn(self.from_sq), pos::to_algn(self.to_sq));
 
         match self.type_of {
             MoveType::Promotion(promoted) | MoveType::PromoCapture(promoted, _) => {
                 uci.push(piece::as_char((promoted & !color::WHITE) | color::BLACK));
-                ()
             }
             _ => (),
         }
@@ -91,6 +141,363 @@ impl Move {
         uci
     }
 
+    /// converts the move to UCI format like `Move::to_uci_fmt()`, except in chess960 mode
+    /// (see `Position::set_chess960()`), where castling is written as the king capturing its own rook,
+    /// e.g. `"e1h1"` instead of `"e1g1"`
+    ///
+    /// `pos` is the position the move is played in, or any later position of the same game
+    pub fn to_uci_fmt_for(self, pos: &pos::Position) -> String {
+        let king_side = match self.type_of {
+            MoveType::KingSideCastle => true,
+            MoveType::QueenSideCastle => false,
+            _ => return self.to_uci_fmt(),
+        };
+
+        if !pos.is_chess960() {
+            return self.to_uci_fmt();
+        }
+
+        let side = match pos::rank_of(self.from_sq) {
+            pos::RANK_1 => color::WHITE,
+            _ => color::BLACK,
+        };
+        let (rook, _) = pos.castling_rook_move(side, king_side);
+
+        format!("{}{}", pos::to_algn(self.from_sq), pos::to_algn(rook))
+    }
+
+    /// converts the move to ICCF numeric notation, files and ranks as digits from 1 to 8, e.g. `"5254"` for e2e4,
+    /// promotions get a fifth digit, 1 for a queen, 2 for a rook, 3 for a bishop and 4 for a knight: `"57581"`
+    ///
+    /// castling is written as the king moving to its destination, e.g. `"5171"`,
+    /// see `Move::to_iccf_for()` for chess960
+    pub fn to_iccf(self) -> String {
+        let digits = |square| {
+            let (file, rank) = pos::make_tuple(square);
+
+            format!("{}{}", file + 1, rank + 1)
+        };
+
+        let mut iccf = digits(self.from_sq) + &digits(self.to_sq);
+
+        if let MoveType::Promotion(promoted) | MoveType::PromoCapture(promoted, _) = self.type_of {
+            iccf.push(match piece::of(promoted) {
+                piece::QUEEN => '1',
+                piece::ROOK => '2',
+                piece::BISHOP => '3',
+                _ => '4',
+            });
+        }
+
+        iccf
+    }
+
+    /// converts the move to ICCF numeric notation like `Move::to_iccf()`, except in chess960 mode
+    /// (see `Position::set_chess960()`), where castling is written as the king moving to its rook's square,
+    /// like `Move::to_uci_fmt_for()`, as the king's destination can also be a plain king move,
+    /// e.g. `"6181"` instead of `"6171"` with the king on f1 and the rook on h1
+    ///
+    /// `pos` is the position the move is played in, or any later position of the same game
+    pub fn to_iccf_for(self, pos: &pos::Position) -> String {
+        let king_side = match self.type_of {
+            MoveType::KingSideCastle => true,
+            MoveType::QueenSideCastle => false,
+            _ => return self.to_iccf(),
+        };
+
+        if !pos.is_chess960() {
+            return self.to_iccf();
+        }
+
+        let side = match pos::rank_of(self.from_sq) {
+            pos::RANK_1 => color::WHITE,
+            _ => color::BLACK,
+        };
+        let (rook, _) = pos.castling_rook_move(side, king_side);
+
+        Move::new(self.from_sq, rook, self.type_of).to_iccf()
+    }
+
+    /// converts the move to standard algebraic notation (SAN), e.g. `"Nbd7"`, `"exd5"`, `"e8=Q+"` or `"O-O-O#"`,
+    /// `pos` is the position before the move is played
+    ///
+    /// the piece is disambiguated by file, rank or both only if another piece of the same type
+    /// can legally move to the same square, the move is made and unmade on `pos` to find the check suffix
+    pub fn to_san(
+        self,
+        pos: &mut pos::Position,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> String {
+        let mut san = match self.type_of {
+            MoveType::KingSideCastle => String::from("O-O"),
+            MoveType::QueenSideCastle => String::from("O-O-O"),
+            _ => {
+                let moving = pos.piece_on(self.from_sq);
+                let is_capture = matches!(
+                    self.type_of,
+                    MoveType::Capture(_) | MoveType::PromoCapture(_, _) | MoveType::EnPassant
+                );
+                let mut san = String::new();
+
+                if moving & piece::PAWN != 0 {
+                    if is_capture {
+                        san.push(pos::to_algn(self.from_sq).chars().next().unwrap());
+                    }
+                } else {
+                    san.push(piece::as_char(piece::of(moving) | color::WHITE));
+
+                    let others = gen_legal(pos, masks, zb)
+                        .iter()
+                        .filter(|m| {
+                            m.to_sq == self.to_sq
+                                && m.from_sq != self.from_sq
+                                && pos.piece_on(m.from_sq) == moving
+                        })
+                        .map(|m| m.from_sq)
+                        .collect::<Vec<pos::Square>>();
+
+                    let from = pos::to_algn(self.from_sq);
+                    let same_file = others
+                        .iter()
+                        .any(|&sq| pos::file_of(sq) == pos::file_of(self.from_sq));
+                    let same_rank = others
+                        .iter()
+                        .any(|&sq| pos::rank_of(sq) == pos::rank_of(self.from_sq));
+
+                    if !others.is_empty() {
+                        if !same_file {
+                            san.push_str(&from[..1]);
+                        } else if !same_rank {
+                            san.push_str(&from[1..]);
+                        } else {
+                            san.push_str(&from);
+                        }
+                    }
+                }
+
+                if is_capture {
+                    san.push('x');
+                }
+
+                san.push_str(&pos::to_algn(self.to_sq));
+
+                if let MoveType::Promotion(promoted) | MoveType::PromoCapture(promoted, _) =
+                    self.type_of
+                {
+                    san.push('=');
+                    san.push(piece::as_char(piece::of(promoted) | color::WHITE));
+                }
+
+                san
+            }
+        };
+
+        self.push_check_suffix(&mut san, pos, masks, zb);
+
+        san
+    }
+
+    /// converts the move to figurine algebraic notation, SAN with the piece letters replaced by
+    /// the figurines of `piece::as_symbol()`, e.g. `"♘bd7"` or `"e8=♕+"`, see `Move::to_san()`
+    pub fn to_figurine_san(
+        self,
+        pos: &mut pos::Position,
+        style: FigurineStyle,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> String {
+        let side = match style {
+            FigurineStyle::Colored => color::of(pos.piece_on(self.from_sq)),
+            FigurineStyle::Outline => color::WHITE,
+            FigurineStyle::Solid => color::BLACK,
+        };
+
+        let mut figurine = String::new();
+
+        // squares are lowercase and castling is written with `O`s, so every uppercase piece letter is a piece
+        for ch in self.to_san(pos, masks, zb).chars() {
+            match ch {
+                'N' | 'B' | 'R' | 'Q' | 'K' => {
+                    figurine.push_str(piece::as_symbol(piece::of(piece::from_char(ch)) | side))
+                }
+                _ => figurine.push(ch),
+            }
+        }
+
+        figurine
+    }
+
+    /// converts the move to long algebraic notation (LAN), e.g. `"Ng1-f3"`, `"e7xd8=Q+"` or `"O-O"`,
+    /// `pos` is the position before the move is played
+    ///
+    /// pawn moves are written without a piece letter, the move is made and unmade on `pos` to find the check suffix
+    pub fn to_lan(
+        self,
+        pos: &mut pos::Position,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> String {
+        let mut lan = match self.type_of {
+            MoveType::KingSideCastle => String::from("O-O"),
+            MoveType::QueenSideCastle => String::from("O-O-O"),
+            _ => {
+                let moving = pos.piece_on(self.from_sq);
+                let mut lan = String::new();
+
+                if moving & piece::PAWN == 0 {
+                    lan.push(piece::as_char(piece::of(moving) | color::WHITE));
+                }
+
+                lan.push_str(&pos::to_algn(self.from_sq));
+                lan.push(match self.type_of {
+                    MoveType::Capture(_) | MoveType::PromoCapture(_, _) | MoveType::EnPassant => {
+                        'x'
+                    }
+                    _ => '-',
+                });
+                lan.push_str(&pos::to_algn(self.to_sq));
+
+                if let MoveType::Promotion(promoted) | MoveType::PromoCapture(promoted, _) =
+                    self.type_of
+                {
+                    lan.push('=');
+                    lan.push(piece::as_char(piece::of(promoted) | color::WHITE));
+                }
+
+                lan
+            }
+        };
+
+        self.push_check_suffix(&mut lan, pos, masks, zb);
+
+        lan
+    }
+
+    /// appends `+` or `#` to a move in SAN or LAN if it gives check or mate
+    fn push_check_suffix(
+        self,
+        notation: &mut String,
+        pos: &mut pos::Position,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) {
+        pos.make_move(self, zb);
+
+        if pos.is_check(masks) {
+            notation.push(if legal_moves(pos, masks, zb).next().is_none() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+
+        pos.unmake_move();
+    }
+
+    /// finds the legal move in `pos` written in standard algebraic notation (SAN) as `san`
+    ///
+    /// check and mate suffixes and annotations are ignored, so `"Nbd7!?"` and `"Nbd7"` are the same,
+    /// castling can be written with zeros (`"0-0"`), and the equal sign of promotions can be left out (`"e8Q"`)
+    pub fn from_san(
+        san: &str,
+        pos: &mut pos::Position,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Result<Self, SanError> {
+        let san = san
+            .trim()
+            .trim_end_matches(['+', '#', '!', '?'])
+            .trim_end_matches("e.p.")
+            .trim_end();
+
+        let legal = gen_legal(pos, masks, zb);
+
+        let castle = match san {
+            "O-O" | "0-0" => Some(MoveType::KingSideCastle),
+            "O-O-O" | "0-0-0" => Some(MoveType::QueenSideCastle),
+            _ => None,
+        };
+
+        if let Some(castle) = castle {
+            return legal
+                .iter()
+                .find(|m| m.type_of == castle)
+                .copied()
+                .ok_or(SanError::NoMatch);
+        }
+
+        let mut chars = san
+            .chars()
+            .filter(|&ch| ch != 'x' && ch != '=')
+            .collect::<Vec<char>>();
+
+        let kind = match chars.first() {
+            Some(&ch) if "NBRQK".contains(ch) => {
+                chars.remove(0);
+                piece::of(piece::from_char(ch))
+            }
+            _ => piece::PAWN,
+        };
+
+        let promotion = match chars.last() {
+            Some(&ch) if "NBRQ".contains(ch) && kind == piece::PAWN => {
+                chars.pop();
+                piece::of(piece::from_char(ch))
+            }
+            _ => piece::NONE,
+        };
+
+        if chars.len() < 2 || chars.len() > 4 {
+            return Err(SanError::Syntax);
+        }
+
+        let target = chars
+            .split_off(chars.len() - 2)
+            .into_iter()
+            .collect::<String>();
+
+        if !matches!(target.as_bytes(), [b'a'..=b'h', b'1'..=b'8']) {
+            return Err(SanError::Syntax);
+        }
+
+        let to = pos::str_to_sq(&target);
+
+        let mut file = None;
+        let mut rank = None;
+
+        for ch in chars {
+            match ch {
+                'a'..='h' if file.is_none() => file = Some((ch as u8 - b'a') as pos::File),
+                '1'..='8' if rank.is_none() => rank = Some((ch as u8 - b'1') as pos::Rank),
+                _ => return Err(SanError::Syntax),
+            }
+        }
+
+        let mut matches = legal.iter().filter(|m| {
+            let promoted = match m.type_of {
+                MoveType::Promotion(p) | MoveType::PromoCapture(p, _) => piece::of(p),
+                _ => piece::NONE,
+            };
+
+            m.to_sq == to
+                && piece::of(pos.piece_on(m.from_sq)) == kind
+                && promoted == promotion
+                && file.is_none_or(|file| pos::file_of(m.from_sq) == file)
+                && rank.is_none_or(|rank| pos::rank_of(m.from_sq) == rank)
+                && !matches!(
+                    m.type_of,
+                    MoveType::KingSideCastle | MoveType::QueenSideCastle
+                )
+        });
+
+        match (matches.next(), matches.next()) {
+            (Some(&mov), None) => Ok(mov),
+            (Some(_), Some(_)) => Err(SanError::Ambiguous),
+            _ => Err(SanError::NoMatch),
+        }
+    }
+
     /// converts a string in uci format to a move
     ///
     /// the format is the following: `"e2e4"`
@@ -98,6 +505,9 @@ impl Move {
     /// for promotions it doesn't matter if there is an equal sign or not, so both
     /// `"e7e8q"` and `"e7e8=q"` are valid
     ///
+    /// in chess960 mode (see `Position::set_chess960()`) castling is read as the king capturing its own rook,
+    /// e.g. `"e1h1"`, and a king moving two squares is a normal move
+    ///
     /// the funtion assumes a string in correct format and doesn't do much error checking
     ///
     /// string correctness should be handled by a gui (really im just lazy)
@@ -120,6 +530,26 @@ impl Move {
             uci.chars().skip(2).take(2).collect::<String>()
         );
 
+        let moving = pos.piece_on(from);
+
+        // in chess960 mode the king castles by capturing its own rook
+        if pos.is_chess960()
+            && moving & piece::KING != 0
+            && pos.piece_on(to) == piece::ROOK | color::of(moving)
+        {
+            let king_side = to > from;
+            let (rook, _) = pos.castling_rook_move(color::of(moving), king_side);
+
+            if rook == to {
+                let (file, castle) = match king_side {
+                    true => (pos::FILE_G, MoveType::KingSideCastle),
+                    false => (pos::FILE_C, MoveType::QueenSideCastle),
+                };
+
+                return Self::new(from, pos::make_sq(file, pos::rank_of(from)), castle);
+            }
+        }
+
         let promo: char = uci.chars().nth_back(0).unwrap();
         let mut mov = Self::new(from, to, MoveType::Normal);
         let promo_piece;
@@ -159,7 +589,8 @@ impl Move {
             }
         }
 
-        if pos.piece_on(from) & piece::KING != 0 {
+        // in chess960 mode a king moving two squares is a normal move
+        if pos.piece_on(from) & piece::KING != 0 && !pos.is_chess960() {
             if from as isize - to as isize == -2 {
                 mov.type_of = MoveType::KingSideCastle;
                 return mov;
@@ -171,19 +602,294 @@ impl Move {
 
         mov
     }
+
+    /// finds the legal move in `pos` written in ICCF numeric notation as `iccf`, see `Move::to_iccf_for()`
+    ///
+    /// returns `None` if the string isn't in ICCF notation, or no legal move matches it,
+    /// or more than one does, which can only happen with chess960 castling outside of chess960 mode
+    pub fn from_iccf(
+        iccf: &str,
+        pos: &mut pos::Position,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Option<Self> {
+        let iccf = iccf.trim();
+        let legal = gen_legal(pos, masks, zb);
+        let mut matching = legal.iter().copied().filter(|m| m.to_iccf_for(pos) == iccf);
+
+        match (matching.next(), matching.next()) {
+            (Some(mov), None) => Some(mov),
+            _ => None,
+        }
+    }
+}
+
+/// maximum number of moves a `MoveList` can hold
+pub const MAX_MOVES: usize = 256;
+
+/// a fixed capacity list of moves that lives on the stack,
+/// used by move generation to avoid allocating a new vector for every call
+///
+/// dereferences to a slice of the moves that were pushed, so methods such as `iter()`,
+/// `contains()` and indexing are available
+#[derive(Clone)]
+pub struct MoveList {
+    // only the first `len` moves are initialized, so creating a list doesn't write all of them
+    moves: [MaybeUninit<Move>; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        MoveList {
+            moves: [MaybeUninit::uninit(); MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    /// adds a move to the end of the list
+    ///
+    /// ***panics*** if the list is already full
+    #[inline(always)]
+    pub fn push(&mut self, mov: Move) {
+        debug_assert!(self.len < MAX_MOVES, "move list is full!");
+
+        self.moves[self.len] = MaybeUninit::new(mov);
+        self.len += 1;
+    }
+
+    /// the number of moves in the list
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// returns true if the list contains no moves
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// removes all moves from the list
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// keeps only the moves for which `f` returns true, preserving their order
+    #[inline(always)]
+    pub fn retain(&mut self, mut f: impl FnMut(&Move) -> bool) {
+        let mut kept = 0;
+
+        for i in 0..self.len {
+            let mov = self[i];
+
+            if f(&mov) {
+                self.moves[kept] = MaybeUninit::new(mov);
+                kept += 1;
+            }
+        }
+
+        self.len = kept;
+    }
+
+    /// the moves in the list as a slice
+    #[inline(always)]
+    pub fn as_slice(&self) -> &[Move] {
+        // SAFETY: the first `len` moves were initialized by `push()`
+        unsafe { slice::from_raw_parts(self.moves.as_ptr().cast(), self.len) }
+    }
+
+    /// the moves in the list as a mutable slice
+    #[inline(always)]
+    pub fn as_mut_slice(&mut self) -> &mut [Move] {
+        // SAFETY: the first `len` moves were initialized by `push()`
+        unsafe { slice::from_raw_parts_mut(self.moves.as_mut_ptr().cast(), self.len) }
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        self.as_slice()
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [Move] {
+        self.as_mut_slice()
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = iter::Map<
+        iter::Take<array::IntoIter<MaybeUninit<Move>, MAX_MOVES>>,
+        fn(MaybeUninit<Move>) -> Move,
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        // SAFETY: only the first `len` moves are taken, which were initialized by `push()`
+        self.moves
+            .into_iter()
+            .take(self.len)
+            .map(|mov| unsafe { mov.assume_init() })
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
+/// ordinal used by MVV-LVA scoring, higher values are more valuable pieces
+#[inline(always)]
+fn mvv_lva_rank(piece: piece::Piece) -> i32 {
+    match piece::of(piece) {
+        piece::PAWN => 1,
+        piece::KNIGHT => 2,
+        piece::BISHOP => 3,
+        piece::ROOK => 4,
+        piece::QUEEN => 5,
+        piece::KING => 6,
+        _ => 0,
+    }
+}
+
+/// scores a move by *most valuable victim / least valuable attacker*,
+/// captures of more valuable pieces score higher, and among those,
+/// captures made with less valuable pieces score higher
+///
+/// the victim is taken from the `MoveType::Capture` payload, and the attacker from `pos`,
+/// so `mov` must not have been played in `pos` yet
+///
+/// moves that don't capture anything score 0, every capture scores above 0
+#[inline(always)]
+pub fn score_mvv_lva(mov: Move, pos: &pos::Position) -> i32 {
+    let victim = match mov.type_of() {
+        MoveType::Capture(cap) | MoveType::PromoCapture(_, cap) => cap,
+        MoveType::EnPassant => piece::PAWN,
+        _ => return 0,
+    };
+
+    mvv_lva_rank(victim) * 10 - mvv_lva_rank(pos.piece_on(mov.from_sq()))
+}
+
+/// sorts `moves` so that captures come first, ordered by `moves::score_mvv_lva()`
+pub fn sort_mvv_lva(moves: &mut [Move], pos: &pos::Position) {
+    moves.sort_unstable_by_key(|&m| -score_mvv_lva(m, pos));
+}
+
+/// piece types a pawn can promote to in standard chess, as a set of piece flags
+pub const ALL_PROMOTIONS: piece::Piece = piece::QUEEN | piece::ROOK | piece::BISHOP | piece::KNIGHT;
+
+/// only promotions to a queen, skipping all underpromotions
+pub const QUEEN_PROMOTIONS: piece::Piece = piece::QUEEN;
+
+/// standard promotions plus promoting to a king, as allowed in antichess
+pub const ANTICHESS_PROMOTIONS: piece::Piece = ALL_PROMOTIONS | piece::KING;
+
+/// the order promotion moves are generated in
+const PROMOTION_ORDER: [piece::Piece; 5] = [
+    piece::QUEEN,
+    piece::ROOK,
+    piece::BISHOP,
+    piece::KNIGHT,
+    piece::KING,
+];
+
+/// pushes a promotion from `from` to `to` for every piece type in `promotions`,
+/// `captured` is the captured piece for capturing promotions
+#[inline(always)]
+fn push_promotions(
+    moves: &mut MoveList,
+    from: pos::Square,
+    to: pos::Square,
+    side: color::Color,
+    promotions: piece::Piece,
+    captured: Option<piece::Piece>,
+) {
+    for promoted in PROMOTION_ORDER {
+        if promotions & promoted != 0 {
+            moves.push(Move::new(
+                from,
+                to,
+                match captured {
+                    Some(cap) => MoveType::PromoCapture(promoted | side, cap),
+                    None => MoveType::Promotion(promoted | side),
+                },
+            ));
+        }
+    }
 }
 
 /// generates all pseudo legal pawn moves
-pub fn pawn_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &AttackMasks) {
+pub fn pawn_moves(pos: &pos::Position, moves: &mut MoveList, masks: &AttackMasks) {
+    pawn_moves_with(pos, moves, masks, ALL_PROMOTIONS);
+}
+
+/// generates all pseudo legal pawn moves, where pawns only promote to the piece types in `promotions`
+///
+/// `e.g. promotions == moves::QUEEN_PROMOTIONS` only generates queen promotions
+pub fn pawn_moves_with(
+    pos: &pos::Position,
+    moves: &mut MoveList,
+    masks: &AttackMasks,
+    promotions: piece::Piece,
+) {
+    pawn_moves_from(
+        pos,
+        pos.piece_bb(piece::PAWN | pos.side_to_move()),
+        moves,
+        masks,
+        promotions,
+    );
+}
+
+/// generates pseudo legal moves for the pawns in `pawns`
+fn pawn_moves_from(
+    pos: &pos::Position,
+    mut pawns: bb::Bitboard,
+    moves: &mut MoveList,
+    masks: &AttackMasks,
+    promotions: piece::Piece,
+) {
     let side = pos.side_to_move();
-    let mut pawns = pos.piece_bb(piece::PAWN | side);
+
+    let start_rank = match side {
+        color::WHITE => pos::RANK_2,
+        _ => pos::RANK_7,
+    };
+
+    let promo_rank = match side {
+        color::WHITE => pos::RANK_8,
+        _ => pos::RANK_1,
+    };
+
+    let ep = match pos.ep_square() {
+        Some(square) => 1 << square,
+        None => 0,
+    };
 
     while pawns != bb::EMPTY {
         let from = pawns.serialize_once();
-        let ep = match pos.ep_square() {
-            Some(from) => 1 << from,
-            None => 0,
-        };
 
         let attacks = masks.pawn_attacks(side, from);
         let mut captures = attacks & pos.color_bb(color::other(side));
@@ -191,41 +897,12 @@ pub fn pawn_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &AttackMask
 
         let up1 = pos::ahead(from, side);
 
-        let start_rank = match side {
-            color::WHITE => pos::RANK_2,
-            _ => pos::RANK_7,
-        };
-
-        let promo_rank = match side {
-            color::WHITE => pos::RANK_8,
-            _ => pos::RANK_1,
-        };
-
         while captures != EMPTY {
             let to = captures.serialize_once();
             let cap = pos.piece_on(to);
 
             if pos::rank_of(to) == promo_rank {
-                moves.push(Move::new(
-                    from,
-                    to,
-                    MoveType::PromoCapture(piece::QUEEN | side, cap),
-                ));
-                moves.push(Move::new(
-                    from,
-                    to,
-                    MoveType::PromoCapture(piece::ROOK | side, cap),
-                ));
-                moves.push(Move::new(
-                    from,
-                    to,
-                    MoveType::PromoCapture(piece::BISHOP | side, cap),
-                ));
-                moves.push(Move::new(
-                    from,
-                    to,
-                    MoveType::PromoCapture(piece::KNIGHT | side, cap),
-                ));
+                push_promotions(moves, from, to, side, promotions, Some(cap));
             } else {
                 moves.push(Move::new(from, to, MoveType::Capture(cap)));
             }
@@ -241,26 +918,7 @@ pub fn pawn_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &AttackMask
 
         if !pos.is_occupied(up1) {
             if pos::rank_of(up1) == promo_rank {
-                moves.push(Move::new(
-                    from,
-                    up1,
-                    MoveType::Promotion(piece::QUEEN | side),
-                ));
-                moves.push(Move::new(
-                    from,
-                    up1,
-                    MoveType::Promotion(piece::ROOK | side),
-                ));
-                moves.push(Move::new(
-                    from,
-                    up1,
-                    MoveType::Promotion(piece::BISHOP | side),
-                ));
-                moves.push(Move::new(
-                    from,
-                    up1,
-                    MoveType::Promotion(piece::KNIGHT | side),
-                ));
+                push_promotions(moves, from, up1, side, promotions, None);
             } else {
                 moves.push(Move::new(from, up1, MoveType::Normal));
             }
@@ -276,7 +934,7 @@ pub fn pawn_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &AttackMask
 }
 
 /// generates all pseudo legal knight moves
-pub fn knight_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &AttackMasks) {
+pub fn knight_moves(pos: &pos::Position, moves: &mut MoveList, masks: &AttackMasks) {
     let side = pos.side_to_move();
     let mut knights = pos.piece_bb(piece::KNIGHT | side);
 
@@ -299,7 +957,7 @@ pub fn knight_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &AttackMa
 }
 
 /// generates all pseudo legal moves for rooks (and queens not moving diagonally)
-pub fn rook_or_queen_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &AttackMasks) {
+pub fn rook_or_queen_moves(pos: &pos::Position, moves: &mut MoveList, masks: &AttackMasks) {
     let side = pos.side_to_move();
     let mut rook_or_queens = pos.piece_bb(piece::ROOK | side) | pos.piece_bb(piece::QUEEN | side);
 
@@ -322,7 +980,7 @@ pub fn rook_or_queen_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &A
 }
 
 /// generates all pseudo legal moves for bishops (and queens moving only diagonally)
-pub fn bishop_or_queen_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &AttackMasks) {
+pub fn bishop_or_queen_moves(pos: &pos::Position, moves: &mut MoveList, masks: &AttackMasks) {
     let side = pos.side_to_move();
     let mut bishop_or_queens =
         pos.piece_bb(piece::BISHOP | side) | pos.piece_bb(piece::QUEEN | side);
@@ -346,10 +1004,14 @@ pub fn bishop_or_queen_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks:
 }
 
 /// generates all pseudo legal king moves
-pub fn king_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &AttackMasks) {
+pub fn king_moves(pos: &pos::Position, moves: &mut MoveList, masks: &AttackMasks) {
     let side = pos.side_to_move();
     let mut king = pos.piece_bb(piece::KING | side);
 
+    if king == bb::EMPTY {
+        return;
+    }
+
     let from = king.serialize_once();
     let attacks = masks.king_attacks(from);
     let mut captures = attacks & pos.color_bb(color::other(side));
@@ -370,40 +1032,207 @@ pub fn king_moves(pos: &pos::Position, moves: &mut Vec<Move>, masks: &AttackMask
         _ => (pos::BK_CASTLE, pos::BQ_CASTLE),
     };
 
-    if pos.castle_rights() & kcastle != 0
-        && !pos.is_occupied(from + 1)
-        && !pos.is_occupied(from + 2)
-        && !pos.is_check(masks)
-        && !bb::is_attacked(from + 1, &pos, color::other(side), masks)
-        && !bb::is_attacked(from + 2, &pos, color::other(side), masks)
-    {
-        moves.push(Move::new(from, from + 2, MoveType::KingSideCastle));
+    if pos.castle_rights() & (kcastle | qcastle) == 0 || pos.is_check(masks) {
+        return;
     }
 
-    if pos.castle_rights() & qcastle != 0
-        && !pos.is_occupied(from - 1)
-        && !pos.is_occupied(from - 2)
-        && !pos.is_occupied(from - 3)
-        && !pos.is_check(masks)
-        && !bb::is_attacked(from - 1, &pos, color::other(side), masks)
-        && !bb::is_attacked(from - 2, &pos, color::other(side), masks)
-    {
-        moves.push(Move::new(from, from - 2, MoveType::QueenSideCastle));
+    for (right, king_side) in [(kcastle, true), (qcastle, false)] {
+        let Some(info) = pos.castling_info(right, masks) else {
+            continue;
+        };
+
+        if info.empty_squares() & pos.occupied_bb() != bb::EMPTY {
+            continue;
+        }
+
+        // the king and rook themselves don't block attacks on the squares the king passes,
+        // as they are both moving
+        let occupied = pos.occupied_bb() & !bb::square_bb(from) & !bb::square_bb(info.rook_from);
+        let mut king_path = info.king_path;
+        let mut safe = true;
+
+        while king_path != bb::EMPTY {
+            let square = king_path.serialize_once();
+
+            if bb::all_attackers_of(square, pos, occupied, masks) & pos.color_bb(color::other(side))
+                != bb::EMPTY
+            {
+                safe = false;
+                break;
+            }
+        }
+
+        // the king can't end up in check either, which the path misses in chess960 when the king
+        // stays on its square and the rook leaving it uncovers an attack, e.g. from a queen behind the rook
+        let occupied = occupied | bb::square_bb(info.king_to) | bb::square_bb(info.rook_to);
+        safe = safe
+            && bb::all_attackers_of(info.king_to, pos, occupied, masks)
+                & pos.color_bb(color::other(side))
+                == bb::EMPTY;
+
+        if safe {
+            moves.push(Move::new(
+                from,
+                info.king_to,
+                if king_side {
+                    MoveType::KingSideCastle
+                } else {
+                    MoveType::QueenSideCastle
+                },
+            ));
+        }
     }
 }
 
-/// generates all legal moves by first generating pseudo legal moves, and then filtering out the illegal ones
-pub fn gen_legal(pos: &mut pos::Position, masks: &AttackMasks, zb: &ZobristValues) -> Vec<Move> {
-    let mut moves = Vec::new();
-    moves.reserve(238); // 238 is the max number of legal moves in any given position
-    let side = pos.side_to_move();
+/// generates all pseudo legal moves, including ones that leave the king in check
+pub fn gen_pseudo_legal(pos: &pos::Position, masks: &AttackMasks) -> MoveList {
+    gen_pseudo_legal_promos(pos, ALL_PROMOTIONS, masks)
+}
 
-    pawn_moves(pos, &mut moves, masks);
+/// same as `moves::gen_pseudo_legal()`, but pawns only promote to the piece types in `promotions`
+pub fn gen_pseudo_legal_promos(
+    pos: &pos::Position,
+    promotions: piece::Piece,
+    masks: &AttackMasks,
+) -> MoveList {
+    let mut moves = MoveList::new();
+
+    pawn_moves_with(pos, &mut moves, masks, promotions);
     knight_moves(pos, &mut moves, masks);
     rook_or_queen_moves(pos, &mut moves, masks);
     bishop_or_queen_moves(pos, &mut moves, masks);
     king_moves(pos, &mut moves, masks);
 
+    moves
+}
+
+/// generates all moves allowed under `rules`
+///
+/// if `rules.check_rules` is false, the position doesn't need to have any kings
+pub fn gen_legal_with(
+    pos: &mut pos::Position,
+    rules: &rules::Rules,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> MoveList {
+    let mut moves = if rules.check_rules {
+        gen_legal_promos(pos, rules.promotions, masks, zb)
+    } else {
+        gen_pseudo_legal_promos(pos, rules.promotions, masks)
+    };
+
+    if !rules.castling {
+        moves.retain(|m| {
+            !matches!(
+                m.type_of(),
+                MoveType::KingSideCastle | MoveType::QueenSideCastle
+            )
+        });
+    }
+
+    moves
+}
+
+/// generates all legal moves by first generating pseudo legal moves, and then filtering out the illegal ones
+pub fn gen_legal(pos: &mut pos::Position, masks: &AttackMasks, zb: &ZobristValues) -> MoveList {
+    let mut moves = MoveList::new();
+    gen_legal_into(pos, &mut moves, masks, zb);
+    moves
+}
+
+/// same as `moves::gen_legal()`, but clears `moves` and fills it with the legal moves
+/// instead of returning a new list, so the same buffer can be reused across calls
+pub fn gen_legal_into(
+    pos: &mut pos::Position,
+    moves: &mut MoveList,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) {
+    gen_legal_promos_into(pos, moves, ALL_PROMOTIONS, masks, zb);
+}
+
+/// same as `moves::gen_legal()`, but pawns only promote to the piece types in `promotions`
+///
+/// `e.g. promotions == moves::QUEEN_PROMOTIONS` skips all underpromotions
+pub fn gen_legal_promos(
+    pos: &mut pos::Position,
+    promotions: piece::Piece,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> MoveList {
+    let mut moves = MoveList::new();
+    gen_legal_promos_into(pos, &mut moves, promotions, masks, zb);
+    moves
+}
+
+/// same as `moves::gen_legal_promos()`, but fills a caller provided buffer
+pub fn gen_legal_promos_into(
+    pos: &mut pos::Position,
+    moves: &mut MoveList,
+    promotions: piece::Piece,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) {
+    moves.clear();
+
+    pawn_moves_with(pos, moves, masks, promotions);
+    knight_moves(pos, moves, masks);
+    rook_or_queen_moves(pos, moves, masks);
+    bishop_or_queen_moves(pos, moves, masks);
+    king_moves(pos, moves, masks);
+
+    retain_legal(pos, moves, masks, zb);
+}
+
+/// generates only legal promotions (including capturing promotions),
+/// where pawns only promote to the piece types in `promotions`
+pub fn gen_promotions(
+    pos: &mut pos::Position,
+    promotions: piece::Piece,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> MoveList {
+    let side = pos.side_to_move();
+    let seventh_rank = match side {
+        color::WHITE => bb::RANK_7_MASK,
+        _ => bb::RANK_2_MASK,
+    };
+
+    // every move of a pawn on the seventh rank is a promotion, and no other move is
+    let mut moves = MoveList::new();
+    pawn_moves_from(
+        pos,
+        pos.piece_bb(piece::PAWN | side) & seventh_rank,
+        &mut moves,
+        masks,
+        promotions,
+    );
+
+    retain_legal(pos, &mut moves, masks, zb);
+
+    moves
+}
+
+/// filters out all pseudo legal moves in `moves` that leave the king in check
+fn retain_legal(
+    pos: &mut pos::Position,
+    moves: &mut MoveList,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) {
+    let in_check = pos.is_check(masks);
+    moves.retain(|&m| leaves_king_safe(m, pos, in_check, masks, zb));
+}
+
+/// returns true if the pseudo legal move `m` doesn't leave the king of the side to move in check
+#[inline(always)]
+fn leaves_king_safe(
+    m: Move,
+    pos: &mut pos::Position,
+    in_check: bool,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> bool {
     fn is_legal(m: Move, pos: &mut pos::Position, masks: &AttackMasks, zb: &ZobristValues) -> bool {
         let cap = pos.fast_make(m, m.type_of() == MoveType::EnPassant, zb);
         // pos.make_move(m, zb);
@@ -414,23 +1243,84 @@ pub fn gen_legal(pos: &mut pos::Position, masks: &AttackMasks, zb: &ZobristValue
         is_legal
     }
 
-    if pos.is_check(masks) {
-        moves
-            .into_iter()
-            .filter(|&m| is_legal(m, pos, masks, zb))
-            .collect()
+    if in_check {
+        is_legal(m, pos, masks, zb)
+    } else if pos.piece_on(m.from_sq()) & piece::KING != 0 {
+        !bb::is_attacked(m.to_sq(), pos, color::other(pos.side_to_move()), masks)
+    } else if bb::might_be_pinned(pos, m.from_sq()) {
+        is_legal(m, pos, masks, zb)
     } else {
-        moves
-            .into_iter()
-            .filter(|&m| {
-                if pos.piece_on(m.from_sq()) & piece::KING != 0 {
-                    !bb::is_attacked(m.to_sq(), &pos, color::other(side), masks)
-                } else if bb::might_be_pinned(pos, m.from_sq()) {
-                    is_legal(m, pos, masks, zb)
-                } else {
-                    true
+        true
+    }
+}
+
+/// an iterator over the legal moves of a position that generates them lazily,
+/// one piece type at a time, so callers that stop early (e.g. to check whether there is any legal move)
+/// don't pay for generating all of them
+///
+/// moves are yielded in the same order as `moves::gen_legal()` returns them
+pub struct LegalMoves<'a> {
+    pos: &'a mut pos::Position,
+    masks: &'a AttackMasks,
+    zb: &'a ZobristValues,
+    in_check: bool,
+    stage: usize,
+    buf: MoveList,
+    idx: usize,
+}
+
+impl<'a> LegalMoves<'a> {
+    pub fn new(pos: &'a mut pos::Position, masks: &'a AttackMasks, zb: &'a ZobristValues) -> Self {
+        let in_check = pos.is_check(masks);
+
+        LegalMoves {
+            pos,
+            masks,
+            zb,
+            in_check,
+            stage: 0,
+            buf: MoveList::new(),
+            idx: 0,
+        }
+    }
+}
+
+impl Iterator for LegalMoves<'_> {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        loop {
+            while self.idx < self.buf.len() {
+                let m = self.buf[self.idx];
+                self.idx += 1;
+
+                if leaves_king_safe(m, self.pos, self.in_check, self.masks, self.zb) {
+                    return Some(m);
                 }
-            })
-            .collect()
+            }
+
+            self.buf.clear();
+            self.idx = 0;
+
+            match self.stage {
+                0 => pawn_moves(self.pos, &mut self.buf, self.masks),
+                1 => knight_moves(self.pos, &mut self.buf, self.masks),
+                2 => rook_or_queen_moves(self.pos, &mut self.buf, self.masks),
+                3 => bishop_or_queen_moves(self.pos, &mut self.buf, self.masks),
+                4 => king_moves(self.pos, &mut self.buf, self.masks),
+                _ => return None,
+            }
+
+            self.stage += 1;
+        }
     }
 }
+
+/// returns a lazy iterator over the legal moves of `pos`, see `moves::LegalMoves`
+pub fn legal_moves<'a>(
+    pos: &'a mut pos::Position,
+    masks: &'a AttackMasks,
+    zb: &'a ZobristValues,
+) -> LegalMoves<'a> {
+    LegalMoves::new(pos, masks, zb)
+}
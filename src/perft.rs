@@ -1,12 +1,23 @@
+#[cfg(feature = "perft-suite")]
 use std::fs;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
 
-use colored::Colorize;
-
-use crate::{AttackMasks, ZobristValues, moves, pos};
+#[cfg(feature = "uci-client")]
+use crate::uci;
+use crate::{AttackMasks, ZobristValues, color, moves, piece, pos, testpos, zobrist};
+#[cfg(feature = "perft-suite")]
+use crate::{epd, report};
 
 /// a standard perft test
 ///
-/// recursively searches a position with a certain depth, useful for testing the correctness of move generation
+/// recursively searches a position with a certain depth, useful for testing the correctness of move generation,
+/// at the root, the node count of every move is printed, see `perft::perft_result()` to get them instead
 pub fn perft(
     pos: &mut pos::Position,
     depth: i32,
@@ -14,31 +25,703 @@ pub fn perft(
     masks: &AttackMasks,
     zb: &ZobristValues,
 ) -> i64 {
-    let mut nodes = 0;
+    if is_root {
+        let result = perft_result(pos, depth, masks, zb);
+        println!("{result}");
+
+        return result.total as i64;
+    }
+
+    count(pos, depth, true, None, masks, zb) as i64
+}
+
+/// like `perft::perft()`, but without bulk counting, every leaf move is made and unmade,
+/// which is slower, but also tests `Position::make_move()` and `Position::unmake_move()` at the last ply
+pub fn perft_full(
+    pos: &mut pos::Position,
+    depth: i32,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> u64 {
+    count(pos, depth, false, None, masks, zb)
+}
+
+/// the results of a perft search, see `perft::perft_result()`
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PerftResult {
+    /// the number of leaf nodes
+    pub total: u64,
+    /// the number of leaf nodes after every legal move of the root, in move generation order
+    pub divide: Vec<(moves::Move, u64)>,
+}
+
+impl fmt::Display for PerftResult {
+    /// writes the result like `perft::perft()` prints it, every root move with its node count, then the total
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (mov, nodes) in &self.divide {
+            writeln!(f, "{}: {nodes}", mov.to_uci_fmt())?;
+        }
+
+        write!(f, "\nsearched {} nodes", self.total)
+    }
+}
+
+/// like `perft::perft()` at the root, but returns the node counts instead of printing them
+pub fn perft_result(
+    pos: &mut pos::Position,
+    depth: i32,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> PerftResult {
+    if depth <= 0 {
+        return PerftResult {
+            total: 1,
+            divide: Vec::new(),
+        };
+    }
+
+    let mut result = PerftResult::default();
+
+    for &mov in moves::gen_legal(pos, masks, zb).iter() {
+        pos.make_move(mov, zb);
+        let nodes = count(pos, depth - 1, true, None, masks, zb);
+        pos.unmake_move();
+
+        result.total += nodes;
+        result.divide.push((mov, nodes));
+    }
+
+    result
+}
+
+/// like `perft::perft_result()`, but calls `on_root_move` with every root move, its node count,
+/// and the time since the search started, as soon as the move is searched
+///
+/// the search can be stopped from another thread by setting `stop`, it then returns `None`
+pub fn perft_with(
+    pos: &mut pos::Position,
+    depth: i32,
+    stop: &AtomicBool,
+    mut on_root_move: impl FnMut(moves::Move, u64, Duration),
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> Option<PerftResult> {
+    if depth <= 0 {
+        return Some(PerftResult {
+            total: 1,
+            divide: Vec::new(),
+        });
+    }
+
+    let start = Instant::now();
+    let mut result = PerftResult::default();
+
+    for &mov in moves::gen_legal(pos, masks, zb).iter() {
+        pos.make_move(mov, zb);
+        let nodes = count(pos, depth - 1, true, Some(stop), masks, zb);
+        pos.unmake_move();
+
+        if stop.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        on_root_move(mov, nodes, start.elapsed());
+
+        result.total += nodes;
+        result.divide.push((mov, nodes));
+    }
+
+    Some(result)
+}
+
+/// the node count of every legal move of `pos`, sorted by the moves in UCI notation,
+/// the same order other engines' `go perft` output is usually diffed in
+///
+/// chess960 positions use the king-takes-rook castling notation, see `Move::to_uci_fmt_for()`
+pub fn divide(
+    pos: &mut pos::Position,
+    depth: i32,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> Vec<(moves::Move, u64)> {
+    let mut divide = perft_result(pos, depth, masks, zb).divide;
+    divide.sort_by_cached_key(|(mov, _)| mov.to_uci_fmt_for(pos));
+
+    divide
+}
+
+/// like `perft::perft_result()`, but the root moves are split between `threads` threads,
+/// each searching its own copy of `pos`, 0 threads uses one per available core
+pub fn perft_parallel(
+    pos: &pos::Position,
+    depth: i32,
+    threads: usize,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> PerftResult {
+    if depth <= 0 {
+        return PerftResult {
+            total: 1,
+            divide: Vec::new(),
+        };
+    }
+
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+
+    let root_moves = moves::gen_legal(&mut pos.clone(), masks, zb);
+    let next = AtomicUsize::new(0);
+
+    // every thread takes the next root move that hasn't been searched yet
+    let counted: Vec<(usize, u64)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(root_moves.len()))
+            .map(|_| {
+                let (root_moves, next) = (&root_moves, &next);
+                let mut pos = pos.clone();
+
+                scope.spawn(move || {
+                    let mut counted = Vec::new();
+
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(&mov) = root_moves.get(i) else {
+                            break;
+                        };
+
+                        pos.make_move(mov, zb);
+                        counted.push((i, count(&mut pos, depth - 1, true, None, masks, zb)));
+                        pos.unmake_move();
+                    }
+
+                    counted
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+
+    let mut result = PerftResult {
+        total: 0,
+        divide: root_moves.iter().map(|&mov| (mov, 0)).collect(),
+    };
+
+    for (i, nodes) in counted {
+        result.total += nodes;
+        result.divide[i].1 = nodes;
+    }
+
+    result
+}
 
-    if depth == 0 {
+/// the number of leaf nodes `depth` plies below `pos`,
+/// with `bulk`, the moves of the last ply are counted without being made
+///
+/// once `stop` is set, the search returns as soon as possible with a meaningless count
+fn count(
+    pos: &mut pos::Position,
+    depth: i32,
+    bulk: bool,
+    stop: Option<&AtomicBool>,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> u64 {
+    if depth <= 0 {
         return 1;
     }
 
-    for mov in moves::gen_legal(pos, masks, zb) {
+    if depth > 1 && stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+        return 0;
+    }
+
+    let legal = moves::gen_legal(pos, masks, zb);
+
+    if bulk && depth == 1 {
+        return legal.len() as u64;
+    }
+
+    let mut nodes = 0;
+
+    for &mov in legal.iter() {
         pos.make_move(mov, zb);
-        let new_nodes = perft(pos, depth - 1, false, masks, zb);
-        nodes += new_nodes;
-        if is_root {
-            println!("{}: {new_nodes}", mov.to_uci_fmt())
+        nodes += count(pos, depth - 1, bulk, stop, masks, zb);
+        pos.unmake_move();
+    }
+
+    nodes
+}
+
+/// where this library's move generation and a reference engine's disagree, found by `perft::bisect()`
+#[cfg(feature = "uci-client")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Divergence {
+    /// the moves played from the position given to `perft::bisect()` to reach `fen`
+    pub path: Vec<moves::Move>,
+    /// the position where the generated moves differ
+    pub fen: String,
+    pub mismatch: Mismatch,
+}
+
+/// a move only one of the move generators produces
+#[cfg(feature = "uci-client")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Mismatch {
+    /// generated by this library, but not by the reference engine
+    Extra(moves::Move),
+    /// generated by the reference engine, but not by this library, in UCI notation since it may not be a valid move
+    Missing(String),
+}
+
+#[cfg(feature = "uci-client")]
+impl fmt::Display for Divergence {
+    /// e.g. `after g1f3 (<fen>): e8e7 is only generated by the reference engine`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self
+            .path
+            .iter()
+            .map(|mov| mov.to_uci_fmt())
+            .collect::<Vec<String>>();
+
+        match path.is_empty() {
+            true => write!(f, "at the root ({}): ", self.fen)?,
+            false => write!(f, "after {} ({}): ", path.join(" "), self.fen)?,
+        }
+
+        match &self.mismatch {
+            Mismatch::Extra(mov) => write!(f, "{} is only generated by libchess", mov.to_uci_fmt()),
+            Mismatch::Missing(mov) => write!(f, "{mov} is only generated by the reference engine"),
+        }
+    }
+}
+
+/// compares the divide of `pos` with the `go perft` of a reference engine, see `uci::Engine::perft()`,
+/// and follows a move with a different node count down, one ply at a time,
+/// until a position where the generated moves differ is found
+///
+/// `pos` has all moves played since `starting_fen`, like in `uci::Engine::go()`, and the engine must be done with
+/// the `uci` handshake, returns `None` if the node counts match, `pos` is left as it was
+#[cfg(feature = "uci-client")]
+pub fn bisect(
+    pos: &mut pos::Position,
+    starting_fen: &str,
+    depth: i32,
+    engine: &mut uci::Engine,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> Result<Option<Divergence>, uci::EngineError> {
+    bisect_from(pos, starting_fen, depth, engine, &mut Vec::new(), masks, zb)
+}
+
+#[cfg(feature = "uci-client")]
+fn bisect_from(
+    pos: &mut pos::Position,
+    starting_fen: &str,
+    depth: i32,
+    engine: &mut uci::Engine,
+    path: &mut Vec<moves::Move>,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> Result<Option<Divergence>, uci::EngineError> {
+    if depth <= 0 {
+        return Ok(None);
+    }
+
+    let ours = divide(pos, depth, masks, zb)
+        .into_iter()
+        .map(|(mov, nodes)| (mov, mov.to_uci_fmt_for(pos), nodes))
+        .collect::<Vec<_>>();
+    let theirs = engine
+        .perft(pos, starting_fen, depth as u32)?
+        .into_iter()
+        .collect::<HashMap<String, u64>>();
+
+    let mismatch = match ours.iter().find(|(_, uci, _)| !theirs.contains_key(uci)) {
+        Some(&(mov, _, _)) => Some(Mismatch::Extra(mov)),
+        None => {
+            let mut missing = theirs
+                .keys()
+                .filter(|&uci| ours.iter().all(|(_, ours, _)| ours != uci))
+                .collect::<Vec<_>>();
+            missing.sort();
+
+            missing.first().map(|&uci| Mismatch::Missing(uci.clone()))
+        }
+    };
+
+    if let Some(mismatch) = mismatch {
+        return Ok(Some(Divergence {
+            path: path.clone(),
+            fen: pos.to_fen(),
+            mismatch,
+        }));
+    }
+
+    for (mov, uci, nodes) in ours {
+        if theirs[&uci] == nodes {
+            continue;
         }
+
+        pos.make_move(mov, zb);
+        path.push(mov);
+        let divergence = bisect_from(pos, starting_fen, depth - 1, engine, path, masks, zb);
+        path.pop();
         pos.unmake_move();
+
+        // the node counts of the reference engine may not add up, then another move might still lead somewhere
+        if let Some(divergence) = divergence? {
+            return Ok(Some(divergence));
+        }
     }
 
-    if is_root {
-        println!("\nsearched {nodes} nodes");
+    Ok(None)
+}
+
+/// the positions `perft::bench()` is usually run on, the standard perft test positions
+pub const BENCH_POSITIONS: [&str; 6] = [
+    testpos::start().fen,
+    testpos::kiwipete().fen,
+    testpos::position_3().fen,
+    testpos::position_4().fen,
+    testpos::position_5().fen,
+    testpos::position_6().fen,
+];
+
+/// the node count and time of a single position of a benchmark
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BenchEntry {
+    pub fen: String,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+/// the results of `perft::bench()`
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct BenchReport {
+    pub depth: i32,
+    pub entries: Vec<BenchEntry>,
+}
+
+impl BenchEntry {
+    /// nodes per second
+    pub fn nps(&self) -> u64 {
+        nps(self.nodes, self.elapsed)
     }
+}
 
-    nodes
+impl BenchReport {
+    /// the nodes of all positions, the same for every run of the same positions and depth
+    pub fn nodes(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.nodes).sum()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.entries.iter().map(|entry| entry.elapsed).sum()
+    }
+
+    /// nodes per second over all positions
+    pub fn nps(&self) -> u64 {
+        nps(self.nodes(), self.elapsed())
+    }
+}
+
+impl fmt::Display for BenchReport {
+    /// writes a line for every position, e.g. `<fen>: 97862 nodes in 12ms (8155166 nps)`, then the total
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{}: {} nodes in {}ms ({} nps)",
+                entry.fen,
+                entry.nodes,
+                entry.elapsed.as_millis(),
+                entry.nps()
+            )?;
+        }
+
+        write!(
+            f,
+            "depth {}, total: {} nodes in {}ms ({} nps)",
+            self.depth,
+            self.nodes(),
+            self.elapsed().as_millis(),
+            self.nps()
+        )
+    }
+}
+
+fn nps(nodes: u64, elapsed: Duration) -> u64 {
+    match elapsed.as_secs_f64() {
+        0.0 => 0,
+        secs => (nodes as f64 / secs) as u64,
+    }
+}
+
+/// runs perft on every position, one after another on the current thread, and times them,
+/// e.g. `perft::bench(&perft::BENCH_POSITIONS, 4, &masks, &zb)`
+///
+/// the node counts only depend on the positions and depth, so a change in them means move generation changed
+pub fn bench(
+    positions: &[&str],
+    depth: i32,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> BenchReport {
+    let entries = positions
+        .iter()
+        .map(|&fen| {
+            let mut pos = pos::Position::from_fen(fen, zb);
+            let timer = Instant::now();
+            let nodes = count(&mut pos, depth, true, None, masks, zb);
+
+            BenchEntry {
+                fen: fen.to_string(),
+                nodes,
+                elapsed: timer.elapsed(),
+            }
+        })
+        .collect();
+
+    BenchReport { depth, entries }
+}
+
+/// everything that a zobrist key is supposed to identify a position by
+type KeyedState = (
+    [piece::Piece; 64],
+    color::Color,
+    pos::CastleRights,
+    Option<pos::Square>,
+);
+
+/// results of `perft::zobrist_check()`
+#[derive(Debug, Clone, Default)]
+pub struct ZobristReport {
+    /// number of positions visited, including transpositions
+    pub nodes: u64,
+    /// number of distinct keys seen
+    pub unique_positions: usize,
+    /// keys that were generated by more than one distinct position
+    pub collisions: Vec<zobrist::Key>,
+    /// move sequences (from the root) that led to a position whose incrementally updated key
+    /// (or pawn key) doesn't match a key hashed from scratch
+    pub mismatches: Vec<Vec<moves::Move>>,
+}
+
+impl ZobristReport {
+    /// returns true if no collisions or mismatches were found
+    pub fn is_ok(&self) -> bool {
+        self.collisions.is_empty() && self.mismatches.is_empty()
+    }
+}
+
+/// walks the move tree like `perft::perft()`, verifying the incremental keys of every position
+/// against `zobrist::hash()` and `zobrist::pawn_hash()`,
+/// and recording every key to find collisions between distinct positions
+pub fn zobrist_check(
+    pos: &mut pos::Position,
+    depth: i32,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> ZobristReport {
+    fn walk(
+        pos: &mut pos::Position,
+        depth: i32,
+        path: &mut Vec<moves::Move>,
+        seen: &mut HashMap<zobrist::Key, KeyedState>,
+        report: &mut ZobristReport,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) {
+        report.nodes += 1;
+
+        if pos.key() != zobrist::hash(pos, zb) || pos.pawn_key() != zobrist::pawn_hash(pos, zb) {
+            report.mismatches.push(path.clone());
+        }
+
+        let state = (
+            *pos.board(),
+            pos.side_to_move(),
+            pos.castle_rights(),
+            pos.ep_square(),
+        );
+
+        match seen.get(&pos.key()) {
+            Some(other) => {
+                if *other != state && !report.collisions.contains(&pos.key()) {
+                    report.collisions.push(pos.key());
+                }
+            }
+            None => {
+                seen.insert(pos.key(), state);
+            }
+        }
+
+        if depth == 0 {
+            return;
+        }
+
+        for &mov in moves::gen_legal(pos, masks, zb).iter() {
+            pos.make_move(mov, zb);
+            path.push(mov);
+            walk(pos, depth - 1, path, seen, report, masks, zb);
+            path.pop();
+            pos.unmake_move();
+        }
+    }
+
+    let mut report = ZobristReport::default();
+    let mut seen = HashMap::new();
+
+    walk(
+        pos,
+        depth,
+        &mut Vec::new(),
+        &mut seen,
+        &mut report,
+        masks,
+        zb,
+    );
+
+    report.unique_positions = seen.len();
+
+    report
+}
+
+/// the results of a perft suite, see `perft::test_epd()`
+#[cfg(feature = "perft-suite")]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct SuiteReport {
+    pub positions: Vec<PositionReport>,
+    /// how long the whole suite took
+    pub elapsed: Duration,
+}
+
+/// the results of the depths tested for a single position of a perft suite
+#[cfg(feature = "perft-suite")]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct PositionReport {
+    pub fen: String,
+    pub depths: Vec<DepthReport>,
+}
+
+/// the result of a perft test of a single depth
+#[cfg(feature = "perft-suite")]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct DepthReport {
+    pub depth: i32,
+    pub expected: i64,
+    pub actual: i64,
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "perft-suite")]
+impl DepthReport {
+    pub fn passed(&self) -> bool {
+        self.actual == self.expected
+    }
+}
+
+#[cfg(feature = "perft-suite")]
+impl SuiteReport {
+    /// every tested depth with the fen of its position
+    pub fn results(&self) -> impl Iterator<Item = (&str, &DepthReport)> {
+        self.positions.iter().flat_map(|position| {
+            position
+                .depths
+                .iter()
+                .map(|depth| (position.fen.as_str(), depth))
+        })
+    }
+
+    /// the tested depths whose node count didn't match, with the fen of their position
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &DepthReport)> {
+        self.results().filter(|(_, depth)| !depth.passed())
+    }
+
+    pub fn passed(&self) -> u32 {
+        self.results().filter(|(_, depth)| depth.passed()).count() as u32
+    }
+
+    pub fn failed(&self) -> u32 {
+        self.failures().count() as u32
+    }
+
+    /// true if every tested depth passed
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// sends the report to `reporter` as the same events `perft::test_epd_with()` sends while testing,
+    /// e.g. to print it with a `report::TerminalReporter`
+    pub fn report_to(&self, reporter: &mut impl report::Reporter) {
+        for position in &self.positions {
+            let fen = &position.fen;
+
+            reporter.report(&report::Event::PerftPosition { fen });
+
+            for depth in &position.depths {
+                reporter.report(&report::Event::PerftResult {
+                    fen,
+                    depth: depth.depth,
+                    expected: depth.expected,
+                    actual: depth.actual,
+                });
+            }
+        }
+
+        reporter.report(&report::Event::PerftSuiteFinished {
+            passed: self.passed(),
+            failed: self.failed(),
+        });
+    }
+}
+
+#[cfg(feature = "perft-suite")]
+impl fmt::Display for SuiteReport {
+    /// writes the report as plain text, a line for every tested depth followed by a summary
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for position in &self.positions {
+            writeln!(f, "{}", position.fen)?;
+
+            for depth in &position.depths {
+                write!(
+                    f,
+                    "  depth {}: expected {}, actual {} in {}ms",
+                    depth.depth,
+                    depth.expected,
+                    depth.actual,
+                    depth.elapsed.as_millis()
+                )?;
+
+                match depth.passed() {
+                    true => writeln!(f, ", ok")?,
+                    false => writeln!(f, " ({:+}), failed", depth.actual - depth.expected)?,
+                }
+            }
+        }
+
+        write!(
+            f,
+            "{} passed, {} failed in {}ms",
+            self.passed(),
+            self.failed(),
+            self.elapsed.as_millis()
+        )
+    }
 }
 
 /// parses an epd file containing perft test positions and compares the results in the file
 /// to the results given by the perft function
+///
+/// nothing is printed, see `SuiteReport::report_to()` or `perft::test_epd_with()` to print the results
+#[cfg(feature = "perft-suite")]
 pub fn test_epd(
     path: &str,
     max_depth: i32,
@@ -46,131 +729,101 @@ pub fn test_epd(
     start_at: usize,
     masks: &AttackMasks,
     zb: &ZobristValues,
-) {
-    #[derive(Debug)]
-    struct TestCase<'a> {
-        fen: &'a str,
-        depths: Vec<i32>,
-        node_counts: Vec<i64>,
-    }
+) -> SuiteReport {
+    test_epd_with(
+        path,
+        max_depth,
+        num_tests,
+        start_at,
+        masks,
+        zb,
+        &mut |_: &report::Event| {},
+    )
+}
 
+/// same as `perft::test_epd()`, but the results are also sent to `reporter` while testing,
+/// e.g. a `report::TerminalReporter` to follow the progress, or a `report::JsonLinesReporter` for scripts
+#[cfg(feature = "perft-suite")]
+pub fn test_epd_with(
+    path: &str,
+    max_depth: i32,
+    num_tests: i32,
+    start_at: usize,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+    reporter: &mut impl report::Reporter,
+) -> SuiteReport {
     let test_cases =
         String::from_utf8_lossy(&fs::read(path).expect("failed to read file")).to_string();
 
-    let lines: Vec<&str> = test_cases.split('\n').collect();
-
-    let mut test_cases: Vec<TestCase> = Vec::new();
-
-    for line in lines {
-        let fen = line.split(';').nth(0).unwrap();
-        let node_counts: Vec<i64> = line
-            .replace(" ", "")
-            .split(";D")
-            .skip(1)
-            .map(|n| {
-                n.chars()
-                    .skip(1)
-                    .collect::<String>()
-                    .trim()
-                    .parse()
-                    .unwrap()
-            })
-            .collect();
-
-        let depths: Vec<i32> = line
-            .replace(" ", "")
-            .split(";D")
-            .skip(1)
-            .map(|n| {
-                n.chars()
-                    .take(1)
-                    .collect::<String>()
-                    .trim()
-                    .parse()
-                    .unwrap()
-            })
-            .collect();
+    let test_cases: Vec<(String, Vec<(i32, i64)>)> = test_cases
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let test_case = epd::Epd::parse(line, zb).expect("invalid EPD record");
+            let fen = test_case.pos.to_fen();
 
-        test_cases.push(TestCase {
-            fen,
-            depths,
-            node_counts,
-        });
-    }
+            (fen, test_case.perft_results())
+        })
+        .collect();
 
-    let mut ok = 0;
-    let mut failed = 0;
-    let mut i = 0;
+    let start = Instant::now();
+    let mut suite = SuiteReport::default();
 
-    for test_case in test_cases.iter().skip(start_at) {
-        if test_case.depths[0] > max_depth {
+    for (fen, results) in test_cases.iter().skip(start_at) {
+        if results.first().is_none_or(|&(depth, _)| depth > max_depth) {
             continue;
         }
-        println!("\ntesting position: {}", test_case.fen.bright_yellow());
 
-        let mut j = 0;
-        for &node_count in &test_case.node_counts {
-            if test_case.depths[j] > max_depth {
+        reporter.report(&report::Event::PerftPosition { fen });
+
+        let mut position = PositionReport {
+            fen: fen.clone(),
+            depths: Vec::new(),
+        };
+
+        for &(depth, node_count) in results {
+            if depth > max_depth {
                 break;
             }
-            print!(
-                "depth: {}; expected nodes: {}; ",
-                test_case.depths[j],
-                node_count.to_string().yellow()
-            );
+
+            let timer = Instant::now();
             let nodes = perft(
-                &mut pos::Position::from_fen(test_case.fen, zb),
-                test_case.depths[j],
+                &mut pos::Position::from_fen(fen, zb),
+                depth,
                 false,
                 masks,
                 zb,
             );
-            if nodes == node_count {
-                println!(
-                    "actual nodes: {}; {}",
-                    nodes.to_string().yellow(),
-                    "ok".green()
-                );
-                ok += 1;
-            } else {
-                println!(
-                    "actual nodes: {} ({}); {}",
-                    nodes.to_string().red(),
-                    {
-                        let s = String::from(if nodes > node_count { "+" } else { "" });
-                        (s + (nodes - node_count).to_string().as_str()).red()
-                    },
-                    "failed".red()
-                );
-                failed += 1;
-            }
 
-            j += 1;
+            reporter.report(&report::Event::PerftResult {
+                fen,
+                depth,
+                expected: node_count,
+                actual: nodes,
+            });
+
+            position.depths.push(DepthReport {
+                depth,
+                expected: node_count,
+                actual: nodes,
+                elapsed: timer.elapsed(),
+            });
         }
 
-        i += 1;
+        suite.positions.push(position);
 
-        if i == num_tests {
+        if suite.positions.len() as i32 == num_tests {
             break;
         }
     }
 
-    let all = ok + failed;
+    suite.elapsed = start.elapsed();
 
-    println!(
-        "results: out of {} tests, {} passed, {} failed",
-        all.to_string().yellow().bold(),
-        if ok == all {
-            ok.to_string().green().bold()
-        } else {
-            ok.to_string().yellow().bold()
-        },
-        if failed == all {
-            failed.to_string().red().bold()
-        } else if failed == 0 {
-            failed.to_string().green().bold()
-        } else {
-            failed.to_string().red().bold()
-        }
-    );
+    reporter.report(&report::Event::PerftSuiteFinished {
+        passed: suite.passed(),
+        failed: suite.failed(),
+    });
+
+    suite
 }
@@ -0,0 +1,552 @@
+// file for reading and writing games in portable game notation (PGN)
+
+use std::fmt;
+
+use crate::{AttackMasks, ZobristValues, color, moves, pos};
+
+/// the tags every PGN game has, in the order they're written
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// the longest a line of movetext gets before it's wrapped
+const MAX_LINE_LEN: usize = 80;
+
+/// the results a game can end with
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// an error found while parsing PGN
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PgnError {
+    /// something that doesn't belong in PGN, e.g. a `)` without a `(`, or a variation before any move
+    Unexpected(String),
+    /// a tag pair, comment or variation that isn't closed before the end of the input
+    Unterminated,
+    /// a move that can't be played in the position it's in
+    InvalidMove { san: String, err: moves::SanError },
+    /// the value of a `FEN` tag that isn't a valid FEN string
+    InvalidFen(String),
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::Unexpected(token) => write!(f, "unexpected {token:?} in PGN"),
+            PgnError::Unterminated => write!(f, "unterminated tag pair, comment or variation"),
+            PgnError::InvalidMove { san, err } => write!(f, "invalid move {san:?}: {err}"),
+            PgnError::InvalidFen(fen) => write!(f, "invalid FEN tag {fen:?}"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// a move of a game, with its annotations and the variations that can be played instead of it
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Node {
+    pub mov: moves::Move,
+    /// the move as it's written in the PGN
+    pub san: String,
+    /// numeric annotation glyphs, `$1` or `!`, `$2` or `?` and so on
+    pub nags: Vec<u8>,
+    /// the comments after the move
+    pub comments: Vec<String>,
+    /// lines that can be played instead of this move
+    pub variations: Vec<Variation>,
+}
+
+/// a line of moves, either the mainline of a game or a variation
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Variation {
+    /// the comments before the first move
+    pub comments: Vec<String>,
+    pub moves: Vec<Node>,
+}
+
+/// a game read from PGN
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Game {
+    /// the tag pairs, in the order they're written
+    pub tags: Vec<(String, String)>,
+    pub mainline: Variation,
+    /// `"1-0"`, `"0-1"`, `"1/2-1/2"` or `"*"`, taken from the end of the movetext,
+    /// or the `Result` tag if the movetext has none
+    pub result: String,
+}
+
+impl Game {
+    /// the value of the tag `name`, if the game has it
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// the position the game starts from, from the `FEN` tag or the standard starting position
+    ///
+    /// assumes the game was parsed successfully, so the `FEN` tag is valid, panics otherwise
+    pub fn start_position(&self, zb: &ZobristValues) -> pos::Position {
+        pos::Position::from_fen(self.tag("FEN").unwrap_or(pos::START_FEN), zb)
+    }
+
+    /// the position at the end of the mainline, with the moves of the mainline in its history
+    ///
+    /// like `Game::start_position()`, assumes the game was parsed successfully
+    pub fn position(&self, zb: &ZobristValues) -> pos::Position {
+        let mut pos = self.start_position(zb);
+
+        for node in &self.mainline.moves {
+            pos.make_move(node.mov, zb);
+        }
+
+        pos
+    }
+}
+
+/// parses the first game in `pgn`
+pub fn parse_pgn(pgn: &str, masks: &AttackMasks, zb: &ZobristValues) -> Result<Game, PgnError> {
+    games(pgn, masks, zb)
+        .next()
+        .unwrap_or(Err(PgnError::Unterminated))
+}
+
+/// an iterator over all games in `pgn`, e.g. the contents of a PGN database
+///
+/// after a game that can't be parsed, the iterator skips to the next game, see `pgn::Games`
+pub fn games<'a>(pgn: &'a str, masks: &'a AttackMasks, zb: &'a ZobristValues) -> Games<'a> {
+    Games {
+        lexer: Lexer { input: pgn, at: 0 },
+        masks,
+        zb,
+    }
+}
+
+/// an iterator over the games of a string of PGN, returned by `pgn::games()`
+pub struct Games<'a> {
+    lexer: Lexer<'a>,
+    masks: &'a AttackMasks,
+    zb: &'a ZobristValues,
+}
+
+impl Iterator for Games<'_> {
+    type Item = Result<Game, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Ok(None) = self.lexer.peek() {
+            return None;
+        }
+
+        let game = self.game();
+
+        if game.is_err() {
+            self.lexer.skip_game();
+        }
+
+        Some(game)
+    }
+}
+
+impl Games<'_> {
+    fn game(&mut self) -> Result<Game, PgnError> {
+        let mut game = Game::default();
+
+        while let Some(Token::Tag(name, value)) = self.lexer.peek()? {
+            game.tags.push((name, value));
+            self.lexer.next()?;
+        }
+
+        if let Some(fen) = game.tag("FEN")
+            && pos::validate_fen(fen).is_err()
+        {
+            return Err(PgnError::InvalidFen(fen.to_string()));
+        }
+
+        let mut pos = game.start_position(self.zb);
+
+        game.mainline = self.line(&mut pos, false)?;
+        game.result = match self.lexer.peek()? {
+            Some(Token::Symbol(result)) if RESULTS.contains(&result) => {
+                self.lexer.next()?;
+                result.to_string()
+            }
+            _ => game.tag("Result").unwrap_or("*").to_string(),
+        };
+
+        Ok(game)
+    }
+
+    /// parses moves until the end of a variation, or until the result or the next game for the mainline,
+    /// `pos` is left as it was
+    fn line(&mut self, pos: &mut pos::Position, nested: bool) -> Result<Variation, PgnError> {
+        let mut line = Variation::default();
+
+        loop {
+            let token = match self.lexer.peek()? {
+                None if nested => return Err(PgnError::Unterminated),
+                None | Some(Token::Tag(..)) => break,
+                Some(Token::Symbol(result)) if !nested && RESULTS.contains(&result) => break,
+                Some(token) => token,
+            };
+
+            self.lexer.next()?;
+
+            match token {
+                Token::Comment(comment) => match line.moves.last_mut() {
+                    Some(node) => node.comments.push(comment),
+                    None => line.comments.push(comment),
+                },
+                Token::Nag(nag) => match line.moves.last_mut() {
+                    Some(node) => node.nags.push(nag),
+                    None => return Err(PgnError::Unexpected(format!("${nag}"))),
+                },
+                Token::Open => {
+                    let Some(node) = line.moves.last_mut() else {
+                        return Err(PgnError::Unexpected(String::from("(")));
+                    };
+
+                    pos.unmake_move();
+                    let variation = self.line(pos, true);
+                    pos.make_move(node.mov, self.zb);
+
+                    node.variations.push(variation?);
+                }
+                Token::Close if nested => break,
+                Token::Close => return Err(PgnError::Unexpected(String::from(")"))),
+                Token::Symbol(symbol) => self.symbol(symbol, pos, &mut line)?,
+                Token::Tag(..) => unreachable!(),
+            }
+        }
+
+        for _ in &line.moves {
+            pos.unmake_move();
+        }
+
+        Ok(line)
+    }
+
+    /// handles a move, a move number or an annotation like `!?`
+    fn symbol(
+        &mut self,
+        symbol: &str,
+        pos: &mut pos::Position,
+        line: &mut Variation,
+    ) -> Result<(), PgnError> {
+        // move numbers, possibly written without a space before the move, e.g. `12.Nf3` or `12...Nf6`
+        let symbol = match symbol.rfind('.') {
+            Some(i) if symbol.starts_with(|ch: char| ch.is_ascii_digit()) => &symbol[i + 1..],
+            _ => symbol,
+        };
+
+        let san = symbol.trim_end_matches(['!', '?']);
+        let nag = nag_of(&symbol[san.len()..]);
+
+        if san.is_empty() {
+            match (line.moves.last_mut(), nag) {
+                (Some(node), Some(nag)) => node.nags.push(nag),
+                _ if symbol.is_empty() => {}
+                _ => return Err(PgnError::Unexpected(symbol.to_string())),
+            }
+
+            return Ok(());
+        }
+
+        let mov = moves::Move::from_san(san, pos, self.masks, self.zb).map_err(|err| {
+            PgnError::InvalidMove {
+                san: san.to_string(),
+                err,
+            }
+        })?;
+
+        pos.make_move(mov, self.zb);
+        line.moves.push(Node {
+            mov,
+            san: san.to_string(),
+            nags: nag.into_iter().collect(),
+            comments: Vec::new(),
+            variations: Vec::new(),
+        });
+
+        Ok(())
+    }
+}
+
+/// the numeric annotation glyph of a move suffix like `!?`
+fn nag_of(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token<'a> {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    /// moves, move numbers, results and annotations
+    Symbol(&'a str),
+}
+
+/// splits PGN into tokens, skipping whitespace and `%` escaped lines
+struct Lexer<'a> {
+    input: &'a str,
+    at: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&mut self) -> Result<Option<Token<'a>>, PgnError> {
+        let at = self.at;
+        let token = self.next();
+        self.at = at;
+
+        token
+    }
+
+    fn next(&mut self) -> Result<Option<Token<'a>>, PgnError> {
+        self.skip_whitespace();
+
+        let rest = &self.input[self.at..];
+        let Some(ch) = rest.chars().next() else {
+            return Ok(None);
+        };
+
+        let token = match ch {
+            '[' => {
+                let end = tag_end(rest).ok_or(PgnError::Unterminated)?;
+                let tag = rest[1..end].trim();
+                let (name, value) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .ok_or_else(|| PgnError::Unexpected(rest[..=end].to_string()))?;
+
+                self.at += end + 1;
+                Token::Tag(
+                    name.to_string(),
+                    value.replace("\\\"", "\"").replace("\\\\", "\\"),
+                )
+            }
+            '{' => {
+                let end = rest.find('}').ok_or(PgnError::Unterminated)?;
+
+                self.at += end + 1;
+                Token::Comment(rest[1..end].trim().to_string())
+            }
+            ';' => {
+                let end = rest.find('\n').unwrap_or(rest.len());
+
+                self.at += end;
+                Token::Comment(rest[1..end].trim().to_string())
+            }
+            '(' => {
+                self.at += 1;
+                Token::Open
+            }
+            ')' => {
+                self.at += 1;
+                Token::Close
+            }
+            '$' => {
+                let digits = rest[1..]
+                    .find(|ch: char| !ch.is_ascii_digit())
+                    .unwrap_or(rest.len() - 1);
+                let nag = rest[1..=digits]
+                    .parse()
+                    .map_err(|_| PgnError::Unexpected(rest[..=digits].to_string()))?;
+
+                self.at += digits + 1;
+                Token::Nag(nag)
+            }
+            _ => {
+                let end = rest
+                    .find(|ch: char| ch.is_whitespace() || "[]{}();$\"".contains(ch))
+                    .unwrap_or(rest.len());
+
+                if end == 0 {
+                    return Err(PgnError::Unexpected(ch.to_string()));
+                }
+
+                self.at += end;
+                Token::Symbol(&rest[..end])
+            }
+        };
+
+        Ok(Some(token))
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = &self.input[self.at..];
+            let trimmed = rest.trim_start();
+            self.at += rest.len() - trimmed.len();
+
+            let line_start = self.at == 0 || self.input[..self.at].ends_with('\n');
+
+            if !(line_start && trimmed.starts_with('%')) {
+                return;
+            }
+
+            self.at += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    /// skips to the next line starting with a tag pair after movetext, used to recover from errors
+    fn skip_game(&mut self) {
+        let mut movetext = false;
+
+        while self.at < self.input.len() {
+            let rest = &self.input[self.at..];
+            let line_end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+
+            if rest.starts_with('[') && movetext {
+                return;
+            }
+
+            movetext |= !rest.starts_with('[') && !rest[..line_end].trim().is_empty();
+            self.at += line_end;
+        }
+    }
+}
+
+/// the index of the `]` closing the tag pair at the start of `rest`, skipping escaped quotes in the value
+fn tag_end(rest: &str) -> Option<usize> {
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, ch) in rest.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ']' if !quoted => return Some(i),
+            '\n' => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// returns the result of a game in PGN format, `"1-0"`, `"0-1"`, `"1/2-1/2"` or `"*"` if the game isn't over
+pub fn result_str(outcome: Option<pos::Outcome>) -> &'static str {
+    match outcome {
+        Some(outcome) => match outcome.winner() {
+            Some(color::WHITE) => "1-0",
+            Some(_) => "0-1",
+            None => "1/2-1/2",
+        },
+        None => "*",
+    }
+}
+
+/// writes the game leading to `pos` as PGN, with the moves of `Position::history()` as SAN movetext,
+/// the game starts where the history does
+///
+/// `tags` are written after the seven tag roster, tags of the roster that are missing from `tags` are `"?"`,
+/// except `Result`, which is taken from `Position::outcome()` if it's missing,
+/// `SetUp` and `FEN` tags are added if the game didn't start from the standard starting position,
+/// and a `Termination` tag from `Termination::pgn_tag()` if the game is over and `tags` has none
+pub fn export_pgn(
+    pos: &pos::Position,
+    tags: &[(&str, &str)],
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> String {
+    let mut game = pos.clone();
+    let mut moves = Vec::new();
+
+    // positions from `Position::from_ply()` can have a move played but no history to unmake it with
+    while !game.history().is_empty() {
+        moves.extend(game.move_played());
+        game.unmake_move();
+    }
+
+    moves.reverse();
+
+    let start_fen = game.to_fen();
+    let mut movetext = Vec::new();
+
+    for (i, &mov) in moves.iter().enumerate() {
+        if game.side_to_move() == color::WHITE {
+            movetext.push(format!("{}.", game.fullmove_number()));
+        } else if i == 0 {
+            movetext.push(format!("{}...", game.fullmove_number()));
+        }
+
+        movetext.push(mov.to_san(&mut game, masks, zb));
+        game.make_move(mov, zb);
+    }
+
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(tag, _)| *tag == name)
+            .map(|(_, value)| value.to_string())
+    };
+
+    let outcome = game.outcome(masks, zb);
+    let result = tag("Result").unwrap_or_else(|| result_str(outcome).to_string());
+    movetext.push(result.clone());
+
+    let mut pgn = String::new();
+
+    for name in SEVEN_TAG_ROSTER {
+        let value = match name {
+            "Result" => result.clone(),
+            _ => tag(name).unwrap_or_else(|| String::from("?")),
+        };
+
+        push_tag(&mut pgn, name, &value);
+    }
+
+    if start_fen != pos::START_FEN {
+        push_tag(&mut pgn, "SetUp", "1");
+        push_tag(&mut pgn, "FEN", &start_fen);
+    }
+
+    if let Some(outcome) = outcome
+        && tag("Termination").is_none()
+    {
+        push_tag(&mut pgn, "Termination", outcome.termination().pgn_tag());
+    }
+
+    for (name, value) in tags {
+        if !SEVEN_TAG_ROSTER.contains(name) && !["SetUp", "FEN"].contains(name) {
+            push_tag(&mut pgn, name, value);
+        }
+    }
+
+    pgn.push('\n');
+
+    let mut line_len = 0;
+
+    for token in movetext {
+        if line_len > 0 && line_len + 1 + token.len() > MAX_LINE_LEN {
+            pgn.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            pgn.push(' ');
+            line_len += 1;
+        }
+
+        line_len += token.len();
+        pgn.push_str(&token);
+    }
+
+    pgn.push('\n');
+    pgn
+}
+
+/// writes a tag pair on its own line, escaping quotes and backslashes in the value
+fn push_tag(pgn: &mut String, name: &str, value: &str) {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+
+    pgn.push_str(&format!("[{name} \"{value}\"]\n"));
+}
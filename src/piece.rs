@@ -1,5 +1,7 @@
 pub mod bb;
 
+use std::fmt;
+
 use crate::color;
 
 pub type Piece = u8;
@@ -28,6 +30,43 @@ pub const BLACK_QUEEN: Piece = QUEEN | color::BLACK;
 pub const BLACK_KING: Piece = KING | color::BLACK;
 pub const SLIDING_PIECE: Piece = ROOK | BISHOP | QUEEN;
 
+/// error returned by `piece::parse()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ParsePieceError {
+    /// the string isn't a single character
+    Length,
+    /// the character isn't one of `pnbrqk` or `PNBRQK`
+    Char(char),
+}
+
+impl fmt::Display for ParsePieceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsePieceError::Length => write!(f, "a piece has to be a single character"),
+            ParsePieceError::Char(ch) => write!(f, "invalid piece character {ch:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ParsePieceError {}
+
+/// converts a piece character like in FEN strings to a piece, uppercase for white and lowercase for black,
+/// returning an error for anything else instead of a piece without a type like `piece::from_char()`
+///
+/// `Piece` is an integer, so `"p".parse::<Piece>()` parses a number, use this for piece characters instead
+pub fn parse(string: &str) -> Result<Piece, ParsePieceError> {
+    let mut chars = string.chars();
+
+    let (Some(ch), None) = (chars.next(), chars.next()) else {
+        return Err(ParsePieceError::Length);
+    };
+
+    match ch {
+        'p' | 'n' | 'b' | 'r' | 'q' | 'k' | 'P' | 'N' | 'B' | 'R' | 'Q' | 'K' => Ok(from_char(ch)),
+        _ => Err(ParsePieceError::Char(ch)),
+    }
+}
+
 /// returns the piece equivalent to `ch`, uppercase characters indicate white piece,
 /// while lowercase characters indicate black pieces,
 ///
@@ -84,6 +123,21 @@ pub fn as_char(piece: Piece) -> char {
     }
 }
 
+/// a piece that displays as its character, see `piece::display()`
+struct Display(Piece);
+
+impl fmt::Display for Display {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", as_char(self.0))
+    }
+}
+
+/// wraps `piece` so it can be formatted as its character, e.g. `format!("{}", piece::display(BLACK_PAWN))`
+/// gives `"p"`, since `Piece` is an integer, which already displays as a number
+pub fn display(piece: Piece) -> impl fmt::Display {
+    Display(piece)
+}
+
 /// returns a UTF-8 symbol for each piece,
 /// may not display properly if your font doesn't support them
 ///
@@ -106,6 +160,40 @@ pub fn as_symbol(piece: Piece) -> &'static str {
     }
 }
 
+/// returns a 4 bit code for `piece`, used in binary formats,
+/// 0 for no piece, 1-6 for white pieces and 9-14 for black pieces, in the order pawn to king
+pub fn to_code(piece: Piece) -> u8 {
+    if piece == NONE {
+        return 0;
+    }
+
+    (of(piece).trailing_zeros() as u8 + 1)
+        | match color::of(piece) {
+            color::BLACK => 8,
+            _ => 0,
+        }
+}
+
+/// returns the piece of a code made by `to_code()`, `None` if `code` isn't a valid code
+///
+/// code 0 returns `Some(NONE)`
+pub fn from_code(code: u8) -> Option<Piece> {
+    let kind = code & 7;
+
+    match (code, kind) {
+        (0, _) => Some(NONE),
+        (_, 1..=6) if code < 16 => Some(
+            (1 << (kind - 1))
+                | if code & 8 != 0 {
+                    color::BLACK
+                } else {
+                    color::WHITE
+                },
+        ),
+        _ => None,
+    }
+}
+
 /// returns true if `piece` is any piece in `pieces`, otherwise false
 #[inline(always)]
 pub fn is_either(piece: Piece, pieces: &Vec<Piece>) -> bool {
@@ -1,5 +1,7 @@
 // file for all bitboard related stuff
 
+pub mod batch;
+
 use crate::{AttackMasks, color, piece, pos};
 
 pub type Bitboard = u64;
@@ -7,13 +9,53 @@ pub type Bitboard = u64;
 pub const EMPTY: Bitboard = 0;
 
 pub const FILE_A_MASK: Bitboard = 0x0101010101010101;
+pub const FILE_B_MASK: Bitboard = FILE_A_MASK << 1;
+pub const FILE_C_MASK: Bitboard = FILE_A_MASK << 2;
+pub const FILE_D_MASK: Bitboard = FILE_A_MASK << 3;
+pub const FILE_E_MASK: Bitboard = FILE_A_MASK << 4;
+pub const FILE_F_MASK: Bitboard = FILE_A_MASK << 5;
+pub const FILE_G_MASK: Bitboard = FILE_A_MASK << 6;
 pub const FILE_H_MASK: Bitboard = 0x8080808080808080;
 pub const FILE_AB_MASK: Bitboard = FILE_A_MASK | (FILE_A_MASK << 1);
 pub const FILE_GH_MASK: Bitboard = FILE_H_MASK | (FILE_H_MASK >> 1);
 
 pub const RANK_1_MASK: Bitboard = 0x00000000000000FF;
+pub const RANK_2_MASK: Bitboard = RANK_1_MASK << 8;
+pub const RANK_3_MASK: Bitboard = RANK_1_MASK << 16;
+pub const RANK_4_MASK: Bitboard = RANK_1_MASK << 24;
+pub const RANK_5_MASK: Bitboard = RANK_1_MASK << 32;
+pub const RANK_6_MASK: Bitboard = RANK_1_MASK << 40;
+pub const RANK_7_MASK: Bitboard = RANK_1_MASK << 48;
 pub const RANK_8_MASK: Bitboard = 0xFF00000000000000;
 
+/// all squares
+pub const FULL: Bitboard = !EMPTY;
+
+/// d4, e4, d5 and e5
+pub const CENTER: Bitboard = (FILE_D_MASK | FILE_E_MASK) & (RANK_4_MASK | RANK_5_MASK);
+
+/// the 4x4 block of squares from c3 to f6
+pub const EXTENDED_CENTER: Bitboard = (FILE_C_MASK | FILE_D_MASK | FILE_E_MASK | FILE_F_MASK)
+    & (RANK_3_MASK | RANK_4_MASK | RANK_5_MASK | RANK_6_MASK);
+
+/// files e to h
+pub const KINGSIDE: Bitboard = FILE_E_MASK | FILE_F_MASK | FILE_G_MASK | FILE_H_MASK;
+
+/// files a to d
+pub const QUEENSIDE: Bitboard = !KINGSIDE;
+
+/// ranks 1 to 4
+pub const WHITE_HALF: Bitboard = RANK_1_MASK | RANK_2_MASK | RANK_3_MASK | RANK_4_MASK;
+
+/// ranks 5 to 8
+pub const BLACK_HALF: Bitboard = !WHITE_HALF;
+
+/// all light squares
+pub const LIGHT_SQUARES: Bitboard = 0x55AA55AA55AA55AA;
+
+/// all dark squares
+pub const DARK_SQUARES: Bitboard = !LIGHT_SQUARES;
+
 pub const MAIN_DIAG_MASK: Bitboard = 0x8040201008040201;
 pub const MAIN_ANTI_DIAG_MASK: Bitboard = 0x0102040810204080;
 
@@ -81,6 +123,88 @@ pub fn c_to_idx(color: color::Color) -> usize {
     }
 }
 
+/// returns a bitboard with only the bit of `square` set
+#[inline(always)]
+pub const fn square_bb(square: pos::Square) -> Bitboard {
+    1 << square
+}
+
+/// returns a mask of all squares on `file`
+#[inline(always)]
+pub const fn file_bb(file: pos::File) -> Bitboard {
+    FILE_A_MASK << file
+}
+
+/// returns a mask of all squares on `rank`
+#[inline(always)]
+pub const fn rank_bb(rank: pos::Rank) -> Bitboard {
+    RANK_1_MASK << (rank * 8)
+}
+
+/// returns a mask of the half of the board `color` starts on
+#[inline(always)]
+pub const fn own_half(color: color::Color) -> Bitboard {
+    match color {
+        color::WHITE => WHITE_HALF,
+        _ => BLACK_HALF,
+    }
+}
+
+/// returns a mask of the half of the board the opponent of `color` starts on
+#[inline(always)]
+pub const fn enemy_half(color: color::Color) -> Bitboard {
+    !own_half(color)
+}
+
+/// returns a mask of the rectangle that has `a` and `b` as opposite corners, both included
+///
+/// `e.g. rect(make_sq(FILE_C, RANK_3), make_sq(FILE_F, RANK_6)) == EXTENDED_CENTER`
+pub const fn rect(a: pos::Square, b: pos::Square) -> Bitboard {
+    let (file_a, rank_a) = ((a & 7) as pos::File, (a >> 3) as pos::Rank);
+    let (file_b, rank_b) = ((b & 7) as pos::File, (b >> 3) as pos::Rank);
+
+    let (min_file, max_file) = if file_a < file_b {
+        (file_a, file_b)
+    } else {
+        (file_b, file_a)
+    };
+    let (min_rank, max_rank) = if rank_a < rank_b {
+        (rank_a, rank_b)
+    } else {
+        (rank_b, rank_a)
+    };
+
+    let mut files = EMPTY;
+    let mut file = min_file;
+    while file <= max_file {
+        files |= file_bb(file);
+        file += 1;
+    }
+
+    let mut ranks = EMPTY;
+    let mut rank = min_rank;
+    while rank <= max_rank {
+        ranks |= rank_bb(rank);
+        rank += 1;
+    }
+
+    files & ranks
+}
+
+/// flips a bitboard vertically, rank 1 becomes rank 8 and so on,
+/// the bitboard equivalent of `square ^ 56`
+#[inline(always)]
+pub const fn flip_vertical(bb: Bitboard) -> Bitboard {
+    bb.swap_bytes()
+}
+
+/// mirrors a bitboard horizontally, file a becomes file h and so on,
+/// the bitboard equivalent of `square ^ 7`
+#[inline(always)]
+pub const fn mirror_horizontal(bb: Bitboard) -> Bitboard {
+    bb.reverse_bits().swap_bytes()
+}
+
 /// returns a mask where all the bits of the file that `square` resides on
 /// are set to 1
 #[inline(always)]
@@ -295,6 +419,66 @@ pub fn attackers_of(
             & (pos.piece_bb(piece::BISHOP | color) | pos.piece_bb(piece::QUEEN | color)))
 }
 
+/// returns a bitboard of all pieces of both colors that attack a given square,
+/// treating only the squares in `occupied` as occupied
+///
+/// pieces that aren't in `occupied` are still counted as attackers, so callers removing pieces
+/// from `occupied` (e.g. for x-rays) should mask the result with it
+#[inline(always)]
+pub fn all_attackers_of(
+    square: pos::Square,
+    pos: &pos::Position,
+    occupied: Bitboard,
+    masks: &AttackMasks,
+) -> Bitboard {
+    let rooks_queens = pos.piece_bb(piece::WHITE_ROOK)
+        | pos.piece_bb(piece::BLACK_ROOK)
+        | pos.piece_bb(piece::WHITE_QUEEN)
+        | pos.piece_bb(piece::BLACK_QUEEN);
+    let bishops_queens = pos.piece_bb(piece::WHITE_BISHOP)
+        | pos.piece_bb(piece::BLACK_BISHOP)
+        | pos.piece_bb(piece::WHITE_QUEEN)
+        | pos.piece_bb(piece::BLACK_QUEEN);
+
+    (masks.pawn_attacks(color::BLACK, square) & pos.piece_bb(piece::WHITE_PAWN))
+        | (masks.pawn_attacks(color::WHITE, square) & pos.piece_bb(piece::BLACK_PAWN))
+        | (masks.knight_attacks(square)
+            & (pos.piece_bb(piece::WHITE_KNIGHT) | pos.piece_bb(piece::BLACK_KNIGHT)))
+        | (masks.king_attacks(square)
+            & (pos.piece_bb(piece::WHITE_KING) | pos.piece_bb(piece::BLACK_KING)))
+        | (masks.rook_attacks_rt(square, occupied) & rooks_queens)
+        | (masks.bishop_attacks_rt(square, occupied) & bishops_queens)
+}
+
+/// returns the squares a rook on `square` attacks through the first pieces in `blockers` it runs into,
+/// i.e. the attacks that appear once those pieces are removed, not including its direct attacks
+///
+/// `blockers` is usually a subset of `occupied`, e.g. the pieces of one side to find pins,
+/// or all pieces to find skewers
+#[inline(always)]
+pub fn xray_rook_attacks(
+    square: pos::Square,
+    occupied: Bitboard,
+    blockers: Bitboard,
+    masks: &AttackMasks,
+) -> Bitboard {
+    let attacks = masks.rook_attacks_rt(square, occupied);
+    attacks ^ masks.rook_attacks_rt(square, occupied ^ (blockers & attacks))
+}
+
+/// returns the squares a bishop on `square` attacks through the first pieces in `blockers` it runs into,
+/// see `bb::xray_rook_attacks`
+#[inline(always)]
+pub fn xray_bishop_attacks(
+    square: pos::Square,
+    occupied: Bitboard,
+    blockers: Bitboard,
+    masks: &AttackMasks,
+) -> Bitboard {
+    let attacks = masks.bishop_attacks_rt(square, occupied);
+    attacks ^ masks.bishop_attacks_rt(square, occupied ^ (blockers & attacks))
+}
+
 /// returns true if a square is attacked by any piece of `color`,
 /// faster alternative to `bb::attackers_of` if you don't need to know where the attackers are
 #[inline(always)]
@@ -382,3 +566,32 @@ pub(crate) fn init_attack_masks_sliding_piece_rays(masks: &mut AttackMasks) {
         masks.bishop_rays[sq] = (diag_mask(sq) | anti_diag_mask(sq)).pop_bit(sq);
     }
 }
+
+/// initializes the `between` and `line` lookup tables, the sliding piece rays must be initialized first
+pub(crate) fn init_line_masks(masks: &mut AttackMasks) {
+    for a in 0..64 {
+        for b in 0..64 {
+            if a == b {
+                continue;
+            }
+
+            let (line, between) = if masks.rook_rays[a] & square_bb(b) != EMPTY {
+                (
+                    masks.rook_rays[a] & masks.rook_rays[b],
+                    masks.rook_attacks_rt(a, square_bb(b)) & masks.rook_attacks_rt(b, square_bb(a)),
+                )
+            } else if masks.bishop_rays[a] & square_bb(b) != EMPTY {
+                (
+                    masks.bishop_rays[a] & masks.bishop_rays[b],
+                    masks.bishop_attacks_rt(a, square_bb(b))
+                        & masks.bishop_attacks_rt(b, square_bb(a)),
+                )
+            } else {
+                continue;
+            };
+
+            masks.line[a][b] = line | square_bb(a) | square_bb(b);
+            masks.between[a][b] = between;
+        }
+    }
+}
@@ -0,0 +1,287 @@
+// file for computing attacks and population counts of many bitboards at once, e.g. mobility of all pieces of a side
+//
+// with the `simd` feature (nightly only), bitboards are processed 4 at a time with `std::simd`,
+// otherwise the same code runs on one bitboard at a time
+
+use std::ops::{BitAnd, BitOr, Not};
+
+#[cfg(feature = "simd")]
+use std::simd::{num::SimdUint, u64x4};
+
+use crate::piece::bb::{self, Bitboard};
+
+/// number of bitboards processed at once with the `simd` feature
+#[cfg(feature = "simd")]
+const LANES: usize = 4;
+
+/// operations shared by a single bitboard and a vector of bitboards
+trait Lanes: Copy + BitAnd<Output = Self> + BitOr<Output = Self> + Not<Output = Self> {
+    fn splat(bb: Bitboard) -> Self;
+    fn shl(self, n: u64) -> Self;
+    fn shr(self, n: u64) -> Self;
+}
+
+impl Lanes for Bitboard {
+    #[inline(always)]
+    fn splat(bb: Bitboard) -> Self {
+        bb
+    }
+
+    #[inline(always)]
+    fn shl(self, n: u64) -> Self {
+        self << n
+    }
+
+    #[inline(always)]
+    fn shr(self, n: u64) -> Self {
+        self >> n
+    }
+}
+
+#[cfg(feature = "simd")]
+impl Lanes for u64x4 {
+    #[inline(always)]
+    fn splat(bb: Bitboard) -> Self {
+        u64x4::splat(bb)
+    }
+
+    #[inline(always)]
+    fn shl(self, n: u64) -> Self {
+        self << u64x4::splat(n)
+    }
+
+    #[inline(always)]
+    fn shr(self, n: u64) -> Self {
+        self >> u64x4::splat(n)
+    }
+}
+
+/// a direction on the board, the shift and the squares a bit can't land on after wrapping around the board
+#[derive(Clone, Copy)]
+struct Dir {
+    shift: u64,
+    north: bool,
+    wrap: Bitboard,
+}
+
+const N: Dir = Dir::new(8, true, bb::EMPTY);
+const S: Dir = Dir::new(8, false, bb::EMPTY);
+const E: Dir = Dir::new(1, true, bb::FILE_A_MASK);
+const W: Dir = Dir::new(1, false, bb::FILE_H_MASK);
+const NE: Dir = Dir::new(9, true, bb::FILE_A_MASK);
+const NW: Dir = Dir::new(7, true, bb::FILE_H_MASK);
+const SE: Dir = Dir::new(7, false, bb::FILE_A_MASK);
+const SW: Dir = Dir::new(9, false, bb::FILE_H_MASK);
+
+impl Dir {
+    const fn new(shift: u64, north: bool, wrap: Bitboard) -> Self {
+        Dir { shift, north, wrap }
+    }
+
+    #[inline(always)]
+    fn shift<T: Lanes>(self, bbs: T, times: u64) -> T {
+        if self.north {
+            bbs.shl(self.shift * times)
+        } else {
+            bbs.shr(self.shift * times)
+        }
+    }
+
+    /// all squares attacked in this direction by the sliders in `sliders`, stopping at the first piece
+    /// (kogge-stone occluded fill)
+    #[inline(always)]
+    fn slide<T: Lanes>(self, mut sliders: T, occupied: T) -> T {
+        let not_wrap = T::splat(!self.wrap);
+        let mut empty = !occupied & not_wrap;
+
+        sliders = sliders | (empty & self.shift(sliders, 1));
+        empty = empty & self.shift(empty, 1);
+        sliders = sliders | (empty & self.shift(sliders, 2));
+        empty = empty & self.shift(empty, 2);
+        sliders = sliders | (empty & self.shift(sliders, 4));
+
+        self.shift(sliders, 1) & not_wrap
+    }
+}
+
+/// an attack computation that can run on a single bitboard or a vector of them
+trait Kernel {
+    fn apply<T: Lanes>(&self, pieces: T, occupied: T) -> T;
+}
+
+struct Knight;
+struct King;
+struct Rook;
+struct Bishop;
+struct Queen;
+
+impl Kernel for Knight {
+    #[inline(always)]
+    fn apply<T: Lanes>(&self, knights: T, _: T) -> T {
+        let l1 = knights.shr(1) & T::splat(!bb::FILE_H_MASK);
+        let l2 = knights.shr(2) & T::splat(!bb::FILE_GH_MASK);
+        let r1 = knights.shl(1) & T::splat(!bb::FILE_A_MASK);
+        let r2 = knights.shl(2) & T::splat(!bb::FILE_AB_MASK);
+        let h1 = l1 | r1;
+        let h2 = l2 | r2;
+
+        h1.shl(16) | h1.shr(16) | h2.shl(8) | h2.shr(8)
+    }
+}
+
+impl Kernel for King {
+    #[inline(always)]
+    fn apply<T: Lanes>(&self, kings: T, _: T) -> T {
+        let sides =
+            (E.shift(kings, 1) & T::splat(!E.wrap)) | (W.shift(kings, 1) & T::splat(!W.wrap));
+        let row = kings | sides;
+
+        sides | N.shift(row, 1) | S.shift(row, 1)
+    }
+}
+
+impl Kernel for Rook {
+    #[inline(always)]
+    fn apply<T: Lanes>(&self, rooks: T, occupied: T) -> T {
+        N.slide(rooks, occupied)
+            | S.slide(rooks, occupied)
+            | E.slide(rooks, occupied)
+            | W.slide(rooks, occupied)
+    }
+}
+
+impl Kernel for Bishop {
+    #[inline(always)]
+    fn apply<T: Lanes>(&self, bishops: T, occupied: T) -> T {
+        NE.slide(bishops, occupied)
+            | NW.slide(bishops, occupied)
+            | SE.slide(bishops, occupied)
+            | SW.slide(bishops, occupied)
+    }
+}
+
+impl Kernel for Queen {
+    #[inline(always)]
+    fn apply<T: Lanes>(&self, queens: T, occupied: T) -> T {
+        Rook.apply(queens, occupied) | Bishop.apply(queens, occupied)
+    }
+}
+
+/// runs `kernel` on every bitboard of `pieces`, writing the results to `attacks`
+#[inline(always)]
+fn map<K: Kernel>(kernel: K, pieces: &[Bitboard], occupied: Bitboard, attacks: &mut [Bitboard]) {
+    assert_eq!(
+        pieces.len(),
+        attacks.len(),
+        "pieces and attacks have different lengths"
+    );
+
+    #[cfg(feature = "simd")]
+    let done = {
+        let occupied = u64x4::splat(occupied);
+        let mut chunks = pieces.chunks_exact(LANES);
+
+        for (chunk, out) in (&mut chunks).zip(attacks.chunks_exact_mut(LANES)) {
+            kernel
+                .apply(u64x4::from_slice(chunk), occupied)
+                .copy_to_slice(out);
+        }
+
+        pieces.len() - chunks.remainder().len()
+    };
+
+    #[cfg(not(feature = "simd"))]
+    let done = 0;
+
+    for (&piece, out) in pieces[done..].iter().zip(&mut attacks[done..]) {
+        *out = kernel.apply(piece, occupied);
+    }
+}
+
+/// computes the knight attacks of every bitboard in `pieces`, a bitboard with more than one knight
+/// gets the attacks of all of them
+///
+/// panics if `pieces` and `attacks` have different lengths
+pub fn knight_attacks(pieces: &[Bitboard], attacks: &mut [Bitboard]) {
+    map(Knight, pieces, bb::EMPTY, attacks);
+}
+
+/// computes the king attacks of every bitboard in `pieces`, see `batch::knight_attacks()`
+pub fn king_attacks(pieces: &[Bitboard], attacks: &mut [Bitboard]) {
+    map(King, pieces, bb::EMPTY, attacks);
+}
+
+/// computes the rook attacks of every bitboard in `pieces` on a board with `occupied` squares,
+/// including attacked pieces of both colors, see `batch::knight_attacks()`
+pub fn rook_attacks(pieces: &[Bitboard], occupied: Bitboard, attacks: &mut [Bitboard]) {
+    map(Rook, pieces, occupied, attacks);
+}
+
+/// computes the bishop attacks of every bitboard in `pieces`, see `batch::rook_attacks()`
+pub fn bishop_attacks(pieces: &[Bitboard], occupied: Bitboard, attacks: &mut [Bitboard]) {
+    map(Bishop, pieces, occupied, attacks);
+}
+
+/// computes the queen attacks of every bitboard in `pieces`, see `batch::rook_attacks()`
+pub fn queen_attacks(pieces: &[Bitboard], occupied: Bitboard, attacks: &mut [Bitboard]) {
+    map(Queen, pieces, occupied, attacks);
+}
+
+/// counts the bits of every bitboard in `bbs` that are also in `mask`,
+/// e.g. the mobility of every piece with its attacks and a mask of the squares not occupied by its own side
+///
+/// panics if `bbs` and `counts` have different lengths
+pub fn popcounts(bbs: &[Bitboard], mask: Bitboard, counts: &mut [u32]) {
+    assert_eq!(
+        bbs.len(),
+        counts.len(),
+        "bbs and counts have different lengths"
+    );
+
+    #[cfg(feature = "simd")]
+    let done = {
+        let mask = u64x4::splat(mask);
+        let mut chunks = bbs.chunks_exact(LANES);
+
+        for (chunk, out) in (&mut chunks).zip(counts.chunks_exact_mut(LANES)) {
+            let ones = (u64x4::from_slice(chunk) & mask).count_ones();
+            out.copy_from_slice(&ones.cast::<u32>().to_array());
+        }
+
+        bbs.len() - chunks.remainder().len()
+    };
+
+    #[cfg(not(feature = "simd"))]
+    let done = 0;
+
+    for (&bb, out) in bbs[done..].iter().zip(&mut counts[done..]) {
+        *out = (bb & mask).count_ones();
+    }
+}
+
+/// the total number of bits of all bitboards in `bbs` that are also in `mask`, see `batch::popcounts()`
+pub fn popcount_sum(bbs: &[Bitboard], mask: Bitboard) -> u32 {
+    #[cfg(feature = "simd")]
+    let (sum, done) = {
+        let mask = u64x4::splat(mask);
+        let mut sum = u64x4::splat(0);
+        let mut chunks = bbs.chunks_exact(LANES);
+
+        for chunk in &mut chunks {
+            sum += (u64x4::from_slice(chunk) & mask).count_ones();
+        }
+
+        (
+            sum.reduce_sum() as u32,
+            bbs.len() - chunks.remainder().len(),
+        )
+    };
+
+    #[cfg(not(feature = "simd"))]
+    let (sum, done) = (0, 0);
+
+    sum + bbs[done..]
+        .iter()
+        .map(|&bb| (bb & mask).count_ones())
+        .sum::<u32>()
+}
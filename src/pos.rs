@@ -1,3 +1,6 @@
+use std::fmt;
+
+#[cfg(feature = "render")]
 use colored::Colorize;
 
 use crate::{
@@ -7,7 +10,7 @@ use crate::{
         self,
         bb::{self, BitboardUtil},
     },
-    zobrist,
+    termination, zobrist,
 };
 
 pub type Rank = isize;
@@ -19,6 +22,10 @@ pub type Square = usize;
 /// if the whole integer is 0, then neither side has any castling rights
 pub type CastleRights = u8;
 
+/// the number of pieces of every type and color, packed into 4 bits each,
+/// in the order of `bb::p_to_idx()`, see `Position::material_key()`
+pub type MaterialKey = u64;
+
 pub const NO_CASTLING: CastleRights = 0x0;
 pub const WK_CASTLE: CastleRights = 0x2; // white, king side
 pub const WQ_CASTLE: CastleRights = 0x4; // white, queen side
@@ -43,16 +50,16 @@ pub const RANK_6: Rank = 5;
 pub const RANK_7: Rank = 6;
 pub const RANK_8: Rank = 7;
 
-/// starting square of the white king's rook
+/// starting square of the white king's rook in standard chess
 pub const WK_ROOK_SQ: Square = 7;
 
-/// starting square of the white queen's rook
+/// starting square of the white queen's rook in standard chess
 pub const WQ_ROOK_SQ: Square = 0;
 
-/// starting square of the black king's rook
+/// starting square of the black king's rook in standard chess
 pub const BK_ROOK_SQ: Square = 63;
 
-/// starting square of the black queen's rook
+/// starting square of the black queen's rook in standard chess
 pub const BQ_ROOK_SQ: Square = 56;
 
 /// Fen string for the starting position
@@ -61,6 +68,13 @@ pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq
 /// number of plies that `Position::rule50` has to be equal to for a position to be a draw
 pub const RULE_50_PLIES: u8 = 100;
 
+/// number of plies that `Position::rule50` has to be equal to for a position to be drawn
+/// automatically, without a claim
+pub const RULE_75_PLIES: u8 = 150;
+
+/// size of a position encoded with `Position::to_bytes()`
+pub const BYTES_SIZE: usize = 32;
+
 pub const QUEEN_VALUE: u32 = 9;
 pub const ROOK_VALUE: u32 = 5;
 pub const BISHOP_VALUE: u32 = 3;
@@ -86,21 +100,392 @@ pub struct StateInfo {
     pub ep_square: Option<Square>,
     pub rule50: u8,
     pub castling: CastleRights,
+    /// starting squares of the rooks each castling right belongs to, in the order
+    /// white king side, white queen side, black king side, black queen side,
+    /// only meaningful while the corresponding right is still present
+    pub castling_rooks: [Square; 4],
     pub move_played: Option<moves::Move>,
     pub board: [piece::Piece; 64],
     pub piece_bb: [bb::Bitboard; 12],
     pub color_bb: [bb::Bitboard; 2],
     pub side: color::Color,
     pub ply: usize,
+    /// the fullmove number as it appears in FEN strings, starts at 1 and increases after black moves
+    pub fullmove: u16,
+    pub key: zobrist::Key,
+    /// zobrist key of only the pawns on the board, see `Position::pawn_key()`
+    pub pawn_key: zobrist::Key,
+    pub material_key: MaterialKey,
+}
+
+/// the part of a `StateInfo` that can't be recomputed when unmaking a move,
+/// stored in the history of a position instead of full states
+///
+/// every entry holds the values of the state *before* a move was made
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Undo {
+    pub ep_square: Option<Square>,
+    pub rule50: u8,
+    pub castling: CastleRights,
+    /// the move that led to the state, not the one made from it
+    pub move_played: Option<moves::Move>,
+    pub key: zobrist::Key,
+    pub pawn_key: zobrist::Key,
+}
+
+/// an immutable copy of everything needed to display a position,
+/// cheap to create and to send to other threads since it doesn't contain the position's history
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PositionSnapshot {
+    pub board: [piece::Piece; 64],
+    pub side: color::Color,
+    pub castling: CastleRights,
+    pub ep_square: Option<Square>,
+    pub last_move: Option<moves::Move>,
+    pub rule50: u8,
+    pub ply: usize,
+    pub fullmove: u16,
     pub key: zobrist::Key,
+    /// how the game ended, if it's over
+    pub outcome: Option<Outcome>,
+}
+
+/// how a game ended by the rules, see `Position::outcome()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Outcome {
+    /// `winner` checkmated the side to move
+    Checkmate { winner: color::Color },
+    /// the side to move has no legal moves and isn't in check
+    Stalemate,
+    /// 50 moves were played by each side without a capture or pawn move
+    FiftyMoveRule,
+    /// the position occurred for the third time
+    Repetition,
+    /// neither side has enough material to checkmate, see `Position::insufficient_material()`
+    InsufficientMaterial,
+}
+
+impl Outcome {
+    /// the side that won, or `None` if the game is drawn
+    #[inline(always)]
+    pub fn winner(&self) -> Option<color::Color> {
+        match self {
+            Outcome::Checkmate { winner } => Some(*winner),
+            _ => None,
+        }
+    }
+
+    /// returns true if the game is drawn
+    #[inline(always)]
+    pub fn is_draw(&self) -> bool {
+        self.winner().is_none()
+    }
+
+    /// the matching reason for the end of the game
+    pub fn termination(&self) -> termination::Termination {
+        match self {
+            Outcome::Checkmate { .. } => termination::Termination::Checkmate,
+            Outcome::Stalemate => termination::Termination::Stalemate,
+            Outcome::FiftyMoveRule => termination::Termination::FiftyMoveRule,
+            Outcome::Repetition => termination::Termination::Repetition,
+            Outcome::InsufficientMaterial => termination::Termination::InsufficientMaterial,
+        }
+    }
+}
+
+/// how much of the history is carried over into a position created with `Position::from_ply_with()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum BranchHistory {
+    /// no history, the new position can't detect repetitions of positions before it
+    Clear,
+    /// the moves since the last capture or pawn move, enough for repetition detection
+    #[default]
+    Repetitions,
+    /// every move up to the ply, so the new position can be unmade all the way back
+    Full,
+}
+
+/// why a position is drawn, see `Position::is_draw()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum DrawReason {
+    Stalemate,
+    FiftyMoveRule,
+    Repetition,
+    InsufficientMaterial,
+}
+
+/// the squares involved in castling with a single castling right, see `Position::castling_info()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct CastlingInfo {
+    pub right: CastleRights,
+    pub king_from: Square,
+    /// always on the g file (king side) or c file (queen side)
+    pub king_to: Square,
+    /// the castling rook's starting square, which can be on any file in chess960
+    pub rook_from: Square,
+    /// always on the f file (king side) or d file (queen side)
+    pub rook_to: Square,
+    /// squares the king passes through, including its destination but not its starting square,
+    /// none of them may be attacked for castling to be legal
+    pub king_path: bb::Bitboard,
+    /// squares the rook passes through, including its destination but not its starting square
+    pub rook_path: bb::Bitboard,
+}
+
+impl CastlingInfo {
+    /// squares that have to be empty for castling, except for the castling king and rook
+    #[inline(always)]
+    pub fn empty_squares(&self) -> bb::Bitboard {
+        (self.king_path | self.rook_path)
+            & !bb::square_bb(self.king_from)
+            & !bb::square_bb(self.rook_from)
+    }
+}
+
+/// error returned by `Position::validate()`, describing the first broken invariant it found
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum InvalidPosition {
+    /// a side doesn't have exactly one king, contains the side and its number of kings
+    KingCount(color::Color, u32),
+    /// a pawn stands on the first or eighth rank
+    PawnOnBackRank(Square),
+    /// the en passant square isn't right behind a pawn that could have just moved two squares
+    EnPassantSquare(Square),
+    /// a castling right is set, but the king or the castling rook isn't where it needs to be
+    CastlingRights(CastleRights),
+    /// the side that isn't to move is in check
+    OpponentInCheck,
+    /// the bitboards don't match the board array
+    BoardMismatch,
+}
+
+impl fmt::Display for InvalidPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidPosition::KingCount(side, count) => write!(
+                f,
+                "{} has {count} kings",
+                if *side == color::WHITE {
+                    "white"
+                } else {
+                    "black"
+                }
+            ),
+            InvalidPosition::PawnOnBackRank(square) => {
+                write!(f, "pawn on {}", to_algn(*square))
+            }
+            InvalidPosition::EnPassantSquare(square) => {
+                write!(f, "invalid en passant square {}", to_algn(*square))
+            }
+            InvalidPosition::CastlingRights(right) => {
+                write!(
+                    f,
+                    "castling right {right:#x} without a king and rook to castle with"
+                )
+            }
+            InvalidPosition::OpponentInCheck => write!(f, "the side not to move is in check"),
+            InvalidPosition::BoardMismatch => write!(f, "bitboards don't match the board"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidPosition {}
+
+impl InvalidPosition {
+    /// the change that fixes this problem, if it can be fixed without guessing where pieces belong
+    pub fn repair(&self) -> Option<Repair> {
+        match *self {
+            InvalidPosition::EnPassantSquare(_) => Some(Repair::ClearEnPassant),
+            InvalidPosition::CastlingRights(right) => Some(Repair::DropCastlingRight(right)),
+            InvalidPosition::OpponentInCheck => Some(Repair::SwitchSideToMove),
+            _ => None,
+        }
+    }
+}
+
+/// error returned by `Position::try_make_move()`, describing why the move was rejected
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum IllegalMove {
+    /// there is no piece of the side to move on the move's starting square
+    NoPiece(Square),
+    /// a legal move between the same squares exists, but with this move type instead,
+    /// e.g. a move to an occupied square not marked as a capture
+    WrongMoveType(moves::MoveType),
+    /// the piece can't move to the target square
+    Unreachable,
+    /// the move would leave the king in check
+    LeavesKingInCheck,
+}
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IllegalMove::NoPiece(square) => {
+                write!(f, "no piece of the side to move on {}", to_algn(*square))
+            }
+            IllegalMove::WrongMoveType(expected) => {
+                write!(f, "wrong move type, expected {expected:?}")
+            }
+            IllegalMove::Unreachable => write!(f, "the piece can't move there"),
+            IllegalMove::LeavesKingInCheck => write!(f, "the move leaves the king in check"),
+        }
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
+/// a problem found in a FEN string by `pos::validate_fen()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct FenIssue {
+    /// the byte offset in the FEN string where the problem is
+    pub at: usize,
+    pub kind: FenIssueKind,
+}
+
+/// the kinds of problems `pos::validate_fen()` finds
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum FenIssueKind {
+    /// the string ends before this field
+    MissingField(&'static str),
+    /// there's more than six fields
+    TooManyFields,
+    /// the board doesn't have 8 ranks, contains the number of ranks
+    RankCount(usize),
+    /// a rank doesn't have 8 squares, contains the rank and its number of squares
+    SquareCount(Rank, u32),
+    /// a character that doesn't belong in this field
+    InvalidChar(char),
+    /// a side doesn't have exactly one king, contains the side and its number of kings
+    KingCount(color::Color, u32),
+    /// a pawn stands on the first or eighth rank
+    PawnOnBackRank,
+    /// the same castling right is given twice, or a side has more than two castling rights
+    DuplicateCastlingRight(char),
+    /// a castling right is set, but the side has no king on its back rank
+    CastlingWithoutKing(char),
+    /// a castling right is set, but the side has no rook to castle with
+    CastlingWithoutRook(char),
+    /// the en passant field isn't a square
+    InvalidEnPassant,
+    /// the en passant square isn't right behind a pawn that could have just moved two squares
+    ImpossibleEnPassant,
+    /// a clock isn't a number in range, the halfmove clock has to fit in a `u8`,
+    /// and the fullmove number can't be 0
+    InvalidClock,
+}
+
+impl fmt::Display for FenIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}: ", self.at)?;
+
+        match self.kind {
+            FenIssueKind::MissingField(field) => write!(f, "missing {field}"),
+            FenIssueKind::TooManyFields => write!(f, "too many fields"),
+            FenIssueKind::RankCount(count) => write!(f, "{count} ranks instead of 8"),
+            FenIssueKind::SquareCount(rank, count) => {
+                write!(f, "rank {} has {count} squares instead of 8", rank + 1)
+            }
+            FenIssueKind::InvalidChar(ch) => write!(f, "invalid character {ch:?}"),
+            FenIssueKind::KingCount(side, count) => write!(
+                f,
+                "{} has {count} kings",
+                if side == color::WHITE {
+                    "white"
+                } else {
+                    "black"
+                }
+            ),
+            FenIssueKind::PawnOnBackRank => write!(f, "pawn on the first or eighth rank"),
+            FenIssueKind::DuplicateCastlingRight(ch) => {
+                write!(
+                    f,
+                    "castling right {ch:?} given twice or too many castling rights"
+                )
+            }
+            FenIssueKind::CastlingWithoutKing(ch) => {
+                write!(f, "castling right {ch:?} without a king on the back rank")
+            }
+            FenIssueKind::CastlingWithoutRook(ch) => {
+                write!(f, "castling right {ch:?} without a rook to castle with")
+            }
+            FenIssueKind::InvalidEnPassant => write!(f, "en passant field isn't a square"),
+            FenIssueKind::ImpossibleEnPassant => {
+                write!(f, "no pawn could have just skipped the en passant square")
+            }
+            FenIssueKind::InvalidClock => write!(f, "invalid clock"),
+        }
+    }
+}
+
+impl std::error::Error for FenIssue {}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            self.write_diagram(f)
+        } else {
+            write!(f, "{}", self.to_fen())
+        }
+    }
+}
+
+impl fmt::Debug for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_diagram(f)?;
+        write!(f, "\n\nfen: {}\nkey: {:#018x}", self.to_fen(), self.key())
+    }
+}
+
+/// error returned by `pos::parse_square()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ParseSquareError {
+    /// the string isn't 2 characters long
+    Length,
+    /// the first character isn't a file from `a` to `h`
+    File(char),
+    /// the second character isn't a rank from `1` to `8`
+    Rank(char),
+}
+
+impl fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSquareError::Length => write!(f, "a square has to be 2 characters"),
+            ParseSquareError::File(ch) => write!(f, "invalid file {ch:?}"),
+            ParseSquareError::Rank(ch) => write!(f, "invalid rank {ch:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+/// a change to a position that fixes a problem found by `Position::validate()`,
+/// see `Position::suggest_repairs()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Repair {
+    /// removes a castling right the king or rook can't castle with
+    DropCastlingRight(CastleRights),
+    /// removes an en passant square that no pawn could have just skipped
+    ClearEnPassant,
+    /// gives the move to the other side, when the side to move is giving check
+    SwitchSideToMove,
 }
 
 /// wrapper for the `StateInfo` struct,
-/// additionally contains a vector of previous states for move unmaking purposes
-#[derive(Debug, Clone, Eq, PartialEq)]
+/// additionally contains a vector of what's needed to unmake every move played
+///
+/// with the `full-history` feature, full copies of previous states are kept as well,
+/// and unmaking a move restores the previous copy instead of reversing the move
+///
+/// displays as its FEN string, or as a board diagram with the alternate flag (`{:#}`),
+/// the `Debug` output is the board diagram followed by the FEN string and the key
+#[derive(Clone, Eq, PartialEq)]
 pub struct Position {
     st: StateInfo,
-    history: Vec<StateInfo>,
+    history: Vec<Undo>,
+    #[cfg(feature = "full-history")]
+    states: Vec<StateInfo>,
+    chess960: bool,
 }
 
 /// gives both sides all castling rights
@@ -184,56 +569,117 @@ pub fn lose_qcastle_rights(
 
 impl Position {
     pub fn blank() -> Self {
-        Position {
-            st: StateInfo {
-                ep_square: None,
-                rule50: 0,
-                castling: NO_CASTLING,
-                move_played: None,
-                board: [piece::NONE; 64],
-                piece_bb: [bb::EMPTY; 12],
-                color_bb: [bb::EMPTY; 2],
-                side: color::NONE,
-                ply: 0,
-                key: 0,
-            },
-            history: Vec::new(),
-        }
+        Self::from_state(StateInfo {
+            ep_square: None,
+            rule50: 0,
+            castling: NO_CASTLING,
+            castling_rooks: [WK_ROOK_SQ, WQ_ROOK_SQ, BK_ROOK_SQ, BQ_ROOK_SQ],
+            move_played: None,
+            board: [piece::NONE; 64],
+            piece_bb: [bb::EMPTY; 12],
+            color_bb: [bb::EMPTY; 2],
+            side: color::NONE,
+            ply: 0,
+            fullmove: 1,
+            key: 0,
+            pawn_key: 0,
+            material_key: 0,
+        })
     }
 
     /// creates a `Position` object from a `StateInfo` object at an index of the history (including the current state)
     ///
-    /// the returned object has a clear history
+    /// the returned object keeps the history needed to detect repetitions, see `Position::from_ply_with()`
     pub fn from_ply(&self, ply: usize) -> Self {
+        self.from_ply_with(ply, BranchHistory::Repetitions)
+    }
+
+    /// same as `Position::from_ply()`, with control over how much of the history up to `ply`
+    /// is carried over, moves can only be unmade as far back as the history carried over
+    ///
+    /// panics if `ply` is greater than the number of moves played
+    pub fn from_ply_with(&self, ply: usize, history: BranchHistory) -> Self {
+        assert!(
+            ply <= self.history.len(),
+            "ply {ply} hasn't been played yet"
+        );
+
+        let mut pos = self.clone();
+
+        while pos.history.len() > ply {
+            pos.unmake_move();
+        }
+
+        let keep = match history {
+            BranchHistory::Clear => 0,
+            BranchHistory::Repetitions => ply.min(pos.st.rule50 as usize),
+            BranchHistory::Full => ply,
+        };
+
+        pos.history.drain(..ply - keep);
+        #[cfg(feature = "full-history")]
+        pos.states.drain(..ply - keep);
+
+        pos
+    }
+
+    /// creates a position with a clear history from a state
+    fn from_state(st: StateInfo) -> Self {
         Position {
-            st: self.history_i()[ply],
+            st,
             history: Vec::new(),
+            #[cfg(feature = "full-history")]
+            states: Vec::new(),
+            chess960: false,
         }
     }
 
+    /// whether UCI moves of the position use chess960 castling notation, see `Position::set_chess960()`
+    #[inline(always)]
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    /// sets chess960 mode, where castling is written in UCI format as the king capturing its own rook
+    /// (`"e1h1"` instead of `"e1g1"`), like FRC aware engines and GUIs expect,
+    /// see `Move::to_uci_fmt_for()` and `Move::from_str_move()`
+    ///
+    /// castling itself works the same in both modes, this is off for new positions
+    #[inline(always)]
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
     /// the color of the current side to move
     #[inline(always)]
     pub fn side_to_move(&self) -> color::Color {
         self.st.side
     }
 
-    /// a vector containing past states of the position
+    /// what's needed to unmake every move played, from the first move to the last
     #[inline(always)]
-    pub fn history(&self) -> &Vec<StateInfo> {
+    pub fn history(&self) -> &[Undo] {
         &self.history
     }
 
     /// a vector containing past states of the position including the current
     ///
-    /// (possibly slow as it clones both the history and the current state)
-    #[inline(always)]
+    /// (slow, as the past states are rebuilt by unmaking every move on a copy of the position)
     pub fn history_i(&self) -> Vec<StateInfo> {
-        let mut h = self.history.clone();
-        h.append(&mut vec![self.st]);
+        let mut pos = self.clone();
+        let mut h = vec![pos.st];
+
+        while !pos.history.is_empty() {
+            pos.unmake_move();
+            h.push(pos.st);
+        }
+
+        h.reverse();
         h
     }
 
-    /// the square where en passant is possible, if any
+    /// the square where en passant is possible, if any,
+    /// after a double pawn push it's only set if an enemy pawn stands next to the pawn
     #[inline(always)]
     pub fn ep_square(&self) -> Option<Square> {
         self.st.ep_square
@@ -246,18 +692,100 @@ impl Position {
         self.st.rule50
     }
 
+    /// the fullmove number as it appears in FEN strings and PGN move numbers,
+    /// starts at 1 and increases after every move by black
+    #[inline(always)]
+    pub fn fullmove_number(&self) -> u16 {
+        self.st.fullmove
+    }
+
     /// an integer containing the castle rights for both players
     #[inline(always)]
     pub fn castle_rights(&self) -> CastleRights {
         self.st.castling
     }
 
+    /// the starting square of the rook that castling with `right` (e.g. `WK_CASTLE`) moves,
+    /// which is only on the a or h file in standard chess
+    #[inline(always)]
+    pub fn castling_rook(&self, right: CastleRights) -> Square {
+        self.st.castling_rooks[right.trailing_zeros() as usize - 1]
+    }
+
+    /// the start and destination squares of the rook when `side` castles,
+    /// the king always ends up on the g file (king side) or c file (queen side),
+    /// and the rook right next to it, no matter where they started
+    #[inline(always)]
+    pub fn castling_rook_move(&self, side: color::Color, king_side: bool) -> (Square, Square) {
+        let (right, rank) = Self::castling_right_of(side, king_side);
+
+        (
+            self.castling_rook(right),
+            make_sq(if king_side { FILE_F } else { FILE_D }, rank),
+        )
+    }
+
+    /// the king and rook squares and paths of castling with `right` (e.g. `WK_CASTLE`),
+    /// or `None` if the side doesn't have that right
+    ///
+    /// doesn't check whether castling is currently possible, e.g. the paths may be blocked
+    pub fn castling_info(&self, right: CastleRights, masks: &AttackMasks) -> Option<CastlingInfo> {
+        let (side, king_side) = match right {
+            WK_CASTLE => (color::WHITE, true),
+            WQ_CASTLE => (color::WHITE, false),
+            BK_CASTLE => (color::BLACK, true),
+            BQ_CASTLE => (color::BLACK, false),
+            _ => return None,
+        };
+
+        let king = self.piece_bb(piece::KING | side);
+
+        if self.st.castling & right == 0 || king == bb::EMPTY {
+            return None;
+        }
+
+        let king_from = king.trailing_zeros() as Square;
+        let king_to = make_sq(if king_side { FILE_G } else { FILE_C }, rank_of(king_from));
+        let (rook_from, rook_to) = self.castling_rook_move(side, king_side);
+
+        let path = |from: Square, to: Square| match from == to {
+            true => bb::EMPTY,
+            false => masks.between(from, to) | bb::square_bb(to),
+        };
+
+        Some(CastlingInfo {
+            right,
+            king_from,
+            king_to,
+            rook_from,
+            rook_to,
+            king_path: path(king_from, king_to),
+            rook_path: path(rook_from, rook_to),
+        })
+    }
+
     /// the zobrist key for the current position
     #[inline(always)]
     pub fn key(&self) -> u64 {
         self.st.key
     }
 
+    /// a zobrist key that only hashes the pawns of both sides, for pawn structure hash tables,
+    /// updated incrementally like `Position::key()`
+    #[inline(always)]
+    pub fn pawn_key(&self) -> zobrist::Key {
+        self.st.pawn_key
+    }
+
+    /// the number of pieces of every type and color on the board, updated incrementally,
+    /// positions with the same material have the same key no matter where the pieces stand
+    ///
+    /// `e.g. pos.material_key() == pos::material_key_from_str("KRvK").unwrap()`
+    #[inline(always)]
+    pub fn material_key(&self) -> MaterialKey {
+        self.st.material_key
+    }
+
     /// an 8x8 board represented as an array with 64 indices, if an index contains no piece,
     /// its value is 0 `(piece::NONE)`
     #[inline(always)]
@@ -289,6 +817,28 @@ impl Position {
         !self.occupied_bb()
     }
 
+    /// the number of pieces of a given type and color on the board, e.g. `piece::WHITE_KNIGHT`
+    #[inline(always)]
+    pub fn piece_count(&self, piece: piece::Piece) -> u32 {
+        self.piece_bb(piece).count_ones()
+    }
+
+    /// iterates over the squares of all pieces of a given type and color, from a1 to h8
+    #[inline(always)]
+    pub fn pieces(&self, piece: piece::Piece) -> impl Iterator<Item = Square> + use<> {
+        let mut pieces = self.piece_bb(piece);
+
+        std::iter::from_fn(move || (pieces != bb::EMPTY).then(|| pieces.serialize_once()))
+    }
+
+    /// the square of the king of a given color, `None` if the position has no such king
+    #[inline(always)]
+    pub fn king_square(&self, color: color::Color) -> Option<Square> {
+        let king = self.piece_bb(piece::KING | color);
+
+        (king != bb::EMPTY).then(|| king.trailing_zeros() as Square)
+    }
+
     /// get the piece on a given square
     #[inline(always)]
     pub fn piece_on(&self, square: Square) -> piece::Piece {
@@ -313,6 +863,23 @@ impl Position {
         self.st.board[square] != piece::NONE
     }
 
+    /// returns a snapshot of the current state, see `PositionSnapshot`
+    #[inline(always)]
+    pub fn snapshot(&mut self, masks: &AttackMasks, zb: &ZobristValues) -> PositionSnapshot {
+        PositionSnapshot {
+            outcome: self.outcome(masks, zb),
+            fullmove: self.st.fullmove,
+            board: self.st.board,
+            side: self.st.side,
+            castling: self.st.castling,
+            ep_square: self.st.ep_square,
+            last_move: self.st.move_played,
+            rule50: self.st.rule50,
+            ply: self.st.ply,
+            key: self.st.key,
+        }
+    }
+
     /// the last move played in the position, if any
     #[inline(always)]
     pub fn move_played(&self) -> Option<moves::Move> {
@@ -361,6 +928,54 @@ impl Position {
         )
     }
 
+    /// returns a bitboard of all enemy pieces giving check to the king of the side to move
+    #[inline(always)]
+    pub fn checkers(&self, masks: &AttackMasks) -> bb::Bitboard {
+        let king = self.piece_bb(piece::KING | self.st.side);
+
+        if king == bb::EMPTY {
+            return bb::EMPTY;
+        }
+
+        bb::attackers_of(
+            king.trailing_zeros() as Square,
+            self,
+            color::other(self.st.side),
+            masks,
+        )
+    }
+
+    /// returns a bitboard of all pieces of the side to move that are absolutely pinned to their king,
+    /// i.e. pieces that are the only piece standing between the king and an enemy sliding piece
+    pub fn pinned(&self, masks: &AttackMasks) -> bb::Bitboard {
+        let side = self.st.side;
+        let them = color::other(side);
+        let king = self.piece_bb(piece::KING | side);
+
+        if king == bb::EMPTY {
+            return bb::EMPTY;
+        }
+
+        let king_sq = king.trailing_zeros() as Square;
+        let queens = self.piece_bb(piece::QUEEN | them);
+
+        let mut snipers = (masks.rook_rays(king_sq) & (self.piece_bb(piece::ROOK | them) | queens))
+            | (masks.bishop_rays(king_sq) & (self.piece_bb(piece::BISHOP | them) | queens));
+        let mut pinned = bb::EMPTY;
+
+        while snipers != bb::EMPTY {
+            let sniper = snipers.serialize_once();
+
+            let blockers = masks.between(king_sq, sniper) & self.occupied_bb();
+
+            if blockers.count_ones() == 1 && blockers & self.color_bb(side) != bb::EMPTY {
+                pinned |= blockers;
+            }
+        }
+
+        pinned
+    }
+
     /// returns the amount of material a side has using the standard values for pieces
     #[inline(always)]
     pub fn count_material(&self, side: color::Color) -> i32 {
@@ -402,71 +1017,433 @@ impl Position {
             && pawns == 0
             && ((bishops == 0 && knights < 3) || (bishops == 1 && knights == 0))
     }
-}
 
-impl Position {
-    /// takes a FEN string and creates a `Position` object with it
-    ///
-    /// the function assumes that the string is in correct format,
-    /// otherwise, it may give funky results
-    pub fn from_fen(fen_str: &str, zb: &ZobristValues) -> Self {
-        let mut pos = Self::blank();
+    /// the number of squares attacked by the knights, bishops, rooks and queens of `side`
+    /// that aren't occupied by its own pieces
+    pub fn mobility(&self, side: color::Color, masks: &AttackMasks) -> u32 {
+        let mut pieces = self.color_bb(side)
+            & !self.piece_bb(piece::PAWN | side)
+            & !self.piece_bb(piece::KING | side);
+        let mut mobility = 0;
+
+        while pieces != bb::EMPTY {
+            let square = pieces.serialize_once();
+            mobility += (self.piece_attacks(square, masks) & !self.color_bb(side)).count_ones();
+        }
 
-        let mut file = FILE_A;
-        let mut rank = RANK_8;
-        let mut str_idx = 0;
+        mobility
+    }
 
-        for ch in fen_str.chars().take(fen_str.find(' ').unwrap()) {
-            if ch == '/' {
-                file = FILE_A;
-                rank -= 1;
-            } else if ch.is_numeric() {
-                file += ch as isize - b'0' as isize;
-            } else {
-                pos.put_piece(piece::from_char(ch), make_sq(file, rank), zb);
-                file += 1;
-            }
+    /// returns the mobility difference between white (+) and black (-), see `Position::mobility()`
+    #[inline(always)]
+    pub fn mobility_diff(&self, masks: &AttackMasks) -> i32 {
+        self.mobility(color::WHITE, masks) as i32 - self.mobility(color::BLACK, masks) as i32
+    }
 
-            str_idx += 1;
-        }
+    /// returns a bitboard of the knights, bishops, rooks and queens of `side` that are trapped,
+    /// i.e. the opponent can win material by capturing them, and they have no move that doesn't lose material
+    ///
+    /// both are judged with static exchange evaluation, so pins and checks are ignored
+    pub fn trapped_pieces(&self, side: color::Color, masks: &AttackMasks) -> bb::Bitboard {
+        let them = color::other(side);
+        let mut pieces = self.color_bb(side)
+            & !self.piece_bb(piece::PAWN | side)
+            & !self.piece_bb(piece::KING | side);
+        let mut trapped = bb::EMPTY;
+
+        while pieces != bb::EMPTY {
+            let square = pieces.serialize_once();
+            let piece = self.st.board[square];
+
+            let mut attackers = bb::attackers_of(square, self, them, masks);
+            let mut wins_material = false;
+
+            while attackers != bb::EMPTY {
+                let from = attackers.serialize_once();
+
+                if self.see(
+                    moves::Move::new(from, square, MoveType::Capture(piece)),
+                    masks,
+                ) > 0
+                {
+                    wins_material = true;
+                    break;
+                }
+            }
 
-        str_idx += 1;
+            if !wins_material {
+                continue;
+            }
 
-        pos.st.side = if fen_str.chars().nth(str_idx).unwrap() == 'w' {
-            color::WHITE
-        } else {
-            color::BLACK
-        };
+            let mut targets = self.piece_attacks(square, masks) & !self.color_bb(side);
+            let mut can_escape = false;
 
-        str_idx += 2;
+            while targets != bb::EMPTY {
+                let to = targets.serialize_once();
+                let type_of = match self.st.board[to] {
+                    piece::NONE => MoveType::Normal,
+                    cap => MoveType::Capture(cap),
+                };
 
-        for ch in fen_str.chars().skip(str_idx).take_while(|ch| *ch != ' ') {
-            match ch {
-                '-' => {
-                    pos.st.castling = NO_CASTLING;
-                }
-                'K' => {
-                    pos.st.castling |= WK_CASTLE;
-                }
-                'Q' => {
-                    pos.st.castling |= WQ_CASTLE;
-                }
-                'k' => {
-                    pos.st.castling |= BK_CASTLE;
-                }
-                'q' => {
-                    pos.st.castling |= BQ_CASTLE;
+                if self.see(moves::Move::new(square, to, type_of), masks) >= 0 {
+                    can_escape = true;
+                    break;
                 }
-                _ => panic!("I don't think this is a fen string"),
             }
 
-            str_idx += 1;
+            if !can_escape {
+                trapped |= bb::square_bb(square);
+            }
         }
 
-        str_idx += 1;
+        trapped
+    }
 
-        if fen_str.chars().nth(str_idx).unwrap() == '-' {
-            pos.st.ep_square = None;
+    /// squares attacked by the knight, bishop, rook or queen on `square`
+    #[inline(always)]
+    fn piece_attacks(&self, square: Square, masks: &AttackMasks) -> bb::Bitboard {
+        match piece::of(self.st.board[square]) {
+            piece::KNIGHT => masks.knight_attacks(square),
+            piece::BISHOP => masks.bishop_attacks_rt(square, self.occupied_bb()),
+            piece::ROOK => masks.rook_attacks_rt(square, self.occupied_bb()),
+            piece::QUEEN => masks.queen_attacks_rt(square, self.occupied_bb()),
+            _ => bb::EMPTY,
+        }
+    }
+
+    /// static exchange evaluation,
+    /// returns the expected material outcome (in pawns, for the side playing `mov`) of the sequence
+    /// of captures on the target square of `mov`, assuming both sides always recapture with their
+    /// least valuable piece and stop capturing once it would lose material
+    ///
+    /// attackers hidden behind sliding pieces (x-rays) are taken into account,
+    /// pins are not, so a pinned piece may still be used to recapture
+    ///
+    /// non capturing moves evaluate whether the moved piece can be safely placed on its target square
+    ///
+    /// `mov` doesn't have to be a move of the side to move
+    pub fn see(&self, mov: moves::Move, masks: &AttackMasks) -> i32 {
+        let to = mov.to_sq();
+        let mut gain = [0; 32];
+        let mut depth = 0;
+        let mut side = color::of(self.st.board[mov.from_sq()]);
+        let mut attacker = self.st.board[mov.from_sq()];
+        let mut occupied = self.occupied_bb().pop_bit(mov.from_sq());
+
+        match mov.type_of() {
+            MoveType::Capture(cap) => gain[0] = see_value(cap),
+            MoveType::EnPassant => {
+                gain[0] = see_value(piece::PAWN);
+                occupied.pop_bit(behind(to, side));
+            }
+            MoveType::Promotion(promoted) => {
+                gain[0] = see_value(promoted) - see_value(piece::PAWN);
+                attacker = promoted;
+            }
+            MoveType::PromoCapture(promoted, cap) => {
+                gain[0] = see_value(cap) + see_value(promoted) - see_value(piece::PAWN);
+                attacker = promoted;
+            }
+            _ => (),
+        }
+
+        let mut attackers = bb::all_attackers_of(to, self, occupied, masks) & occupied;
+
+        loop {
+            depth += 1;
+            color::switch(&mut side);
+
+            // the value of the piece standing on the target square, minus whatever was gained before
+            gain[depth] = see_value(attacker) - gain[depth - 1];
+
+            if (-gain[depth - 1]).max(gain[depth]) < 0 {
+                break;
+            }
+
+            let own_attackers = attackers & self.color_bb(side);
+
+            if own_attackers == bb::EMPTY {
+                break;
+            }
+
+            let Some(from) = [
+                piece::PAWN,
+                piece::KNIGHT,
+                piece::BISHOP,
+                piece::ROOK,
+                piece::QUEEN,
+                piece::KING,
+            ]
+            .iter()
+            .map(|&p| own_attackers & self.piece_bb(p | side))
+            .find(|&b| b != bb::EMPTY)
+            .map(|b| b.trailing_zeros() as Square) else {
+                break;
+            };
+
+            attacker = self.st.board[from];
+            occupied.pop_bit(from);
+
+            // removing a piece may reveal a sliding piece behind it
+            attackers = bb::all_attackers_of(to, self, occupied, masks) & occupied;
+
+            if depth == gain.len() - 1 {
+                break;
+            }
+        }
+
+        while depth > 1 {
+            depth -= 1;
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        }
+
+        gain[0]
+    }
+
+    /// returns true if `mov` could be played in the current position when ignoring whether
+    /// it leaves the king in check, i.e. if it would be generated by the pseudo legal move generators
+    ///
+    /// the move type has to match the position too, for example a capture has to carry the piece
+    /// that's actually on its target square
+    pub fn is_pseudo_legal(&self, mov: moves::Move, masks: &AttackMasks) -> bool {
+        let side = self.st.side;
+        let (from, to) = (mov.from_sq(), mov.to_sq());
+        let moving = self.st.board[from];
+        let target = self.st.board[to];
+
+        if moving == piece::NONE || color::of(moving) != side {
+            return false;
+        }
+
+        let target_ok = match mov.type_of() {
+            MoveType::Capture(cap) | MoveType::PromoCapture(_, cap) => {
+                cap == target && color::of(cap) == color::other(side)
+            }
+            MoveType::EnPassant => self.st.ep_square == Some(to),
+            // in chess960 the king can castle onto its own square or its rook's,
+            // the rest is checked against the generated castling moves below
+            MoveType::KingSideCastle | MoveType::QueenSideCastle => true,
+            _ => target == piece::NONE,
+        };
+
+        if !target_ok {
+            return false;
+        }
+
+        let to_bb = bb::square_bb(to);
+
+        if moving & piece::PAWN != 0 {
+            let (start_rank, promo_rank) = match side {
+                color::WHITE => (RANK_2, RANK_8),
+                _ => (RANK_7, RANK_1),
+            };
+            let is_promo = rank_of(to) == promo_rank;
+            let attacks = masks.pawn_attacks(side, from);
+            let up1 = ahead(from, side);
+
+            let promo_ok = |promoted: piece::Piece| {
+                color::of(promoted) == side
+                    && matches!(
+                        piece::of(promoted),
+                        piece::KNIGHT | piece::BISHOP | piece::ROOK | piece::QUEEN
+                    )
+            };
+
+            return match mov.type_of() {
+                MoveType::Normal => !is_promo && to == up1,
+                MoveType::PawnTwoUp => {
+                    rank_of(from) == start_rank && !self.is_occupied(up1) && to == ahead(up1, side)
+                }
+                MoveType::Capture(_) => !is_promo && attacks & to_bb != bb::EMPTY,
+                MoveType::Promotion(promoted) => is_promo && to == up1 && promo_ok(promoted),
+                MoveType::PromoCapture(promoted, _) => {
+                    is_promo && attacks & to_bb != bb::EMPTY && promo_ok(promoted)
+                }
+                MoveType::EnPassant => attacks & to_bb != bb::EMPTY,
+                _ => false,
+            };
+        }
+
+        match mov.type_of() {
+            MoveType::Normal | MoveType::Capture(_) => {
+                let attacks = match piece::of(moving) {
+                    piece::KNIGHT => masks.knight_attacks(from),
+                    piece::BISHOP => masks.bishop_attacks_rt(from, self.occupied_bb()),
+                    piece::ROOK => masks.rook_attacks_rt(from, self.occupied_bb()),
+                    piece::QUEEN => masks.queen_attacks_rt(from, self.occupied_bb()),
+                    piece::KING => masks.king_attacks(from),
+                    _ => bb::EMPTY,
+                };
+
+                attacks & to_bb != bb::EMPTY
+            }
+            MoveType::KingSideCastle | MoveType::QueenSideCastle => {
+                let mut castles = moves::MoveList::new();
+                moves::king_moves(self, &mut castles, masks);
+
+                moving & piece::KING != 0
+                    && castles.iter().any(|m| {
+                        m.from_sq() == from && m.to_sq() == to && m.type_of() == mov.type_of()
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    /// returns true if `mov` would put the opponent in check, either directly or by discovering an attack
+    /// from a sliding piece, without making the move
+    ///
+    /// `mov` is assumed to be (pseudo) legal
+    pub fn gives_check(&self, mov: moves::Move, masks: &AttackMasks) -> bool {
+        let side = self.st.side;
+        let their_king = self.piece_bb(piece::KING | color::other(side));
+
+        if their_king == bb::EMPTY {
+            return false;
+        }
+
+        let king_sq = their_king.trailing_zeros() as Square;
+        let (from, to) = (mov.from_sq(), mov.to_sq());
+
+        let mut occupied = (self.occupied_bb() & !bb::square_bb(from)) | bb::square_bb(to);
+        // squares of our pieces that have moved away, so they can't discover attacks
+        let mut moved = bb::square_bb(from);
+        // the piece that ends up giving a direct check, and the square it attacks from
+        let (mut checker, mut checker_sq) = (self.st.board[from], to);
+
+        match mov.type_of() {
+            MoveType::EnPassant => {
+                occupied.pop_bit(behind(to, side));
+            }
+            MoveType::Promotion(promoted) | MoveType::PromoCapture(promoted, _) => {
+                checker = promoted;
+            }
+            MoveType::KingSideCastle | MoveType::QueenSideCastle => {
+                let (rook_from, rook_to) =
+                    self.castling_rook_move(side, mov.type_of() == MoveType::KingSideCastle);
+
+                // in chess960 the king can end up on the rook's square, or the other way around
+                occupied = (self.occupied_bb() & !bb::square_bb(from) & !bb::square_bb(rook_from))
+                    | bb::square_bb(to)
+                    | bb::square_bb(rook_to);
+                moved |= bb::square_bb(rook_from);
+                (checker, checker_sq) = (piece::ROOK | side, rook_to);
+            }
+            _ => (),
+        }
+
+        let direct = match piece::of(checker) {
+            piece::PAWN => masks.pawn_attacks(side, checker_sq),
+            piece::KNIGHT => masks.knight_attacks(checker_sq),
+            piece::BISHOP => masks.bishop_attacks_rt(checker_sq, occupied),
+            piece::ROOK => masks.rook_attacks_rt(checker_sq, occupied),
+            piece::QUEEN => masks.queen_attacks_rt(checker_sq, occupied),
+            _ => bb::EMPTY,
+        };
+
+        if direct & their_king != bb::EMPTY {
+            return true;
+        }
+
+        let queens = self.piece_bb(piece::QUEEN | side);
+        let rooks = (self.piece_bb(piece::ROOK | side) | queens) & !moved;
+        let bishops = (self.piece_bb(piece::BISHOP | side) | queens) & !moved;
+
+        masks.rook_attacks_rt(king_sq, occupied) & rooks != bb::EMPTY
+            || masks.bishop_attacks_rt(king_sq, occupied) & bishops != bb::EMPTY
+    }
+
+    /// returns true if `mov` is legal in the current position,
+    /// without generating the full list of legal moves
+    ///
+    /// useful for moves that come from an untrusted source, such as a transposition table
+    /// or a network connection
+    pub fn is_legal(&mut self, mov: moves::Move, masks: &AttackMasks, zb: &ZobristValues) -> bool {
+        if !self.is_pseudo_legal(mov, masks) {
+            return false;
+        }
+
+        match mov.type_of() {
+            // castling moves are only generated if none of the king's squares are attacked
+            MoveType::KingSideCastle | MoveType::QueenSideCastle => true,
+            _ => {
+                let ep = mov.type_of() == MoveType::EnPassant;
+                let cap = self.fast_make(mov, ep, zb);
+                let is_legal = !self.is_check(masks);
+                self.fast_unmake(mov, cap, ep, zb);
+
+                is_legal
+            }
+        }
+    }
+}
+
+/// piece values used by static exchange evaluation,
+/// the king is worth more than every other piece combined so it's never traded
+#[inline(always)]
+fn see_value(piece: piece::Piece) -> i32 {
+    match piece::of(piece) {
+        piece::PAWN => PAWN_VALUE as i32,
+        piece::KNIGHT => KNIGHT_VALUE as i32,
+        piece::BISHOP => BISHOP_VALUE as i32,
+        piece::ROOK => ROOK_VALUE as i32,
+        piece::QUEEN => QUEEN_VALUE as i32,
+        piece::KING => 1000,
+        _ => 0,
+    }
+}
+
+impl Position {
+    /// takes a FEN string and creates a `Position` object with it
+    ///
+    /// the function assumes that the string is in correct format,
+    /// otherwise, it may give funky results, see `pos::validate_fen()`
+    pub fn from_fen(fen_str: &str, zb: &ZobristValues) -> Self {
+        let mut pos = Self::blank();
+
+        let mut file = FILE_A;
+        let mut rank = RANK_8;
+        let mut str_idx = 0;
+
+        for ch in fen_str.chars().take(fen_str.find(' ').unwrap()) {
+            if ch == '/' {
+                file = FILE_A;
+                rank -= 1;
+            } else if ch.is_numeric() {
+                file += ch as isize - b'0' as isize;
+            } else {
+                pos.put_piece(piece::from_char(ch), make_sq(file, rank), zb);
+                file += 1;
+            }
+
+            str_idx += 1;
+        }
+
+        str_idx += 1;
+
+        pos.st.side = if fen_str.chars().nth(str_idx).unwrap() == 'w' {
+            color::WHITE
+        } else {
+            color::BLACK
+        };
+
+        str_idx += 2;
+
+        for ch in fen_str.chars().skip(str_idx).take_while(|ch| *ch != ' ') {
+            match ch {
+                '-' => {
+                    pos.st.castling = NO_CASTLING;
+                }
+                _ => pos.add_castling_right(ch),
+            }
+
+            str_idx += 1;
+        }
+
+        str_idx += 1;
+
+        if fen_str.chars().nth(str_idx).unwrap() == '-' {
+            pos.st.ep_square = None;
             str_idx += 2;
         } else {
             pos.st.ep_square = Some(string_to_sq(
@@ -482,25 +1459,424 @@ impl Position {
             .collect();
         pos.st.rule50 = tmp.parse().unwrap();
 
+        if let Some(fullmove) = fen_str
+            .split_whitespace()
+            .nth(5)
+            .and_then(|tmp| tmp.parse().ok())
+        {
+            pos.st.fullmove = fullmove;
+        }
+
+        pos.clear_uncapturable_ep();
         pos.st.key = zobrist::hash(&pos, zb);
 
         pos.history.reserve(400); // 400 is compltely arbitrary
 
+        #[cfg(feature = "full-history")]
+        pos.states.reserve(400);
+
+        pos
+    }
+
+    /// creates a position with the pieces in `pieces` on their squares, for setting up positions
+    /// without going through a FEN string, e.g. in endgame generators
+    ///
+    /// castling rights are given to the outermost rook on each side of the king, like `KQkq` in a FEN string,
+    /// rights without a king and rook in place are ignored, the halfmove clock is 0 and the fullmove number 1
+    pub fn from_pieces(
+        pieces: impl IntoIterator<Item = (Square, piece::Piece)>,
+        side: color::Color,
+        castling: CastleRights,
+        ep_square: Option<Square>,
+        zb: &ZobristValues,
+    ) -> Self {
+        let mut pos = Self::blank();
+
+        for (square, piece) in pieces {
+            pos.put_piece(piece, square, zb);
+        }
+
+        pos.st.side = side;
+        pos.st.ep_square = ep_square;
+
+        for (right, ch) in [
+            (WK_CASTLE, 'K'),
+            (WQ_CASTLE, 'Q'),
+            (BK_CASTLE, 'k'),
+            (BQ_CASTLE, 'q'),
+        ] {
+            if castling & right != 0 {
+                pos.add_castling_right(ch);
+            }
+        }
+
+        pos.clear_uncapturable_ep();
+        pos.st.key = zobrist::hash(&pos, zb);
+
+        pos
+    }
+
+    /// encodes the position in `BYTES_SIZE` bytes, much smaller and faster to decode than a FEN string,
+    /// e.g. for storing large amounts of positions
+    ///
+    /// the first 8 bytes are the occupied squares, followed by 16 bytes of 4 bit piece codes
+    /// (see `piece::to_code()`) for every occupied square from a1 to h8, then 4 bytes with the side to move,
+    /// castling rights, en passant file and the files of the castling rooks, the halfmove clock,
+    /// the fullmove number in 2 bytes, and a byte that's always 0,
+    /// all numbers are little endian
+    ///
+    /// the history isn't encoded, panics if there are more than 32 pieces on the board
+    pub fn to_bytes(&self) -> [u8; BYTES_SIZE] {
+        let mut bytes = [0; BYTES_SIZE];
+        let occupied = self.occupied_bb();
+
+        assert!(
+            occupied.count_ones() <= 32,
+            "can't encode more than 32 pieces"
+        );
+
+        bytes[..8].copy_from_slice(&occupied.to_le_bytes());
+
+        let mut squares = occupied;
+        let mut i = 0;
+
+        while squares != bb::EMPTY {
+            bytes[8 + i / 2] |=
+                piece::to_code(self.piece_on(squares.serialize_once())) << (4 * (i % 2));
+            i += 1;
+        }
+
+        let mut state = (self.st.side == color::BLACK) as u32
+            | (self.st.castling & 0x1e) as u32
+            | self.st.ep_square.map_or(0, |sq| file_of(sq) as u32 + 1) << 5;
+
+        for (i, &rook) in self.st.castling_rooks.iter().enumerate() {
+            state |= (file_of(rook) as u32) << (9 + 3 * i);
+        }
+
+        bytes[24..28].copy_from_slice(&state.to_le_bytes());
+        bytes[28] = self.st.rule50;
+        bytes[29..31].copy_from_slice(&self.st.fullmove.to_le_bytes());
+
+        bytes
+    }
+
+    /// decodes a position encoded with `Position::to_bytes()`, `None` if `bytes` isn't a valid encoding
+    pub fn from_bytes(bytes: &[u8; BYTES_SIZE], zb: &ZobristValues) -> Option<Self> {
+        let mut pos = Self::blank();
+        let mut squares = bb::Bitboard::from_le_bytes(bytes[..8].try_into().unwrap());
+
+        if squares.count_ones() > 32 {
+            return None;
+        }
+
+        let mut i = 0;
+
+        while squares != bb::EMPTY {
+            match piece::from_code((bytes[8 + i / 2] >> (4 * (i % 2))) & 0xf)? {
+                piece::NONE => return None,
+                piece => pos.set_piece(piece, squares.serialize_once()),
+            }
+
+            i += 1;
+        }
+
+        let state = u32::from_le_bytes(bytes[24..28].try_into().unwrap());
+
+        pos.st.side = match state & 1 {
+            0 => color::WHITE,
+            _ => color::BLACK,
+        };
+        pos.st.castling = (state & 0x1e) as CastleRights;
+
+        pos.st.ep_square = match (state >> 5) & 0xf {
+            0 => None,
+            1..=8 => Some(make_sq(
+                ((state >> 5) & 0xf) as File - 1,
+                if pos.st.side == color::WHITE {
+                    RANK_6
+                } else {
+                    RANK_3
+                },
+            )),
+            _ => return None,
+        };
+
+        for (i, rook) in pos.st.castling_rooks.iter_mut().enumerate() {
+            let rank = if i < 2 { RANK_1 } else { RANK_8 };
+            *rook = make_sq(((state >> (9 + 3 * i)) & 7) as File, rank);
+        }
+
+        pos.st.rule50 = bytes[28];
+        pos.st.fullmove = u16::from_le_bytes(bytes[29..31].try_into().unwrap());
+
+        pos.clear_uncapturable_ep();
+        pos.st.key = zobrist::hash(&pos, zb);
+        pos.st.pawn_key = zobrist::pawn_hash(&pos, zb);
+
+        Some(pos)
+    }
+
+    /// returns the FEN string of the position, with all six fields
+    ///
+    /// castling rights are written as `KQkq`, unless the castling rook isn't the outermost rook
+    /// on its side of the king, which only happens in chess960, then the rook's file is written instead (X-FEN)
+    pub fn to_fen(&self) -> String {
+        self.fen_with(false)
+    }
+
+    /// same as `Position::to_fen()`, but castling rights are always written as the files of the
+    /// castling rooks, e.g. `HAha` instead of `KQkq` (Shredder-FEN)
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen_with(true)
+    }
+
+    fn fen_with(&self, shredder: bool) -> String {
+        let mut fen = String::new();
+
+        for rank in (RANK_1..=RANK_8).rev() {
+            let mut empty = 0;
+
+            for file in FILE_A..=FILE_H {
+                let piece = self.piece_on_fr(file, rank);
+
+                if piece == piece::NONE {
+                    empty += 1;
+                    continue;
+                }
+
+                if empty > 0 {
+                    fen.push((b'0' + empty) as char);
+                    empty = 0;
+                }
+
+                fen.push(piece::as_char(piece));
+            }
+
+            if empty > 0 {
+                fen.push((b'0' + empty) as char);
+            }
+
+            if rank != RANK_1 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(match self.st.side {
+            color::WHITE => " w ",
+            _ => " b ",
+        });
+
+        if self.st.castling == NO_CASTLING {
+            fen.push('-');
+        } else {
+            for (side, king_side) in [
+                (color::WHITE, true),
+                (color::WHITE, false),
+                (color::BLACK, true),
+                (color::BLACK, false),
+            ] {
+                let (right, _) = Self::castling_right_of(side, king_side);
+
+                if self.st.castling & right == 0 {
+                    continue;
+                }
+
+                let rook = self.castling_rook(right);
+
+                let ch = if !shredder && self.outermost_rook(side, king_side) == Some(rook) {
+                    if king_side { 'k' } else { 'q' }
+                } else {
+                    (b'a' + file_of(rook) as u8) as char
+                };
+
+                fen.push(match side {
+                    color::WHITE => ch.to_ascii_uppercase(),
+                    _ => ch,
+                });
+            }
+        }
+
+        fen.push(' ');
+
+        match self.st.ep_square {
+            Some(square) => fen.push_str(&to_algn(square)),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(" {} {}", self.st.rule50, self.st.fullmove));
+
+        fen
+    }
+
+    /// returns a diagram of both boards side by side, `self` on the left and `other` on the right,
+    /// with squares that differ marked by a `*`, followed by a line for every other FEN field that differs
+    ///
+    /// useful for debugging, e.g. when comparing the expected and actual position in a failing test
+    pub fn diff_diagram(&self, other: &Position) -> String {
+        let mut diagram = String::new();
+        let mut differing = Vec::new();
+
+        for rank in (RANK_1..=RANK_8).rev() {
+            let mut left = format!("{} ", rank + 1);
+            let mut right = format!("{} ", rank + 1);
+
+            for file in FILE_A..=FILE_H {
+                let (a, b) = (self.piece_on_fr(file, rank), other.piece_on_fr(file, rank));
+                let mark = if a != b { '*' } else { ' ' };
+
+                if a != b {
+                    differing.push(to_algn(make_sq(file, rank)));
+                }
+
+                for (string, piece) in [(&mut left, a), (&mut right, b)] {
+                    string.push(match piece {
+                        piece::NONE => '.',
+                        _ => piece::as_char(piece),
+                    });
+                    string.push(mark);
+                }
+            }
+
+            diagram.push_str(&format!("{left}   {right}\n"));
+        }
+
+        diagram.push_str("  a b c d e f g h       a b c d e f g h\n");
+
+        if !differing.is_empty() {
+            diagram.push_str(&format!("squares: {}\n", differing.join(" ")));
+        }
+
+        let (fen_a, fen_b) = (self.to_fen(), other.to_fen());
+
+        for ((name, a), b) in [
+            "side to move",
+            "castling",
+            "en passant",
+            "halfmove clock",
+            "fullmove number",
+        ]
+        .iter()
+        .zip(fen_a.split(' ').skip(1))
+        .zip(fen_b.split(' ').skip(1))
+        {
+            if a != b {
+                diagram.push_str(&format!("{name}: {a} | {b}\n"));
+            }
+        }
+
+        diagram
+    }
+
+    /// returns the position flipped vertically with the colors of all pieces swapped,
+    /// so white's pieces stand where black's stood and the other side is to move,
+    /// castling rights and the en passant square are flipped along with the board
+    ///
+    /// the evaluation of the returned position from the side to move's point of view is the same,
+    /// which makes it useful for testing evaluation and move generation symmetry
+    ///
+    /// the returned position has a clear history
+    pub fn mirrored(&self, zb: &ZobristValues) -> Position {
+        let mut st = self.st;
+
+        for square in 0..64 {
+            let piece = self.st.board[square];
+
+            st.board[square ^ 56] = match piece {
+                piece::NONE => piece::NONE,
+                _ => piece::of(piece) | color::other(color::of(piece)),
+            };
+        }
+
+        for (i, &bb) in self.st.piece_bb.iter().enumerate() {
+            st.piece_bb[(i + 6) % 12] = bb::flip_vertical(bb);
+        }
+
+        st.color_bb = [
+            bb::flip_vertical(self.st.color_bb[1]),
+            bb::flip_vertical(self.st.color_bb[0]),
+        ];
+
+        st.side = color::other(self.st.side);
+        st.ep_square = self.st.ep_square.map(|square| square ^ 56);
+        st.castling = (self.st.castling & (WK_CASTLE | WQ_CASTLE)) << 2
+            | (self.st.castling & (BK_CASTLE | BQ_CASTLE)) >> 2;
+
+        let [wk, wq, bk, bq] = self.st.castling_rooks;
+        st.castling_rooks = [bk ^ 56, bq ^ 56, wk ^ 56, wq ^ 56];
+
+        let mut pos = Self::from_transformed(st, zb);
+        pos.chess960 = self.chess960;
+
+        pos
+    }
+
+    /// returns the position mirrored horizontally, file a becomes file h and so on,
+    /// the en passant square is mirrored along with the board
+    ///
+    /// castling rights are dropped, since after mirroring the king no longer castles
+    /// to the same side of the board as the rook it castles with
+    ///
+    /// the returned position has a clear history
+    pub fn flipped_horizontal(&self, zb: &ZobristValues) -> Position {
+        let mut st = self.st;
+
+        for square in 0..64 {
+            st.board[square ^ 7] = self.st.board[square];
+        }
+
+        for (i, &bb) in self.st.piece_bb.iter().enumerate() {
+            st.piece_bb[i] = bb::mirror_horizontal(bb);
+        }
+
+        for (i, &bb) in self.st.color_bb.iter().enumerate() {
+            st.color_bb[i] = bb::mirror_horizontal(bb);
+        }
+
+        st.ep_square = self.st.ep_square.map(|square| square ^ 7);
+        st.castling = NO_CASTLING;
+
+        Self::from_transformed(st, zb)
+    }
+
+    /// creates a position with a clear history from a transformed state, recomputing its keys
+    fn from_transformed(mut st: StateInfo, zb: &ZobristValues) -> Position {
+        st.ply = 0;
+        st.move_played = None;
+
+        let mut pos = Self::from_state(st);
+
+        pos.st.key = zobrist::hash(&pos, zb);
+        pos.st.pawn_key = zobrist::pawn_hash(&pos, zb);
+        // mirroring swaps the colors of the pieces, so the material counts move between the sides
+        let pieces: Vec<piece::Piece> = pos
+            .st
+            .board
+            .iter()
+            .copied()
+            .filter(|&p| p != piece::NONE)
+            .collect();
+        pos.st.material_key = material_key_of(&pieces);
+
         pos
     }
 
     /// prints a visual representation of the board
     pub fn visualize(&self) {
-        println!("");
-        println!("+---+---+---+---+---+---+---+---+");
+        println!("\n{self:#}\n");
+    }
+
+    /// writes the board diagram printed by `Position::visualize()`, without the surrounding blank lines
+    fn write_diagram(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "+---+---+---+---+---+---+---+---+")?;
         for rank in (RANK_1..=RANK_8).rev() {
             for file in FILE_A..=FILE_H {
-                print!("| {} ", piece::as_char(self.piece_on_fr(file, rank)));
+                write!(f, "| {} ", piece::as_char(self.piece_on_fr(file, rank)))?;
             }
-            println!("| {}", rank + 1);
-            println!("+---+---+---+---+---+---+---+---+");
+            writeln!(f, "| {}", rank + 1)?;
+            writeln!(f, "+---+---+---+---+---+---+---+---+")?;
         }
-        println!("  a   b   c   d   e   f   g   h\n");
+        write!(f, "  a   b   c   d   e   f   g   h")
     }
 
     /// prints a visual representation of the board, with the square indices instead of pieces
@@ -520,7 +1896,7 @@ impl Position {
 
     /// prints a visual representation of the board, but smaller than that of `Position::visualize()`
     pub fn visualize_smaller(&self) {
-        println!("");
+        println!();
         for rank in (RANK_1..=RANK_8).rev() {
             print!(" ");
             for file in FILE_A..=FILE_H {
@@ -540,6 +1916,7 @@ impl Position {
 
     /// prints a pretty visual representation of the board, using UTF-8 symbols for the chess pieces,
     /// and a colored board
+    #[cfg(feature = "render")]
     pub fn visualize_pretty(&self) {
         for rank in (RANK_1..=RANK_8).rev() {
             for file in FILE_A..=FILE_H {
@@ -571,194 +1948,731 @@ impl Position {
     /// the function doesn't check for move legality, and assumes that the type of the move is correct,
     /// for example it would technically allow you to play a pawn push disguised as a king side castle
     pub fn make_move(&mut self, mov: moves::Move, zb: &ZobristValues) {
-        self.history.push(self.st);
+        self.history.push(Undo {
+            ep_square: self.st.ep_square,
+            rule50: self.st.rule50,
+            castling: self.st.castling,
+            move_played: self.st.move_played,
+            key: self.st.key,
+            pawn_key: self.st.pawn_key,
+        });
+
+        #[cfg(feature = "full-history")]
+        self.states.push(self.st);
+
         self.st.rule50 += 1;
         self.st.move_played = Some(mov);
 
         self.st.ply += 1;
 
-        if let Some(square) = self.st.ep_square {
-            self.st.ep_square = None;
-            self.st.key ^= zb.ep_files[file_of(square) as usize];
+        if self.st.side == color::BLACK {
+            self.st.fullmove += 1;
+        }
+
+        if let Some(square) = self.st.ep_square {
+            self.st.ep_square = None;
+            self.st.key ^= zb.ep_files[file_of(square) as usize];
+        }
+
+        let moving_piece = self.st.board[mov.from_sq()];
+
+        if moving_piece & piece::PAWN != 0 {
+            self.st.rule50 = 0;
+            self.st.move_played.unwrap().is_reversible = false;
+        }
+
+        if moving_piece & piece::KING != 0 {
+            self.st.key ^= lose_kcastle_rights(&mut self.st.castling, self.st.side, zb);
+            self.st.key ^= lose_qcastle_rights(&mut self.st.castling, self.st.side, zb);
+        } else if moving_piece & piece::ROOK != 0 {
+            self.lose_rook_castling_rights(mov.from_sq(), self.st.side, zb);
+        }
+
+        let rook_captured = match mov.type_of() {
+            MoveType::Capture(cap) | MoveType::PromoCapture(_, cap) => cap & piece::ROOK != 0,
+            _ => false,
+        };
+
+        if rook_captured {
+            self.lose_rook_castling_rights(mov.to_sq(), color::other(self.st.side), zb);
+        }
+
+        match mov.type_of() {
+            MoveType::Normal => {
+                self.move_piece(mov.from_sq(), mov.to_sq(), zb);
+            }
+            MoveType::Capture(_) => {
+                self.move_piece(mov.from_sq(), mov.to_sq(), zb);
+
+                self.st.rule50 = 0;
+                self.st.move_played.unwrap().is_reversible = false;
+            }
+            MoveType::PawnTwoUp => {
+                self.move_piece(mov.from_sq(), mov.to_sq(), zb);
+
+                // the en passant square is only set if an enemy pawn can capture en passant, like in Polyglot,
+                // otherwise positions that are the same would have different keys and not count as repetitions
+                if self.can_capture_en_passant(mov.to_sq(), color::other(self.st.side)) {
+                    let sq_behind = behind(mov.to_sq(), self.st.side);
+
+                    self.st.ep_square = Some(sq_behind);
+                    self.st.key ^= zb.ep_files[file_of(sq_behind) as usize];
+                }
+            }
+            MoveType::Promotion(promoted) | MoveType::PromoCapture(promoted, _) => {
+                self.put_piece(promoted, mov.to_sq(), zb);
+                self.remove_piece(mov.from_sq(), zb);
+
+                self.st.move_played.unwrap().is_reversible = false;
+            }
+            MoveType::EnPassant => {
+                self.move_piece(mov.from_sq(), mov.to_sq(), zb);
+                self.remove_piece(behind(mov.to_sq(), self.st.side), zb);
+
+                self.st.move_played.unwrap().is_reversible = false;
+            }
+            MoveType::KingSideCastle | MoveType::QueenSideCastle => {
+                let (rook_from, rook_to) = self
+                    .castling_rook_move(self.st.side, mov.type_of() == MoveType::KingSideCastle);
+                let (king, rook) = (self.st.board[mov.from_sq()], self.st.board[rook_from]);
+
+                // in chess960 the king and rook can land on each other's starting squares,
+                // or not move at all, so both are lifted off the board before being put back down
+                self.remove_piece(mov.from_sq(), zb);
+                self.remove_piece(rook_from, zb);
+                self.put_piece(king, mov.to_sq(), zb);
+                self.put_piece(rook, rook_to, zb);
+
+                self.st.move_played.unwrap().is_reversible = false;
+            }
+        }
+
+        color::switch(&mut self.st.side);
+        self.st.key ^= zb.black_to_move;
+    }
+
+    /// returns the key the position would have after `mov` is made, without making it,
+    /// e.g. for prefetching transposition table entries
+    ///
+    /// like `Position::make_move()`, doesn't check if the move is legal
+    pub fn key_after(&self, mov: moves::Move, zb: &ZobristValues) -> zobrist::Key {
+        let side = self.st.side;
+        let psq = |piece: piece::Piece, square: Square| zb.piece_sq[bb::p_to_idx(piece)][square];
+
+        let mut key = self.st.key ^ zb.black_to_move;
+        let mut castling = self.st.castling;
+
+        if let Some(square) = self.st.ep_square {
+            key ^= zb.ep_files[file_of(square) as usize];
+        }
+
+        let moving = self.st.board[mov.from_sq()];
+
+        // the castling rights lost if a rook of `color` leaves or is captured on `square`
+        let mut lose_rook_rights = |square: Square, color: color::Color| {
+            let (kcastle, _) = Self::castling_right_of(color, true);
+            let (qcastle, _) = Self::castling_right_of(color, false);
+
+            if castling & kcastle != 0 && self.castling_rook(kcastle) == square {
+                lose_kcastle_rights(&mut castling, color, zb)
+            } else if castling & qcastle != 0 && self.castling_rook(qcastle) == square {
+                lose_qcastle_rights(&mut castling, color, zb)
+            } else {
+                0
+            }
+        };
+
+        if moving & piece::ROOK != 0 {
+            key ^= lose_rook_rights(mov.from_sq(), side);
+        }
+
+        if let MoveType::Capture(cap) | MoveType::PromoCapture(_, cap) = mov.type_of()
+            && cap & piece::ROOK != 0
+        {
+            key ^= lose_rook_rights(mov.to_sq(), color::other(side));
+        }
+
+        if moving & piece::KING != 0 {
+            key ^= lose_kcastle_rights(&mut castling, side, zb);
+            key ^= lose_qcastle_rights(&mut castling, side, zb);
+        }
+
+        match mov.type_of() {
+            MoveType::Normal => {
+                key ^= psq(moving, mov.from_sq()) ^ psq(moving, mov.to_sq());
+            }
+            MoveType::Capture(_) => {
+                key ^= psq(moving, mov.from_sq())
+                    ^ psq(moving, mov.to_sq())
+                    ^ psq(self.st.board[mov.to_sq()], mov.to_sq());
+            }
+            MoveType::PawnTwoUp => {
+                key ^= psq(moving, mov.from_sq()) ^ psq(moving, mov.to_sq());
+
+                if self.can_capture_en_passant(mov.to_sq(), color::other(side)) {
+                    key ^= zb.ep_files[file_of(mov.to_sq()) as usize];
+                }
+            }
+            MoveType::Promotion(promoted) => {
+                key ^= psq(moving, mov.from_sq()) ^ psq(promoted, mov.to_sq());
+            }
+            MoveType::PromoCapture(promoted, _) => {
+                key ^= psq(moving, mov.from_sq())
+                    ^ psq(promoted, mov.to_sq())
+                    ^ psq(self.st.board[mov.to_sq()], mov.to_sq());
+            }
+            MoveType::EnPassant => {
+                let captured = behind(mov.to_sq(), side);
+
+                key ^= psq(moving, mov.from_sq())
+                    ^ psq(moving, mov.to_sq())
+                    ^ psq(self.st.board[captured], captured);
+            }
+            MoveType::KingSideCastle | MoveType::QueenSideCastle => {
+                let (rook_from, rook_to) =
+                    self.castling_rook_move(side, mov.type_of() == MoveType::KingSideCastle);
+                let rook = self.st.board[rook_from];
+
+                key ^= psq(moving, mov.from_sq())
+                    ^ psq(moving, mov.to_sq())
+                    ^ psq(rook, rook_from)
+                    ^ psq(rook, rook_to);
+            }
+        }
+
+        key
+    }
+
+    /// checks that `mov` is legal before making it, leaving the position untouched if it isn't
+    ///
+    /// meant for moves from untrusted sources like a network connection or user input,
+    /// where `Position::make_move()` trusting the caller could corrupt the position
+    pub fn try_make_move(
+        &mut self,
+        mov: moves::Move,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Result<(), IllegalMove> {
+        let moving = self.st.board[mov.from_sq()];
+
+        if moving == piece::NONE || color::of(moving) != self.st.side {
+            return Err(IllegalMove::NoPiece(mov.from_sq()));
+        }
+
+        if !self.is_legal(mov, masks, zb) {
+            if self.is_pseudo_legal(mov, masks) {
+                return Err(IllegalMove::LeavesKingInCheck);
+            }
+
+            let same_squares = moves::gen_pseudo_legal(self, masks)
+                .iter()
+                .find(|m| m.from_sq() == mov.from_sq() && m.to_sq() == mov.to_sq())
+                .map(|m| m.type_of());
+
+            return Err(match same_squares {
+                Some(expected) => IllegalMove::WrongMoveType(expected),
+                None => IllegalMove::Unreachable,
+            });
+        }
+
+        self.make_move(mov, zb);
+
+        Ok(())
+    }
+
+    /// unmakes the last move played in a position, by moving the pieces back
+    /// and restoring what can't be recomputed from the last entry of the history
+    ///
+    /// with the `full-history` feature, the whole previous state is restored instead
+    ///
+    /// **panics** in debug if there are no moves to be unmade
+    pub fn unmake_move(&mut self) {
+        debug_assert!(
+            !self.history.is_empty(),
+            "tried to unmake move on a start position"
+        );
+
+        #[cfg(feature = "full-history")]
+        {
+            self.history.pop();
+            self.st = self.states.pop().unwrap();
+        }
+
+        #[cfg(not(feature = "full-history"))]
+        {
+            let undo = self.history.pop().unwrap();
+            let mov = self.st.move_played.unwrap();
+            color::switch(&mut self.st.side);
+            let side = self.st.side;
+
+            match mov.type_of() {
+                MoveType::Normal | MoveType::PawnTwoUp => {
+                    self.shift_piece(mov.to_sq(), mov.from_sq());
+                }
+                MoveType::Capture(cap) => {
+                    self.shift_piece(mov.to_sq(), mov.from_sq());
+                    self.set_piece(cap, mov.to_sq());
+                }
+                MoveType::Promotion(_) => {
+                    self.clear_piece(mov.to_sq());
+                    self.set_piece(piece::PAWN | side, mov.from_sq());
+                }
+                MoveType::PromoCapture(_, cap) => {
+                    self.clear_piece(mov.to_sq());
+                    self.set_piece(piece::PAWN | side, mov.from_sq());
+                    self.set_piece(cap, mov.to_sq());
+                }
+                MoveType::EnPassant => {
+                    self.shift_piece(mov.to_sq(), mov.from_sq());
+                    self.set_piece(piece::PAWN | color::other(side), behind(mov.to_sq(), side));
+                }
+                MoveType::KingSideCastle | MoveType::QueenSideCastle => {
+                    let (rook_from, rook_to) =
+                        self.castling_rook_move(side, mov.type_of() == MoveType::KingSideCastle);
+                    let (king, rook) = (self.st.board[mov.to_sq()], self.st.board[rook_to]);
+
+                    self.clear_piece(mov.to_sq());
+                    self.clear_piece(rook_to);
+                    self.set_piece(king, mov.from_sq());
+                    self.set_piece(rook, rook_from);
+                }
+            }
+
+            if side == color::BLACK {
+                self.st.fullmove -= 1;
+            }
+
+            self.st.ply -= 1;
+            self.st.ep_square = undo.ep_square;
+            self.st.rule50 = undo.rule50;
+            self.st.castling = undo.castling;
+            self.st.move_played = undo.move_played;
+            self.st.key = undo.key;
+            self.st.pawn_key = undo.pawn_key;
         }
+    }
 
-        let moving_piece = self.st.board[mov.from_sq()];
+    /// checks that the position could occur in a game, useful after reading positions from untrusted sources,
+    /// since most other functions assume a valid position and may panic or misbehave otherwise
+    ///
+    /// checks that each side has exactly one king, that there are no pawns on the first or eighth rank,
+    /// that the en passant square and castling rights match the pieces on the board,
+    /// that the side not to move isn't in check, and that the bitboards match the board array
+    pub fn validate(&self, masks: &AttackMasks) -> Result<(), InvalidPosition> {
+        let mut piece_bb = [bb::EMPTY; 12];
+        let mut color_bb = [bb::EMPTY; 2];
+
+        for square in 0..64 {
+            let piece = self.st.board[square];
+
+            if piece != piece::NONE {
+                piece_bb[bb::p_to_idx(piece)] |= bb::square_bb(square);
+                color_bb[bb::c_to_idx(color::of(piece))] |= bb::square_bb(square);
+            }
+        }
 
-        if moving_piece & piece::PAWN != 0 {
-            self.st.rule50 = 0;
-            self.st.move_played.unwrap().is_reversible = false;
+        if piece_bb != self.st.piece_bb || color_bb != self.st.color_bb {
+            return Err(InvalidPosition::BoardMismatch);
         }
 
-        if moving_piece & piece::KING != 0 {
-            self.st.key ^= lose_kcastle_rights(&mut self.st.castling, self.st.side, zb);
-            self.st.key ^= lose_qcastle_rights(&mut self.st.castling, self.st.side, zb);
-        } else if moving_piece & piece::ROOK != 0 {
-            self.st.key ^= match file_of(mov.from_sq()) {
-                FILE_A => lose_qcastle_rights(&mut self.st.castling, self.st.side, zb),
-                FILE_H => lose_kcastle_rights(&mut self.st.castling, self.st.side, zb),
-                _ => 0,
+        for side in [color::WHITE, color::BLACK] {
+            let kings = self.piece_bb(piece::KING | side).count_ones();
+
+            if kings != 1 {
+                return Err(InvalidPosition::KingCount(side, kings));
             }
         }
 
-        let rook_captured = match mov.type_of() {
-            MoveType::Capture(cap) | MoveType::PromoCapture(_, cap) => cap & piece::ROOK != 0,
-            _ => false,
-        };
+        let pawns = self.piece_bb(piece::WHITE_PAWN) | self.piece_bb(piece::BLACK_PAWN);
 
-        if rook_captured {
-            self.st.key ^= match self.st.side {
-                color::WHITE => match mov.to_sq() {
-                    BQ_ROOK_SQ => lose_qcastle_rights(&mut self.st.castling, color::BLACK, zb),
-                    BK_ROOK_SQ => lose_kcastle_rights(&mut self.st.castling, color::BLACK, zb),
-                    _ => 0,
-                },
-                color::BLACK => match mov.to_sq() {
-                    WQ_ROOK_SQ => lose_qcastle_rights(&mut self.st.castling, color::WHITE, zb),
-                    WK_ROOK_SQ => lose_kcastle_rights(&mut self.st.castling, color::WHITE, zb),
-                    _ => 0,
-                },
-                _ => 0,
-            }
+        if pawns & (bb::RANK_1_MASK | bb::RANK_8_MASK) != bb::EMPTY {
+            return Err(InvalidPosition::PawnOnBackRank(
+                (pawns & (bb::RANK_1_MASK | bb::RANK_8_MASK)).trailing_zeros() as Square,
+            ));
         }
 
-        match mov.type_of() {
-            MoveType::Normal => {
-                self.move_piece(mov.from_sq(), mov.to_sq(), zb);
-            }
-            MoveType::Capture(_) => {
-                self.move_piece(mov.from_sq(), mov.to_sq(), zb);
+        if let Some(ep) = self.st.ep_square
+            && !self.ep_square_is_consistent(ep)
+        {
+            return Err(InvalidPosition::EnPassantSquare(ep));
+        }
 
-                self.st.rule50 = 0;
-                self.st.move_played.unwrap().is_reversible = false;
+        for (side, king_side) in [
+            (color::WHITE, true),
+            (color::WHITE, false),
+            (color::BLACK, true),
+            (color::BLACK, false),
+        ] {
+            let (right, rank) = Self::castling_right_of(side, king_side);
+
+            if self.st.castling & right == 0 {
+                continue;
             }
-            MoveType::PawnTwoUp => {
-                self.move_piece(mov.from_sq(), mov.to_sq(), zb);
 
-                let sq_behind = behind(mov.to_sq(), self.st.side);
+            let king = self.piece_bb(piece::KING | side).trailing_zeros() as Square;
+            let rook = self.castling_rook(right);
 
-                self.st.ep_square = Some(sq_behind);
-                self.st.key ^= zb.ep_files[file_of(sq_behind) as usize];
+            if rank_of(king) != rank
+                || rank_of(rook) != rank
+                || self.st.board[rook] != piece::ROOK | side
+                || (rook > king) != king_side
+            {
+                return Err(InvalidPosition::CastlingRights(right));
             }
-            MoveType::Promotion(promoted) | MoveType::PromoCapture(promoted, _) => {
-                self.put_piece(promoted, mov.to_sq(), zb);
-                self.remove_piece(mov.from_sq(), zb);
+        }
 
-                self.st.move_played.unwrap().is_reversible = false;
-            }
-            MoveType::EnPassant => {
-                self.move_piece(mov.from_sq(), mov.to_sq(), zb);
-                self.remove_piece(behind(mov.to_sq(), self.st.side), zb);
+        let them = color::other(self.st.side);
 
-                self.st.move_played.unwrap().is_reversible = false;
-            }
-            MoveType::KingSideCastle => {
-                self.move_piece(mov.from_sq(), mov.to_sq(), zb);
+        if bb::is_attacked(
+            self.piece_bb(piece::KING | them).trailing_zeros() as Square,
+            self,
+            self.st.side,
+            masks,
+        ) {
+            return Err(InvalidPosition::OpponentInCheck);
+        }
 
-                let (rook_from, rook_to): (Square, Square) = if self.st.side == color::WHITE {
-                    (WK_ROOK_SQ, WK_ROOK_SQ - 2)
-                } else {
-                    (BK_ROOK_SQ, BK_ROOK_SQ - 2)
-                };
+        Ok(())
+    }
 
-                self.move_piece(rook_from, rook_to, zb);
-                self.st.move_played.unwrap().is_reversible = false;
+    /// returns the repairs that make the position pass `Position::validate()`,
+    /// in the order they have to be applied, or the first problem that can't be repaired, e.g. a missing king
+    ///
+    /// meant for FEN strings from sources like the web, which are often slightly wrong,
+    /// the returned list is empty if the position is already valid
+    pub fn suggest_repairs(
+        &self,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Result<Vec<Repair>, InvalidPosition> {
+        let mut pos = self.clone();
+        let mut repairs = Vec::new();
+
+        while let Err(invalid) = pos.validate(masks) {
+            let repair = invalid.repair().ok_or(invalid)?;
+
+            // switching sides back and forth can't fix a position where both kings are in check
+            if repairs.contains(&repair) {
+                return Err(invalid);
             }
-            MoveType::QueenSideCastle => {
-                self.move_piece(mov.from_sq(), mov.to_sq(), zb);
 
-                let (rook_from, rook_to): (Square, Square) = if self.st.side == color::WHITE {
-                    (WQ_ROOK_SQ, WQ_ROOK_SQ + 3)
-                } else {
-                    (BQ_ROOK_SQ, BQ_ROOK_SQ + 3)
-                };
+            pos.apply_repairs(&[repair], zb);
+            repairs.push(repair);
+        }
 
-                self.move_piece(rook_from, rook_to, zb);
-                self.st.move_played.unwrap().is_reversible = false;
+        Ok(repairs)
+    }
+
+    /// applies repairs returned by `Position::suggest_repairs()`, updating the key
+    pub fn apply_repairs(&mut self, repairs: &[Repair], zb: &ZobristValues) {
+        for repair in repairs {
+            match *repair {
+                Repair::DropCastlingRight(right) => self.st.castling &= !right,
+                Repair::ClearEnPassant => self.st.ep_square = None,
+                Repair::SwitchSideToMove => self.st.side = color::other(self.st.side),
             }
         }
 
-        color::switch(&mut self.st.side);
-        self.st.key ^= zb.black_to_move;
+        self.st.key = zobrist::hash(self, zb);
+    }
+
+    /// returns true if the en passant square `ep` is right behind a pawn that could have just moved
+    /// two squares, so that capturing en passant doesn't remove a piece that isn't there
+    pub(crate) fn ep_square_is_consistent(&self, ep: Square) -> bool {
+        let side = self.st.side;
+        let ep_rank = match side {
+            color::WHITE => RANK_6,
+            _ => RANK_3,
+        };
+
+        rank_of(ep) == ep_rank
+            && self.st.board[behind(ep, side)] == piece::PAWN | color::other(side)
+            && !self.is_occupied(ep)
+            && !self.is_occupied(ahead(ep, side))
     }
 
-    /// unmakes the last move played in a position
-    /// by simply setting the current state to the last one in the `history` vector
+    /// clears the en passant square if no pawn can capture on it, the same as `Position::make_move()`
+    /// only sets it when one can, so the key doesn't depend on how the position was reached
     ///
-    /// **panics** in debug if there are no moves to be unmade
-    pub fn unmake_move(&mut self) {
-        debug_assert!(
-            self.history.len() != 0,
-            "tried to unmake move on a start position"
-        );
+    /// inconsistent en passant squares are kept, for `Position::validate()` to report them
+    fn clear_uncapturable_ep(&mut self) {
+        let side = self.st.side;
+
+        if let Some(ep) = self.st.ep_square
+            && self.ep_square_is_consistent(ep)
+            && !self.can_capture_en_passant(behind(ep, side), side)
+        {
+            self.st.ep_square = None;
+        }
+    }
+
+    /// returns true if a pawn of `side` stands next to `pawn_sq`,
+    /// so it could capture en passant if a pawn just moved two squares to `pawn_sq`
+    fn can_capture_en_passant(&self, pawn_sq: Square, side: color::Color) -> bool {
+        let pawn = bb::square_bb(pawn_sq);
+        let adjacent = ((pawn << 1) & !bb::FILE_A_MASK) | ((pawn >> 1) & !bb::FILE_H_MASK);
+
+        adjacent & self.piece_bb(piece::PAWN | side) != bb::EMPTY
+    }
+
+    /// returns how the game ended if the position is the end of a game by the rules, otherwise `None`
+    ///
+    /// checkmate and stalemate take precedence over the draws, which means a checkmate
+    /// on the move that reaches the 50 move limit still wins
+    pub fn outcome(&mut self, masks: &AttackMasks, zb: &ZobristValues) -> Option<Outcome> {
+        if moves::legal_moves(self, masks, zb).next().is_none() {
+            return Some(if self.is_check(masks) {
+                Outcome::Checkmate {
+                    winner: color::other(self.st.side),
+                }
+            } else {
+                Outcome::Stalemate
+            });
+        }
+
+        if self.insufficient_material(color::WHITE) && self.insufficient_material(color::BLACK) {
+            Some(Outcome::InsufficientMaterial)
+        } else if self.st.rule50 >= 100 {
+            Some(Outcome::FiftyMoveRule)
+        } else if self.is_3_rep() {
+            Some(Outcome::Repetition)
+        } else {
+            None
+        }
+    }
 
-        self.st = *self.history.last().unwrap();
-        self.history.pop();
+    /// returns the reason the position is drawn by the rules, or `None` if it isn't,
+    /// combining stalemate, insufficient material for both sides, the 50 move rule and threefold repetition
+    ///
+    /// a checkmate delivered on the last move before the 50 move rule applies isn't a draw
+    pub fn is_draw(&mut self, masks: &AttackMasks, zb: &ZobristValues) -> Option<DrawReason> {
+        match self.outcome(masks, zb)? {
+            Outcome::Checkmate { .. } => None,
+            Outcome::Stalemate => Some(DrawReason::Stalemate),
+            Outcome::FiftyMoveRule => Some(DrawReason::FiftyMoveRule),
+            Outcome::Repetition => Some(DrawReason::Repetition),
+            Outcome::InsufficientMaterial => Some(DrawReason::InsufficientMaterial),
+        }
     }
 
     /// returns true if a position has occured at least 3 times, otherwise false
     pub fn is_3_rep(&self) -> bool {
-        if let Some(mov) = self.st.move_played {
-            if !mov.is_reversible() {
-                return false;
+        self.has_occurred(3)
+    }
+
+    /// returns true if a position has occured at least 5 times, otherwise false
+    ///
+    /// unlike threefold repetition, which has to be claimed, this ends the game automatically under FIDE rules
+    pub fn is_5_rep(&self) -> bool {
+        self.has_occurred(5)
+    }
+
+    /// returns true if 75 moves were played by each side without a capture or pawn move,
+    /// which ends the game automatically under FIDE rules, unlike the 50 move rule which has to be claimed
+    #[inline(always)]
+    pub fn is_75_move_rule(&self) -> bool {
+        self.st.rule50 >= RULE_75_PLIES
+    }
+
+    /// returns true if the current position has occured at least `times` times
+    fn has_occurred(&self, times: usize) -> bool {
+        self.earlier_occurrences().nth(times - 2).is_some()
+    }
+
+    /// returns how many times the current position has occurred, including this time,
+    /// so 1 means the position is new
+    pub fn repetition_count(&self) -> usize {
+        self.earlier_occurrences().count() + 1
+    }
+
+    /// returns true if the current position already occurred at `root_ply` or later,
+    /// where `root_ply` is `Position::ply()` at the root of a search
+    ///
+    /// engines usually score a single repetition inside the search tree as a draw,
+    /// as the side that could avoid it would have done so if it wanted to
+    pub fn has_repeated_once_since_root(&self, root_ply: usize) -> bool {
+        self.earlier_occurrences()
+            .next()
+            .is_some_and(|ply| ply >= root_ply)
+    }
+
+    /// the plies of every earlier occurrence of the current position, from the most recent one,
+    /// only looking back as far as the last irreversible move
+    fn earlier_occurrences(&self) -> impl Iterator<Item = usize> + '_ {
+        // the ply of the first entry in the history, not 0 for positions created with `from_ply()`
+        let base = self.st.ply - self.history.len();
+        let len = self.history.len();
+
+        let reversible = self.st.move_played.is_none_or(|mov| mov.is_reversible());
+        // the same side has to be to move, so only every other position can be a repetition
+        let mut idx = (reversible && len >= 4 && self.st.rule50 >= 4).then(|| len - 4);
+
+        std::iter::from_fn(move || {
+            while let Some(i) = idx {
+                let entry = self.history[i];
+
+                idx = match entry.move_played {
+                    Some(mov) if !mov.is_reversible() => None,
+                    _ if i < 2 || len - (i - 2) > self.st.rule50 as usize => None,
+                    _ => Some(i - 2),
+                };
+
+                if entry.key == self.st.key {
+                    return Some(base + i);
+                }
             }
+
+            None
+        })
+    }
+}
+
+impl Position {
+    /// the castling right and rank that belong to `side` castling on `king_side`
+    #[inline(always)]
+    fn castling_right_of(side: color::Color, king_side: bool) -> (CastleRights, Rank) {
+        match (side, king_side) {
+            (color::WHITE, true) => (WK_CASTLE, RANK_1),
+            (color::WHITE, false) => (WQ_CASTLE, RANK_1),
+            (_, true) => (BK_CASTLE, RANK_8),
+            (_, false) => (BQ_CASTLE, RANK_8),
         }
+    }
 
-        // 6 is the minimum number of plies needed for a 3-fold repetition to be possible
-        if self.history.len() < 6 {
-            return false;
+    /// the square of the rook of `side` furthest away from its king on the back rank,
+    /// on the king or queen side, if there is one
+    fn outermost_rook(&self, side: color::Color, king_side: bool) -> Option<Square> {
+        let (_, rank) = Self::castling_right_of(side, king_side);
+        let king = self.piece_bb(piece::KING | side) & bb::rank_bb(rank);
+
+        if king == bb::EMPTY {
+            return None;
         }
 
-        let mut idx = self.st.ply - 4;
-        let mut cnt = 0;
+        let king_file = file_of(king.trailing_zeros() as Square);
+        let is_rook = |&file: &File| self.piece_on_fr(file, rank) == piece::ROOK | side;
 
-        loop {
-            if self.history[idx].key == self.st.key {
-                cnt += 1;
-                if cnt == 2 {
-                    return true;
-                }
-            }
+        let file = if king_side {
+            (king_file + 1..=FILE_H).rev().find(is_rook)
+        } else {
+            (FILE_A..king_file).find(is_rook)
+        };
 
-            if let Some(mov) = self.history[idx].move_played {
-                if !mov.is_reversible() {
-                    break;
-                }
-            }
+        file.map(|file| make_sq(file, rank))
+    }
 
-            if idx < 2 {
-                break;
+    /// adds a castling right while parsing the castling field of a FEN string
+    ///
+    /// `KQkq` use the outermost rook on that side of the king as the castling rook (X-FEN),
+    /// file letters (`A-H` for white, `a-h` for black) name the rook's file directly (Shredder-FEN),
+    /// the right isn't added if there is no such rook
+    fn add_castling_right(&mut self, ch: char) {
+        let side = if ch.is_ascii_uppercase() {
+            color::WHITE
+        } else {
+            color::BLACK
+        };
+
+        let rook = match ch.to_ascii_lowercase() {
+            'k' => self.outermost_rook(side, true),
+            'q' => self.outermost_rook(side, false),
+            file @ 'a'..='h' => {
+                let (_, rank) = Self::castling_right_of(side, true);
+                let square = make_sq((file as u8 - b'a') as File, rank);
+
+                (self.st.board[square] == piece::ROOK | side).then_some(square)
             }
+            _ => panic!("I don't think this is a fen string"),
+        };
+
+        let king = self.piece_bb(piece::KING | side);
+
+        if let Some(rook) = rook
+            && king != bb::EMPTY
+        {
+            let king_side = rook > king.trailing_zeros() as Square;
+            let (right, _) = Self::castling_right_of(side, king_side);
 
-            idx -= 2;
+            self.st.castling |= right;
+            self.st.castling_rooks[right.trailing_zeros() as usize - 1] = rook;
         }
+    }
+
+    /// removes the castling rights of `side` that belong to a rook on `square`,
+    /// called when that rook moves or is captured
+    #[inline(always)]
+    fn lose_rook_castling_rights(
+        &mut self,
+        square: Square,
+        side: color::Color,
+        zb: &ZobristValues,
+    ) {
+        let (kcastle, qcastle) = match side {
+            color::WHITE => (WK_CASTLE, WQ_CASTLE),
+            _ => (BK_CASTLE, BQ_CASTLE),
+        };
 
-        false
+        if self.st.castling & kcastle != 0 && self.castling_rook(kcastle) == square {
+            self.st.key ^= lose_kcastle_rights(&mut self.st.castling, side, zb);
+        } else if self.st.castling & qcastle != 0 && self.castling_rook(qcastle) == square {
+            self.st.key ^= lose_qcastle_rights(&mut self.st.castling, side, zb);
+        }
     }
-}
 
-impl Position {
     fn put_piece(&mut self, piece: piece::Piece, square: Square, zb: &ZobristValues) {
         if self.is_occupied(square) {
             self.remove_piece(square, zb);
         }
 
+        self.set_piece(piece, square);
+        self.update_keys(piece, square, zb);
+    }
+
+    fn remove_piece(&mut self, square: Square, zb: &ZobristValues) {
+        self.update_keys(self.st.board[square], square, zb);
+        self.clear_piece(square);
+    }
+
+    /// toggles `piece` on `square` in all zobrist keys that hash it
+    #[inline(always)]
+    fn update_keys(&mut self, piece: piece::Piece, square: Square, zb: &ZobristValues) {
+        let value = zb.piece_sq[bb::p_to_idx(piece)][square];
+
+        self.st.key ^= value;
+
+        if piece & piece::PAWN != 0 {
+            self.st.pawn_key ^= value;
+        }
+    }
+
+    /// puts a piece on an empty square without updating the key
+    #[inline(always)]
+    fn set_piece(&mut self, piece: piece::Piece, square: Square) {
         self.st.board[square] = piece;
+        self.st.material_key += 1 << (4 * bb::p_to_idx(piece));
 
         self.piece_bb_mut(piece).set_bit(square);
         self.color_bb_mut(piece).set_bit(square);
-
-        self.st.key ^= zb.piece_sq[bb::p_to_idx(piece)][square];
     }
 
-    fn remove_piece(&mut self, square: Square, zb: &ZobristValues) {
-        self.st.key ^= zb.piece_sq[bb::p_to_idx(self.st.board[square])][square];
-
+    /// removes the piece on a square without updating the key
+    #[inline(always)]
+    fn clear_piece(&mut self, square: Square) {
+        self.st.material_key -= 1 << (4 * bb::p_to_idx(self.st.board[square]));
         self.piece_bb_mut(self.st.board[square]).pop_bit(square);
         self.color_bb_mut(self.st.board[square]).pop_bit(square);
 
         self.st.board[square] = piece::NONE;
     }
 
+    /// moves a piece to an empty square without updating the key
+    #[inline(always)]
+    #[cfg(not(feature = "full-history"))]
+    fn shift_piece(&mut self, from: Square, to: Square) {
+        self.set_piece(self.st.board[from], to);
+        self.clear_piece(from);
+    }
+
     fn move_piece(&mut self, from: Square, to: Square, zb: &ZobristValues) {
         self.put_piece(self.st.board[from], to, zb);
         self.remove_piece(from, zb);
@@ -821,6 +2735,38 @@ impl Position {
     }
 }
 
+/// returns the material key of a position with exactly the pieces in `pieces`
+pub fn material_key_of(pieces: &[piece::Piece]) -> MaterialKey {
+    pieces
+        .iter()
+        .map(|&piece| 1 << (4 * bb::p_to_idx(piece)))
+        .sum()
+}
+
+/// returns the material key described by a string like `"KRvK"` or `"KBNvKP"`,
+/// with white's pieces before the `v` and black's after it, or `None` if the string is invalid
+pub fn material_key_from_str(string: &str) -> Option<MaterialKey> {
+    let (white, black) = string.split_once(['v', 'V'])?;
+    let mut pieces = Vec::new();
+
+    for (part, side) in [(white, color::WHITE), (black, color::BLACK)] {
+        for ch in part.chars() {
+            match piece::of(piece::from_char(ch)) {
+                piece::NONE => return None,
+                piece => pieces.push(piece | side),
+            }
+        }
+    }
+
+    Some(material_key_of(&pieces))
+}
+
+/// the number of pieces of type and color `piece` in a material key
+#[inline(always)]
+pub fn material_count(key: MaterialKey, piece: piece::Piece) -> u32 {
+    ((key >> (4 * bb::p_to_idx(piece))) & 0xf) as u32
+}
+
 /// takes a file and rank number and returns the equivalent square index
 pub fn make_sq(file: File, rank: Rank) -> Square {
     debug_assert!(file <= FILE_H, "file index is out of bounds");
@@ -851,11 +2797,39 @@ pub fn rank_of(square: Square) -> Rank {
 }
 
 /// converts a `String` in algebraic notation to a square index
+#[allow(clippy::ptr_arg)]
 pub fn string_to_sq(string: &String) -> Square {
-    str_to_sq(&string)
+    str_to_sq(string)
+}
+
+/// converts a square in algebraic notation (e.g. `"e4"`) to a square index,
+/// returning an error instead of panicking on invalid input like `pos::str_to_sq()`
+///
+/// `Square` is an integer, so `"e4".parse::<Square>()` parses a number, use this for square names instead
+pub fn parse_square(string: &str) -> Result<Square, ParseSquareError> {
+    let mut chars = string.chars();
+
+    let (Some(file), Some(rank), None) = (chars.next(), chars.next(), chars.next()) else {
+        return Err(ParseSquareError::Length);
+    };
+
+    if !('a'..='h').contains(&file) {
+        return Err(ParseSquareError::File(file));
+    }
+
+    if !('1'..='8').contains(&rank) {
+        return Err(ParseSquareError::Rank(rank));
+    }
+
+    Ok(make_sq(
+        (file as u8 - b'a') as File,
+        (rank as u8 - b'1') as Rank,
+    ))
 }
 
 /// converts a string literal in algebraic notation to a square index
+///
+/// the string is assumed to be a valid square, see `pos::parse_square()` for user input
 pub fn str_to_sq(string: &str) -> Square {
     debug_assert_eq!(
         string.len(),
@@ -864,7 +2838,7 @@ pub fn str_to_sq(string: &str) -> Square {
         string
     );
 
-    let file = (string.chars().nth(0).unwrap() as u8 - b'a') as File;
+    let file = (string.chars().next().unwrap() as u8 - b'a') as File;
     let rank = (string.chars().nth(1).unwrap() as u8 - b'1') as Rank;
 
     make_sq(file, rank)
@@ -899,4 +2873,218 @@ pub fn ahead(square: Square, color: color::Color) -> Square {
     }
 }
 
+/// checks a FEN string without parsing it into a position, returning every problem found,
+/// meant for FEN strings from users, which should be checked before `Position::from_fen()`
+///
+/// unlike `Position::validate()`, checks and the bitboards aren't looked at, since that needs a position
+pub fn validate_fen(fen: &str) -> Result<(), Vec<FenIssue>> {
+    const FIELDS: [&str; 6] = [
+        "board",
+        "side to move",
+        "castling rights",
+        "en passant square",
+        "halfmove clock",
+        "fullmove number",
+    ];
+
+    let mut issues = Vec::new();
+    let mut issue = |at, kind| issues.push(FenIssue { at, kind });
+
+    let mut fields = Vec::new();
+    let mut start = None;
+
+    for (i, ch) in fen.char_indices().chain([(fen.len(), ' ')]) {
+        match (ch.is_whitespace(), start) {
+            (true, Some(from)) => {
+                fields.push((from, &fen[from..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+
+    for field in FIELDS.iter().skip(fields.len()) {
+        issue(fen.len(), FenIssueKind::MissingField(field));
+    }
+
+    if let Some(&(at, _)) = fields.get(6) {
+        issue(at, FenIssueKind::TooManyFields);
+    }
+
+    // the board as characters, indexed by square, `None` if it isn't well formed
+    let board = fields.first().and_then(|&(at, field)| {
+        let mut board = [' '; 64];
+        let mut ok = true;
+        let ranks: Vec<(usize, &str)> = field
+            .split('/')
+            .scan(at, |offset, rank| {
+                let at = *offset;
+                *offset += rank.len() + 1;
+
+                Some((at, rank))
+            })
+            .collect();
+
+        if ranks.len() != 8 {
+            issue(at, FenIssueKind::RankCount(ranks.len()));
+            ok = false;
+        }
+
+        for (i, &(at, field)) in ranks.iter().enumerate() {
+            let rank = RANK_8 - i as Rank;
+            let mut file = 0;
+            let mut chars_ok = true;
+
+            for (j, ch) in field.char_indices() {
+                match ch {
+                    '1'..='8' => file += ch as u32 - '0' as u32,
+                    'p' | 'n' | 'b' | 'r' | 'q' | 'k' | 'P' | 'N' | 'B' | 'R' | 'Q' | 'K' => {
+                        if (RANK_1..=RANK_8).contains(&rank) && file < 8 {
+                            board[make_sq(file as File, rank)] = ch;
+                        }
+
+                        if matches!(ch, 'p' | 'P') && (rank == RANK_1 || rank == RANK_8) {
+                            issue(at + j, FenIssueKind::PawnOnBackRank);
+                        }
+
+                        file += 1;
+                    }
+                    _ => {
+                        issue(at + j, FenIssueKind::InvalidChar(ch));
+                        chars_ok = false;
+                    }
+                }
+            }
+
+            // the number of squares of a rank with invalid characters is unknown
+            if !chars_ok {
+                ok = false;
+            } else if file != 8 {
+                issue(at, FenIssueKind::SquareCount(rank, file));
+                ok = false;
+            }
+        }
+
+        for (side, king) in [(color::WHITE, 'K'), (color::BLACK, 'k')] {
+            let count = field.chars().filter(|&ch| ch == king).count() as u32;
+
+            if count != 1 {
+                issue(at, FenIssueKind::KingCount(side, count));
+            }
+        }
+
+        ok.then_some(board)
+    });
+
+    let side = fields.get(1).and_then(|&(at, field)| match field {
+        "w" => Some(color::WHITE),
+        "b" => Some(color::BLACK),
+        _ => {
+            issue(at, FenIssueKind::InvalidChar(field.chars().next().unwrap()));
+            None
+        }
+    });
+
+    if let Some(&(at, field)) = fields.get(2)
+        && field != "-"
+    {
+        let mut seen = Vec::new();
+
+        for (i, ch) in field.char_indices() {
+            if !matches!(ch, 'K' | 'Q' | 'k' | 'q' | 'A'..='H' | 'a'..='h') {
+                issue(at + i, FenIssueKind::InvalidChar(ch));
+                continue;
+            }
+
+            let same_side = seen
+                .iter()
+                .filter(|&&other: &&char| other.is_uppercase() == ch.is_uppercase())
+                .count();
+
+            if seen.contains(&ch) || same_side == 2 {
+                issue(at + i, FenIssueKind::DuplicateCastlingRight(ch));
+            }
+
+            seen.push(ch);
+
+            let Some(board) = board else {
+                continue;
+            };
+
+            let (king, rook, rank) = match ch.is_uppercase() {
+                true => ('K', 'R', RANK_1),
+                false => ('k', 'r', RANK_8),
+            };
+
+            let back_rank = |file: File| board[make_sq(file, rank)];
+            let Some(king_file) = (FILE_A..=FILE_H).find(|&file| back_rank(file) == king) else {
+                issue(at + i, FenIssueKind::CastlingWithoutKing(ch));
+                continue;
+            };
+
+            let has_rook = match ch.to_ascii_uppercase() {
+                'K' => (king_file + 1..=FILE_H).any(|file| back_rank(file) == rook),
+                'Q' => (FILE_A..king_file).any(|file| back_rank(file) == rook),
+                file => back_rank((file as u8 - b'A') as File) == rook,
+            };
+
+            if !has_rook {
+                issue(at + i, FenIssueKind::CastlingWithoutRook(ch));
+            }
+        }
+    }
+
+    if let Some(&(at, field)) = fields.get(3)
+        && field != "-"
+    {
+        match field.as_bytes() {
+            [b'a'..=b'h', b'1'..=b'8'] => {
+                let ep = str_to_sq(field);
+
+                // the pawn that skipped the square stands in front of it, and the square it came from is empty
+                let possible = match (side, board) {
+                    (Some(side), Some(board)) => {
+                        let (pawn, ep_rank) = match side {
+                            color::WHITE => ('p', RANK_6),
+                            _ => ('P', RANK_3),
+                        };
+
+                        rank_of(ep) == ep_rank
+                            && board[ep] == ' '
+                            && board[ahead(ep, side)] == ' '
+                            && board[behind(ep, side)] == pawn
+                    }
+                    _ => matches!(rank_of(ep), RANK_3 | RANK_6),
+                };
+
+                if !possible {
+                    issue(at, FenIssueKind::ImpossibleEnPassant);
+                }
+            }
+            _ => issue(at, FenIssueKind::InvalidEnPassant),
+        }
+    }
+
+    if let Some(&(at, field)) = fields.get(4)
+        && field.parse::<u8>().is_err()
+    {
+        issue(at, FenIssueKind::InvalidClock);
+    }
+
+    if let Some(&(at, field)) = fields.get(5)
+        && field
+            .parse::<u16>()
+            .ok()
+            .is_none_or(|fullmove| fullmove == 0)
+    {
+        issue(at, FenIssueKind::InvalidClock);
+    }
+
+    match issues.is_empty() {
+        true => Ok(()),
+        false => Err(issues),
+    }
+}
+
 // demon go get a job
@@ -0,0 +1,151 @@
+// file for reporting the progress and results of long running functions, e.g. perft suites,
+// either as colored text for people or as JSON lines for scripts
+
+#[cfg(feature = "json")]
+use std::io;
+
+#[cfg(feature = "render")]
+use colored::Colorize;
+
+/// something a long running function has to report
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+#[cfg_attr(feature = "json", serde(tag = "event", rename_all = "snake_case"))]
+pub enum Event<'a> {
+    /// a perft suite started testing a position
+    PerftPosition { fen: &'a str },
+    /// the result of a perft test of a single depth
+    PerftResult {
+        fen: &'a str,
+        depth: i32,
+        expected: i64,
+        actual: i64,
+    },
+    /// a perft suite finished, `passed + failed` is the number of depths tested
+    PerftSuiteFinished { passed: u32, failed: u32 },
+}
+
+/// receives the events of a long running function, see `report::TerminalReporter`
+/// and `report::JsonLinesReporter`
+///
+/// also implemented for closures, e.g. `&mut |event: &Event| events.push(*event)`
+pub trait Reporter {
+    fn report(&mut self, event: &Event);
+}
+
+impl<F: FnMut(&Event)> Reporter for F {
+    fn report(&mut self, event: &Event) {
+        self(event)
+    }
+}
+
+/// prints events as colored text to stdout
+#[cfg(feature = "render")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TerminalReporter;
+
+#[cfg(feature = "render")]
+impl Reporter for TerminalReporter {
+    fn report(&mut self, event: &Event) {
+        match *event {
+            Event::PerftPosition { fen } => println!("\ntesting position: {}", fen.bright_yellow()),
+            Event::PerftResult {
+                depth,
+                expected,
+                actual,
+                ..
+            } => {
+                print!(
+                    "depth: {}; expected nodes: {}; ",
+                    depth,
+                    expected.to_string().yellow()
+                );
+
+                if actual == expected {
+                    println!(
+                        "actual nodes: {}; {}",
+                        actual.to_string().yellow(),
+                        "ok".green()
+                    );
+                } else {
+                    println!(
+                        "actual nodes: {} ({}); {}",
+                        actual.to_string().red(),
+                        format!("{:+}", actual - expected).red(),
+                        "failed".red()
+                    );
+                }
+            }
+            Event::PerftSuiteFinished { passed, failed } => {
+                let all = passed + failed;
+
+                println!(
+                    "results: out of {} tests, {} passed, {} failed",
+                    all.to_string().yellow().bold(),
+                    if passed == all {
+                        passed.to_string().green().bold()
+                    } else {
+                        passed.to_string().yellow().bold()
+                    },
+                    if failed == 0 {
+                        failed.to_string().green().bold()
+                    } else {
+                        failed.to_string().red().bold()
+                    }
+                );
+            }
+        }
+    }
+}
+
+/// writes every event as a JSON object on its own line, with the kind of event in the `event` field,
+/// e.g. `{"event":"perft_suite_finished","passed":12,"failed":0}`
+///
+/// write errors don't stop the function being reported on, the first one is kept in `JsonLinesReporter::error`
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub struct JsonLinesReporter<W: io::Write> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+#[cfg(feature = "json")]
+impl<W: io::Write> JsonLinesReporter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesReporter {
+            writer,
+            error: None,
+        }
+    }
+
+    /// the first error that happened while writing, if any
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// returns the writer, or the first error that happened while writing
+    pub fn into_inner(self) -> io::Result<W> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.writer),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl<W: io::Write> Reporter for JsonLinesReporter<W> {
+    fn report(&mut self, event: &Event) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let result = serde_json::to_writer(&mut self.writer, event)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(self.writer))
+            .and_then(|_| self.writer.flush());
+
+        if let Err(err) = result {
+            self.error = Some(err);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// file for rule sets that restrict or relax standard chess, mostly used for teaching
+
+use crate::{moves, piece, pos};
+
+/// a set of rules that changes which moves are generated by `moves::gen_legal_with()`,
+/// along with the position a game under these rules starts from
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Rules {
+    /// FEN string of the position a game under these rules starts from
+    pub start_fen: &'static str,
+    /// whether castling moves are generated
+    pub castling: bool,
+    /// whether moves that leave the king in check are filtered out,
+    ///
+    /// if this is false, all pseudo legal moves are generated, which also means positions
+    /// without kings can be played
+    pub check_rules: bool,
+    /// the piece types pawns can promote to, as a set of piece flags (e.g. `moves::ALL_PROMOTIONS`),
+    ///
+    /// may include `piece::KING` for variants like antichess, see `moves::ANTICHESS_PROMOTIONS`
+    pub promotions: piece::Piece,
+}
+
+/// FEN string for the "pawn game", where each side only has its pawns
+pub const PAWNS_ONLY_FEN: &str = "8/pppppppp/8/8/8/8/PPPPPPPP/8 w - - 0 1";
+
+/// FEN string for the "pawn game" with both kings added
+pub const PAWNS_AND_KINGS_FEN: &str = "4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1";
+
+/// FEN string for the starting position without any castling rights
+pub const NO_CASTLING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1";
+
+/// standard chess
+pub const STANDARD: Rules = Rules {
+    start_fen: pos::START_FEN,
+    castling: true,
+    check_rules: true,
+    promotions: moves::ALL_PROMOTIONS,
+};
+
+/// only pawns on the board, there are no kings so check rules don't apply
+pub const PAWNS_ONLY: Rules = Rules {
+    start_fen: PAWNS_ONLY_FEN,
+    castling: false,
+    check_rules: false,
+    promotions: moves::ALL_PROMOTIONS,
+};
+
+/// pawns and kings only, played with normal check rules
+pub const PAWNS_AND_KINGS: Rules = Rules {
+    start_fen: PAWNS_AND_KINGS_FEN,
+    castling: false,
+    check_rules: true,
+    promotions: moves::ALL_PROMOTIONS,
+};
+
+/// standard chess, but castling is never allowed
+pub const NO_CASTLING: Rules = Rules {
+    start_fen: NO_CASTLING_FEN,
+    castling: false,
+    check_rules: true,
+    promotions: moves::ALL_PROMOTIONS,
+};
+
+/// standard chess, but moves that leave the king in check are allowed,
+/// which means kings can be captured
+pub const NO_CHECKS: Rules = Rules {
+    start_fen: pos::START_FEN,
+    castling: true,
+    check_rules: false,
+    promotions: moves::ALL_PROMOTIONS,
+};
+
+/// standard chess, but pawns can only promote to a queen,
+/// so beginners don't have to think about underpromotions
+pub const QUEEN_PROMOTIONS_ONLY: Rules = Rules {
+    start_fen: pos::START_FEN,
+    castling: true,
+    check_rules: true,
+    promotions: moves::QUEEN_PROMOTIONS,
+};
+
+impl Default for Rules {
+    fn default() -> Self {
+        STANDARD
+    }
+}
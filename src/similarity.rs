@@ -0,0 +1,138 @@
+// file for comparing positions by structure, e.g. for finding games with similar pawn structures
+
+use crate::{
+    color,
+    piece::{self, bb},
+    pos,
+};
+
+/// how much a differing pawn counts compared to a differing piece in `Distance::total()`
+pub const PAWN_WEIGHT: u32 = 2;
+
+/// the structural difference between two positions,
+/// every field is 0 if both positions have the same piece placement
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct Distance {
+    /// number of squares where one position has a pawn the other doesn't (hamming distance)
+    pub pawns: u32,
+    /// number of squares where one position has a non-pawn piece the other doesn't
+    pub pieces: u32,
+    /// absolute difference of the material balance of both positions, in pawns
+    pub material: u32,
+}
+
+impl Distance {
+    /// combines all fields into a single value, lower values mean more similar positions
+    #[inline(always)]
+    pub fn total(&self) -> u32 {
+        self.pawns * PAWN_WEIGHT + self.pieces + self.material
+    }
+}
+
+/// the parts of a position needed to compare it to others,
+/// much smaller than a `Position` as it has no history or board array
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Signature {
+    piece_bb: [bb::Bitboard; 12],
+    material_diff: i32,
+}
+
+impl Signature {
+    pub fn of(pos: &pos::Position) -> Self {
+        let mut piece_bb = [bb::EMPTY; 12];
+
+        for color in [color::WHITE, color::BLACK] {
+            for piece in [
+                piece::PAWN,
+                piece::KNIGHT,
+                piece::BISHOP,
+                piece::ROOK,
+                piece::QUEEN,
+                piece::KING,
+            ] {
+                piece_bb[bb::p_to_idx(piece | color)] = pos.piece_bb(piece | color);
+            }
+        }
+
+        Signature {
+            piece_bb,
+            material_diff: pos.material_diff(),
+        }
+    }
+
+    /// returns the structural difference between two signatures
+    pub fn distance(&self, other: &Signature) -> Distance {
+        let mut distance = Distance {
+            material: self.material_diff.abs_diff(other.material_diff),
+            ..Default::default()
+        };
+
+        for (i, (&a, &b)) in self.piece_bb.iter().zip(other.piece_bb.iter()).enumerate() {
+            // pawns are at index 0 for white and 6 for black
+            if i % 6 == 0 {
+                distance.pawns += (a ^ b).count_ones();
+            } else {
+                distance.pieces += (a ^ b).count_ones();
+            }
+        }
+
+        distance
+    }
+}
+
+/// returns the structural difference between two positions
+pub fn distance(a: &pos::Position, b: &pos::Position) -> Distance {
+    Signature::of(a).distance(&Signature::of(b))
+}
+
+/// a collection of position signatures that can be searched for positions similar to a given one,
+///
+/// positions are identified by the index they were added at
+#[derive(Debug, Clone, Default)]
+pub struct SimilarityIndex {
+    entries: Vec<Signature>,
+}
+
+impl SimilarityIndex {
+    pub fn new() -> Self {
+        SimilarityIndex {
+            entries: Vec::new(),
+        }
+    }
+
+    /// adds a position to the index, and returns the index it was added at
+    pub fn push(&mut self, pos: &pos::Position) -> usize {
+        self.entries.push(Signature::of(pos));
+        self.entries.len() - 1
+    }
+
+    /// the number of positions in the index
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// returns true if no positions have been added
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// returns up to `max_results` positions most similar to `pos`, as pairs of the index
+    /// the position was added at and its distance to `pos`, sorted from most to least similar
+    pub fn find_similar(&self, pos: &pos::Position, max_results: usize) -> Vec<(usize, Distance)> {
+        let sig = Signature::of(pos);
+
+        let mut results = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (i, sig.distance(entry)))
+            .collect::<Vec<(usize, Distance)>>();
+
+        results.sort_by_key(|&(i, distance)| (distance.total(), i));
+        results.truncate(max_results);
+
+        results
+    }
+}
@@ -0,0 +1,128 @@
+// file for tables holding a value for every square of the board, e.g. piece square tables or heatmaps
+
+use std::{
+    fmt,
+    ops::{Index, IndexMut},
+};
+
+use crate::pos;
+
+/// a value for each of the 64 squares, indexed by `pos::Square`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SquareTable<T> {
+    values: [T; 64],
+}
+
+impl<T: Copy> SquareTable<T> {
+    /// creates a table with `value` on every square
+    pub fn new(value: T) -> Self {
+        SquareTable {
+            values: [value; 64],
+        }
+    }
+
+    /// returns the table flipped vertically, so a table written from white's point of view
+    /// can be used for black, e.g. `table.mirrored()[sq] == table[sq ^ 56]`
+    pub fn mirrored(&self) -> Self {
+        SquareTable {
+            values: std::array::from_fn(|square| self.values[square ^ 56]),
+        }
+    }
+}
+
+impl<T> SquareTable<T> {
+    /// creates a table from an array indexed by square, so the first 8 values are rank 1
+    pub const fn from_array(values: [T; 64]) -> Self {
+        SquareTable { values }
+    }
+
+    /// creates a table from a function called with every square
+    pub fn from_fn(f: impl FnMut(pos::Square) -> T) -> Self {
+        SquareTable {
+            values: std::array::from_fn(f),
+        }
+    }
+
+    /// the values of all squares, indexed by square
+    #[inline(always)]
+    pub fn as_array(&self) -> &[T; 64] {
+        &self.values
+    }
+
+    /// the values of a single rank, from the a file to the h file
+    #[inline(always)]
+    pub fn rank(&self, rank: pos::Rank) -> &[T; 8] {
+        self.values[rank as usize * 8..][..8].try_into().unwrap()
+    }
+
+    /// iterates over the ranks from rank 1 to rank 8, see `SquareTable::rank()`
+    pub fn ranks(&self) -> impl DoubleEndedIterator<Item = &[T; 8]> {
+        (pos::RANK_1..=pos::RANK_8).map(|rank| self.rank(rank))
+    }
+
+    /// iterates over all squares and their values, from a1 to h8
+    pub fn iter(&self) -> impl Iterator<Item = (pos::Square, &T)> {
+        self.values.iter().enumerate()
+    }
+}
+
+impl<T: Default + Copy> Default for SquareTable<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> Index<pos::Square> for SquareTable<T> {
+    type Output = T;
+
+    #[inline(always)]
+    fn index(&self, square: pos::Square) -> &T {
+        &self.values[square]
+    }
+}
+
+impl<T> IndexMut<pos::Square> for SquareTable<T> {
+    #[inline(always)]
+    fn index_mut(&mut self, square: pos::Square) -> &mut T {
+        &mut self.values[square]
+    }
+}
+
+/// prints the table as a board with rank 8 at the top, all values right aligned to the widest one,
+/// the precision is passed on to the values, e.g. `format!("{table:.2}")`
+impl<T: fmt::Display> fmt::Display for SquareTable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cells = self
+            .values
+            .iter()
+            .map(|value| match f.precision() {
+                Some(precision) => format!("{value:.precision$}"),
+                None => value.to_string(),
+            })
+            .collect::<Vec<String>>();
+
+        let width = cells
+            .iter()
+            .map(|cell| cell.chars().count())
+            .max()
+            .unwrap_or(1);
+
+        for rank in (pos::RANK_1..=pos::RANK_8).rev() {
+            write!(f, "{} ", rank + 1)?;
+
+            for file in pos::FILE_A..=pos::FILE_H {
+                write!(f, " {:>width$}", cells[pos::make_sq(file, rank)])?;
+            }
+
+            writeln!(f)?;
+        }
+
+        write!(f, " ")?;
+
+        for file in "abcdefgh".chars() {
+            write!(f, " {file:>width$}")?;
+        }
+
+        writeln!(f)
+    }
+}
@@ -0,0 +1,94 @@
+// file for the reasons a game can end, shared by everything that needs to record or report them
+
+use std::fmt;
+
+/// why a game ended
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Termination {
+    /// the side to move is checkmated
+    Checkmate,
+    /// the side to move has no legal moves and isn't in check
+    Stalemate,
+    /// neither side has enough material to checkmate
+    InsufficientMaterial,
+    /// the same position occurred three times
+    Repetition,
+    /// 50 moves were played by each side without a capture or pawn move
+    FiftyMoveRule,
+    /// a player resigned
+    Resignation,
+    /// a player ran out of time
+    TimeForfeit,
+    /// a player was declared lost by an adjudicator, e.g. because of a hopeless score
+    AdjudicatedResign,
+    /// the game was declared drawn by an adjudicator, e.g. with `adjudication::likely_draw()`
+    AdjudicatedDraw,
+    /// the game was adjudicated with the result of a tablebase probe
+    AdjudicatedTablebase,
+    /// both players agreed to a draw
+    Agreement,
+    /// a player (or engine) stopped responding or left the game
+    Abandonment,
+    /// a player broke the rules, e.g. an engine sent an illegal move
+    RulesInfraction,
+    /// the game was won by a rule that only exists in the variant being played
+    VariantWin,
+}
+
+impl Termination {
+    /// value of the PGN `Termination` tag for this reason,
+    /// which only distinguishes games that ended by the rules from the other few cases
+    pub fn pgn_tag(&self) -> &'static str {
+        match self {
+            Termination::Checkmate
+            | Termination::Stalemate
+            | Termination::InsufficientMaterial
+            | Termination::Repetition
+            | Termination::FiftyMoveRule
+            | Termination::Resignation
+            | Termination::Agreement
+            | Termination::VariantWin => "normal",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::AdjudicatedResign
+            | Termination::AdjudicatedDraw
+            | Termination::AdjudicatedTablebase => "adjudication",
+            Termination::Abandonment => "abandoned",
+            Termination::RulesInfraction => "rules infraction",
+        }
+    }
+
+    /// returns true if the game ended because of the rules of chess (or the variant),
+    /// and not because of a player's or an adjudicator's decision
+    pub fn is_by_rules(&self) -> bool {
+        matches!(
+            self,
+            Termination::Checkmate
+                | Termination::Stalemate
+                | Termination::InsufficientMaterial
+                | Termination::Repetition
+                | Termination::FiftyMoveRule
+                | Termination::VariantWin
+        )
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::InsufficientMaterial => "insufficient material",
+            Termination::Repetition => "threefold repetition",
+            Termination::FiftyMoveRule => "50 move rule",
+            Termination::Resignation => "resignation",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::AdjudicatedResign => "adjudicated loss",
+            Termination::AdjudicatedDraw => "adjudicated draw",
+            Termination::AdjudicatedTablebase => "adjudicated by tablebase",
+            Termination::Agreement => "draw by agreement",
+            Termination::Abandonment => "abandoned",
+            Termination::RulesInfraction => "rules infraction",
+            Termination::VariantWin => "variant win",
+        })
+    }
+}
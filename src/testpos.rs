@@ -0,0 +1,323 @@
+// file for well known test positions with their perft results, for regression tests of move generation
+// and anything else that needs positions with known properties, without shipping EPD files
+//
+// the perft results are from https://www.chessprogramming.org/Perft_Results
+// and Martin Sedlak's collection of tricky positions, extended to lower depths with `perft::perft()`
+
+use crate::{ZobristValues, color, pos};
+
+/// a position with known facts about it
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TestPosition {
+    pub name: &'static str,
+    pub fen: &'static str,
+    /// the number of leaf nodes at depth 1, 2, 3..., see `perft::perft()`
+    pub perft: &'static [u64],
+    /// whether the side to move is in check
+    pub in_check: bool,
+    /// how the game ended in this position, `None` if it hasn't
+    pub outcome: Option<pos::Outcome>,
+}
+
+impl TestPosition {
+    /// creates the position from `TestPosition::fen`
+    pub fn position(&self, zb: &ZobristValues) -> pos::Position {
+        pos::Position::from_fen(self.fen, zb)
+    }
+}
+
+/// the standard starting position
+pub const fn start() -> TestPosition {
+    TestPosition {
+        name: "start",
+        fen: pos::START_FEN,
+        perft: &[20, 400, 8902, 197281, 4865609, 119060324],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// "position 2", full of castling, en passant, promotion and pin edge cases
+pub const fn kiwipete() -> TestPosition {
+    TestPosition {
+        name: "kiwipete",
+        fen: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        perft: &[48, 2039, 97862, 4085603, 193690690],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// rook and pawn endgame with en passant captures that expose the king
+pub const fn position_3() -> TestPosition {
+    TestPosition {
+        name: "position 3",
+        fen: "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        perft: &[14, 191, 2812, 43238, 674624, 11030083],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// promotions, castling through attacks and pins, with white in check
+pub const fn position_4() -> TestPosition {
+    TestPosition {
+        name: "position 4",
+        fen: "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        perft: &[6, 264, 9467, 422333, 15833292],
+        in_check: true,
+        outcome: None,
+    }
+}
+
+/// `position_4()` with colors swapped, the perft results are the same
+pub const fn position_4_mirrored() -> TestPosition {
+    TestPosition {
+        name: "position 4 mirrored",
+        fen: "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        perft: &[6, 264, 9467, 422333, 15833292],
+        in_check: true,
+        outcome: None,
+    }
+}
+
+/// a pawn on the 7th rank that can capture and promote, known to catch many bugs
+pub const fn position_5() -> TestPosition {
+    TestPosition {
+        name: "position 5",
+        fen: "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        perft: &[44, 1486, 62379, 2103487, 89941194],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// a quiet, symmetrical middlegame position
+pub const fn position_6() -> TestPosition {
+    TestPosition {
+        name: "position 6",
+        fen: "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        perft: &[46, 2079, 89890, 3894594],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// after d7-d5, white can't capture en passant because the rook on h5 would attack the king along the rank
+pub const fn illegal_ep_rank_pin() -> TestPosition {
+    TestPosition {
+        name: "illegal en passant, rank pin",
+        fen: "3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1",
+        perft: &[18, 92, 1670, 10138, 185429, 1134888, 20757544],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// after d2-d4, black can't capture en passant because the pawn on c4 is pinned by the bishop on a2
+pub const fn illegal_ep_diagonal_pin() -> TestPosition {
+    TestPosition {
+        name: "illegal en passant, diagonal pin",
+        fen: "8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1",
+        perft: &[13, 102, 1266, 10276, 135655, 1015133, 14047573],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// capturing en passant gives check with the bishop behind the captured pawn
+pub const fn ep_discovered_check() -> TestPosition {
+    TestPosition {
+        name: "en passant discovered check",
+        fen: "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        perft: &[15, 126, 1928, 13931, 206379, 1440467, 21190412],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// castling king side gives check with the rook
+pub const fn short_castling_check() -> TestPosition {
+    TestPosition {
+        name: "short castling gives check",
+        fen: "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+        perft: &[15, 66, 1198, 6399, 120330, 661072, 12762196],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// castling queen side gives check with the rook
+pub const fn long_castling_check() -> TestPosition {
+    TestPosition {
+        name: "long castling gives check",
+        fen: "3k4/8/8/8/8/8/8/R3K3 w Q - 0 1",
+        perft: &[16, 71, 1286, 7418, 141077, 803711, 15594314],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// castling rights are lost when rooks are captured by the bishops
+pub const fn castling_rights_lost() -> TestPosition {
+    TestPosition {
+        name: "castling rights lost",
+        fen: "r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1",
+        perft: &[26, 1141, 27826, 1274206, 31912360],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// castling is prevented by the queens attacking the squares the kings pass
+pub const fn castling_prevented() -> TestPosition {
+    TestPosition {
+        name: "castling prevented",
+        fen: "r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1",
+        perft: &[44, 1494, 50509, 1720476, 58773923],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// the king is in check and a promotion blocks it
+pub const fn promote_out_of_check() -> TestPosition {
+    TestPosition {
+        name: "promote out of check",
+        fen: "2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1",
+        perft: &[11, 133, 1442, 19174, 266199, 3821001],
+        in_check: true,
+        outcome: None,
+    }
+}
+
+/// moving the knight gives a discovered check with the queen
+pub const fn discovered_check() -> TestPosition {
+    TestPosition {
+        name: "discovered check",
+        fen: "8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1",
+        perft: &[29, 165, 5160, 31961, 1004658, 6334638],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// promoting to a queen or rook gives check
+pub const fn promote_to_give_check() -> TestPosition {
+    TestPosition {
+        name: "promote to give check",
+        fen: "4k3/1P6/8/8/8/8/K7/8 w - - 0 1",
+        perft: &[9, 40, 472, 2661, 38983, 217342, 3742283],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// only underpromoting to a knight gives check
+pub const fn underpromote_to_check() -> TestPosition {
+    TestPosition {
+        name: "underpromote to check",
+        fen: "8/P1k5/K7/8/8/8/8/8 w - - 0 1",
+        perft: &[6, 27, 273, 1329, 18135, 92683, 1555980],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// white stalemates itself after a few moves
+pub const fn self_stalemate() -> TestPosition {
+    TestPosition {
+        name: "self stalemate",
+        fen: "K1k5/8/P7/8/8/8/8/8 w - - 0 1",
+        perft: &[2, 6, 13, 63, 382, 2217, 15453],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// promoting to a queen or rook stalemates, other lines lead to mate
+pub const fn stalemate_and_checkmate() -> TestPosition {
+    TestPosition {
+        name: "stalemate and checkmate",
+        fen: "8/k1P5/8/1K6/8/8/8/8 w - - 0 1",
+        perft: &[10, 25, 268, 926, 10857, 43261, 567584],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// black has lines that stalemate and lines that checkmate the lone king
+pub const fn stalemate_and_checkmate_2() -> TestPosition {
+    TestPosition {
+        name: "stalemate and checkmate 2",
+        fen: "8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1",
+        perft: &[37, 183, 6559, 23527, 811573, 3114998],
+        in_check: false,
+        outcome: None,
+    }
+}
+
+/// black has no legal moves but isn't in check
+pub const fn stalemate() -> TestPosition {
+    TestPosition {
+        name: "stalemate",
+        fen: "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+        perft: &[0],
+        in_check: false,
+        outcome: Some(pos::Outcome::Stalemate),
+    }
+}
+
+/// the fastest possible checkmate, 1. f3 e5 2. g4 Qh4#
+pub const fn fools_mate() -> TestPosition {
+    TestPosition {
+        name: "fool's mate",
+        fen: "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+        perft: &[0],
+        in_check: true,
+        outcome: Some(pos::Outcome::Checkmate {
+            winner: color::BLACK,
+        }),
+    }
+}
+
+/// king and knight against king, a draw even though there are legal moves
+pub const fn insufficient_material() -> TestPosition {
+    TestPosition {
+        name: "insufficient material",
+        fen: "8/8/4k3/8/8/3NK3/8/8 w - - 0 1",
+        perft: &[15, 104, 1407, 9498, 125273, 813611, 10461077],
+        in_check: false,
+        outcome: Some(pos::Outcome::InsufficientMaterial),
+    }
+}
+
+/// every position in this file
+pub const fn all() -> [TestPosition; 24] {
+    [
+        start(),
+        kiwipete(),
+        position_3(),
+        position_4(),
+        position_4_mirrored(),
+        position_5(),
+        position_6(),
+        illegal_ep_rank_pin(),
+        illegal_ep_diagonal_pin(),
+        ep_discovered_check(),
+        short_castling_check(),
+        long_castling_check(),
+        castling_rights_lost(),
+        castling_prevented(),
+        promote_out_of_check(),
+        discovered_check(),
+        promote_to_give_check(),
+        underpromote_to_check(),
+        self_stalemate(),
+        stalemate_and_checkmate(),
+        stalemate_and_checkmate_2(),
+        stalemate(),
+        fools_mate(),
+        insufficient_material(),
+    ]
+}
@@ -1,6 +1,18 @@
+pub mod go;
+pub mod option;
+pub mod pool;
+pub mod score;
+pub mod server;
+pub mod strength;
+pub mod transcript;
+
 use std::{
+    fmt,
     io::{self, BufRead, BufReader, Write},
-    process::{Child, ChildStdout, Command, Stdio},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
 };
 
 use crate::{moves, pos};
@@ -12,61 +24,457 @@ pub const IS_READY: &str = "isready";
 pub const READY_OK: &str = "readyok";
 pub const BEST_MOVE: &str = "bestmove";
 pub const STOP: &str = "stop";
+pub const QUIT: &str = "quit";
+
+/// how long a dropped `Engine` gets to exit after `quit` before it's killed
+pub const DROP_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// how long a restarted engine gets to answer `uci` and `isready`
+pub const RESTART_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// error returned by the methods of `Engine` that talk to the engine
+#[derive(Debug)]
+pub enum EngineError {
+    /// communicating with the engine failed, e.g. a read timed out
+    Io(io::Error),
+    /// the engine exited without being asked to quit, or closed its output,
+    /// `restarted` is true if it was restarted by its `RestartPolicy`
+    Crashed {
+        status: Option<ExitStatus>,
+        restarted: bool,
+    },
+    /// `Engine::set_option()` was called with an unknown option or a value that doesn't fit the option
+    Option(option::OptionError),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Io(err) => write!(f, "{err}"),
+            EngineError::Crashed { status, restarted } => {
+                match status {
+                    Some(status) => write!(f, "engine crashed ({status})")?,
+                    None => write!(f, "engine crashed")?,
+                }
+
+                match restarted {
+                    true => write!(f, ", restarted it"),
+                    false => Ok(()),
+                }
+            }
+            EngineError::Option(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EngineError::Io(err) => Some(err),
+            EngineError::Option(err) => Some(err),
+            EngineError::Crashed { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for EngineError {
+    fn from(err: io::Error) -> Self {
+        EngineError::Io(err)
+    }
+}
+
+impl From<EngineError> for io::Error {
+    fn from(err: EngineError) -> Self {
+        match err {
+            EngineError::Io(err) => err,
+            EngineError::Crashed { .. } => io::Error::new(io::ErrorKind::BrokenPipe, err),
+            EngineError::Option(_) => io::Error::new(io::ErrorKind::InvalidInput, err),
+        }
+    }
+}
+
+/// what an `Engine` does when its process crashes
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum RestartPolicy {
+    /// leave the engine dead, every later call fails
+    #[default]
+    Never,
+    /// start the engine again, at most `max_restarts` times, and send it the options
+    /// and the position it had before the crash
+    Restart { max_restarts: u32 },
+}
+
+/// a function called with every line sent to or received from an engine, see `Engine::on_send()`
+type Hook = Box<dyn FnMut(&transcript::Entry) + Send>;
 
 /// struct for communicating with UCI engines from a gui
 pub struct Engine {
+    path: String,
     exe: Child,
-    stdout_reader: BufReader<ChildStdout>,
+    started: Instant,
+    /// lines read from the engine's stdout on another thread, so reads can time out
+    lines: mpsc::Receiver<io::Result<String>>,
     buf: String,
+    recording: Option<(Instant, transcript::Transcript)>,
+    options: Vec<option::EngineOption>,
+    /// whether the engine was told to quit, so its output ending isn't a crash
+    quitting: bool,
+    restart_policy: RestartPolicy,
+    restarts: u32,
+    /// the last `setoption` command of every option and the last `position` command,
+    /// sent again after a restart
+    sent_options: Vec<(String, String)>,
+    sent_position: Option<String>,
+    hooks: Vec<Hook>,
 }
 
 impl Engine {
     pub fn new(path: &str) -> io::Result<Self> {
-        let mut exe = Command::new(path)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()?;
-
-        let stdout_reader = BufReader::new(exe.stdout.take().unwrap());
+        let (exe, lines) = spawn(path)?;
 
         Ok(Engine {
+            path: path.to_string(),
             exe,
-            stdout_reader,
+            started: Instant::now(),
+            lines,
             buf: String::new(),
+            recording: None,
+            options: Vec::new(),
+            quitting: false,
+            restart_policy: RestartPolicy::Never,
+            restarts: 0,
+            sent_options: Vec::new(),
+            sent_position: None,
+            hooks: Vec::new(),
         })
     }
 
-    /// send a command to an engine
+    /// sets what happens when the engine crashes, `RestartPolicy::Never` by default
+    pub fn set_restart_policy(&mut self, policy: RestartPolicy) {
+        self.restart_policy = policy;
+    }
+
+    /// how many times the engine was restarted after crashing
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// send a command to an engine, fails with `BrokenPipe` after `Engine::quit()`
     #[inline(always)]
-    pub fn send(&mut self, cmd: &str) -> io::Result<()> {
-        self.exe
+    pub fn send(&mut self, cmd: &str) -> Result<(), EngineError> {
+        let stdin = self
+            .exe
             .stdin
             .as_mut()
-            .unwrap()
-            .write(format!("{cmd}\n").as_bytes())?;
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "engine has quit"))?;
+
+        if let Err(err) = stdin.write_all(format!("{cmd}\n").as_bytes()) {
+            return match err.kind() {
+                io::ErrorKind::BrokenPipe if !self.quitting => Err(self.crashed()),
+                _ => Err(err.into()),
+            };
+        }
+
+        let cmd = cmd.trim();
+
+        match cmd.split_whitespace().next() {
+            // the engine declares its options again after every `uci`
+            Some(UCI) => self.options.clear(),
+            Some(QUIT) => self.quitting = true,
+            Some("setoption") => {
+                let name = cmd.split(" value ").next().unwrap_or(cmd);
+                let name = name.trim_start_matches("setoption").trim();
+                let name = name.trim_start_matches("name").trim().to_lowercase();
+
+                match self.sent_options.iter_mut().find(|(n, _)| *n == name) {
+                    Some((_, sent)) => *sent = cmd.to_string(),
+                    None => self.sent_options.push((name, cmd.to_string())),
+                }
+            }
+            Some("position") => self.sent_position = Some(cmd.to_string()),
+            _ => {}
+        }
+
+        if let Some((start, transcript)) = &mut self.recording {
+            transcript.push(start.elapsed(), transcript::Direction::Sent, cmd);
+        }
+
+        self.run_hooks(transcript::Direction::Sent, cmd);
 
         Ok(())
     }
 
-    /// returns the next line of output from an engine
+    /// returns the next line of output from an engine,
+    /// an empty string once the engine closed its output after being told to quit
     #[inline(always)]
-    pub fn get_next(&mut self) -> io::Result<&str> {
-        self.buf.clear();
+    pub fn get_next(&mut self) -> Result<&str, EngineError> {
+        match self.lines.recv() {
+            Ok(line) => self.receive(line),
+            Err(_) => self.closed(),
+        }
+    }
+
+    /// like `Engine::get_next()`, but gives up with an error of kind `TimedOut`
+    /// if the engine doesn't send a line within `timeout`
+    pub fn get_next_timeout(&mut self, timeout: Duration) -> Result<&str, EngineError> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => self.receive(line),
+            Err(RecvTimeoutError::Disconnected) => self.closed(),
+            Err(RecvTimeoutError::Timeout) => Err(EngineError::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("engine sent nothing for {}ms", timeout.as_millis()),
+            ))),
+        }
+    }
+
+    /// reads lines until one starting with `token`, e.g. `uci::READY_OK`, and returns it
+    ///
+    /// gives up with an error of kind `TimedOut` if no such line arrives within `timeout`,
+    /// or `UnexpectedEof` if the engine exits first
+    pub fn wait_for(&mut self, token: &str, timeout: Duration) -> Result<&str, EngineError> {
+        let deadline = Instant::now() + timeout;
 
-        self.stdout_reader.read_line(&mut self.buf)?;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let line = self.get_next_timeout(left).map_err(|err| match err {
+                EngineError::Io(err) if err.kind() == io::ErrorKind::TimedOut => {
+                    EngineError::Io(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("no '{token}' from engine within {}ms", timeout.as_millis()),
+                    ))
+                }
+                _ => err,
+            })?;
+
+            if line.is_empty() {
+                return Err(EngineError::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("engine exited before sending '{token}'"),
+                )));
+            }
+
+            if line.split_whitespace().next() == Some(token) {
+                return Ok(&self.buf);
+            }
+        }
+    }
+
+    /// stores a line received from the engine
+    fn receive(&mut self, line: io::Result<String>) -> Result<&str, EngineError> {
+        self.buf = line?;
+
+        if let Some((start, transcript)) = &mut self.recording {
+            transcript.push(start.elapsed(), transcript::Direction::Received, &self.buf);
+        }
+
+        let line = std::mem::take(&mut self.buf);
+        self.run_hooks(transcript::Direction::Received, &line);
+        self.buf = line;
+
+        if let Some(option) = option::EngineOption::parse(&self.buf) {
+            self.options.push(option);
+        }
 
         Ok(&self.buf)
     }
 
+    /// calls the hooks with a line, timed from when the engine was started
+    fn run_hooks(&mut self, direction: transcript::Direction, line: &str) {
+        if self.hooks.is_empty() {
+            return;
+        }
+
+        let entry = transcript::Entry {
+            elapsed: self.started.elapsed(),
+            direction,
+            line: line.trim_end_matches(['\n', '\r']).to_string(),
+        };
+
+        for hook in &mut self.hooks {
+            hook(&entry);
+        }
+    }
+
+    /// the engine closed its output, which is only expected after it was told to quit
+    fn closed(&mut self) -> Result<&str, EngineError> {
+        if !self.quitting {
+            return Err(self.crashed());
+        }
+
+        self.buf.clear();
+
+        Ok(&self.buf)
+    }
+
+    /// the error for a crash, restarting the engine first if the `RestartPolicy` allows it
+    fn crashed(&mut self) -> EngineError {
+        // the engine's output can close a moment before the process exits
+        let deadline = Instant::now() + Duration::from_millis(100);
+        let mut status = None;
+
+        while status.is_none() && Instant::now() < deadline {
+            status = self.exe.try_wait().ok().flatten();
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let restarted = match self.restart_policy {
+            RestartPolicy::Restart { max_restarts } if self.restarts < max_restarts => {
+                self.restarts += 1;
+                self.restart().is_ok()
+            }
+            _ => false,
+        };
+
+        EngineError::Crashed { status, restarted }
+    }
+
+    /// starts the engine again and sends it the options and position it had before
+    fn restart(&mut self) -> Result<(), EngineError> {
+        let _ = self.exe.kill();
+        let _ = self.exe.wait();
+
+        (self.exe, self.lines) = spawn(&self.path)?;
+        self.quitting = false;
+
+        // a crash while restarting isn't restarted again
+        let policy = std::mem::take(&mut self.restart_policy);
+        let result = self.reapply();
+        self.restart_policy = policy;
+
+        result
+    }
+
+    fn reapply(&mut self) -> Result<(), EngineError> {
+        self.send(UCI)?;
+        self.wait_for(UCI_OK, RESTART_TIMEOUT)?;
+
+        let sent_options = self.sent_options.clone();
+
+        for (_, cmd) in sent_options {
+            self.send(&cmd)?;
+        }
+
+        if let Some(position) = self.sent_position.clone() {
+            self.send(&position)?;
+        }
+
+        self.send(IS_READY)?;
+        self.wait_for(READY_OK, RESTART_TIMEOUT)?;
+
+        Ok(())
+    }
+
+    /// the options the engine declared after the last `uci` command,
+    /// recorded while reading the engine's output, so they're complete once `uciok` is received
+    pub fn options(&self) -> &[option::EngineOption] {
+        &self.options
+    }
+
+    /// sends `setoption` for one of the options the engine declared, after checking `value`
+    /// against the option's type and bounds
+    pub fn set_option(
+        &mut self,
+        name: &str,
+        value: &option::OptionValue,
+    ) -> Result<(), EngineError> {
+        let option = self
+            .options
+            .iter()
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| option::OptionError::Unknown(name.to_string()))
+            .map_err(EngineError::Option)?;
+
+        let cmd = match option.validate(value).map_err(EngineError::Option)? {
+            option::OptionValue::Button => format!("setoption name {}", option.name),
+            value => format!("setoption name {} value {value}", option.name),
+        };
+
+        self.send(&cmd)
+    }
+
+    /// sends `stop` and `quit` and closes the engine's input, then waits for the engine to exit,
+    /// killing it if it's still running after `timeout`
+    ///
+    /// returns the engine's exit status, calling it again after the engine exited returns the same status
+    pub fn quit(&mut self, timeout: Duration) -> io::Result<ExitStatus> {
+        self.quitting = true;
+
+        // the engine may have exited already, in which case its input is closed
+        let _ = self.send(STOP);
+        let _ = self.send(QUIT);
+        drop(self.exe.stdin.take());
+
+        let deadline = Instant::now() + timeout;
+
+        while Instant::now() < deadline {
+            if let Some(status) = self.exe.try_wait()? {
+                return Ok(status);
+            }
+
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        match self.exe.try_wait()? {
+            Some(status) => Ok(status),
+            None => {
+                self.exe.kill()?;
+                self.exe.wait()
+            }
+        }
+    }
+
+    /// calls `hook` with every command sent to the engine, and the time since the engine was started
+    pub fn on_send(&mut self, mut hook: impl FnMut(Duration, &str) + Send + 'static) {
+        self.hooks.push(Box::new(move |entry| {
+            if entry.direction == transcript::Direction::Sent {
+                hook(entry.elapsed, &entry.line);
+            }
+        }));
+    }
+
+    /// calls `hook` with every line received from the engine, without the newline,
+    /// and the time since the engine was started
+    pub fn on_receive(&mut self, mut hook: impl FnMut(Duration, &str) + Send + 'static) {
+        self.hooks.push(Box::new(move |entry| {
+            if entry.direction == transcript::Direction::Received {
+                hook(entry.elapsed, &entry.line);
+            }
+        }));
+    }
+
+    /// writes every line sent to and received from the engine to `log`, in the format of a `Transcript`,
+    /// so the log can be parsed and replayed later
+    ///
+    /// errors writing to `log` are ignored
+    pub fn log_to(&mut self, mut log: impl Write + Send + 'static) {
+        self.hooks.push(Box::new(move |entry| {
+            let _ = writeln!(log, "{entry}").and_then(|_| log.flush());
+        }));
+    }
+
+    /// starts recording every line sent to and received from the engine,
+    /// discarding any recording that was already in progress
+    pub fn start_recording(&mut self) {
+        self.recording = Some((Instant::now(), transcript::Transcript::new()));
+    }
+
+    /// stops recording and returns everything recorded since `Engine::start_recording()`,
+    /// or `None` if nothing was being recorded
+    pub fn stop_recording(&mut self) -> Option<transcript::Transcript> {
+        self.recording.take().map(|(_, transcript)| transcript)
+    }
+
     /// if the next line of output contains `expected`,
     /// the function returns the entire line,
     /// otherwise `None`
+    ///
+    /// errors reading the line, e.g. `EngineError::Crashed`, are returned instead of `None`
     #[inline(always)]
-    pub fn try_get(&mut self, expected: &str) -> Option<&str> {
-        if self.get_next().ok()?.contains(expected) {
-            Some(&self.buf)
+    pub fn try_get(&mut self, expected: &str) -> Result<Option<&str>, EngineError> {
+        if self.get_next()?.contains(expected) {
+            Ok(Some(&self.buf))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -77,17 +485,79 @@ impl Engine {
         starting_fen: &str,
         wtime_ms: u128,
         btime_ms: u128,
-    ) -> io::Result<()> {
+    ) -> Result<(), EngineError> {
+        let params = go::GoParams::new()
+            .wtime(Duration::from_millis(wtime_ms as u64))
+            .btime(Duration::from_millis(btime_ms as u64));
+
+        self.go(pos, starting_fen, &params)
+    }
+
+    /// asks the engine to search `pos`, with all moves played since `starting_fen`, within the limits of `params`,
+    /// the result can be read with `Engine::try_get_move()` like after `Engine::request_move()`
+    pub fn go(
+        &mut self,
+        pos: &pos::Position,
+        starting_fen: &str,
+        params: &go::GoParams,
+    ) -> Result<(), EngineError> {
+        self.send_position(pos, starting_fen)?;
+        self.send(&params.to_command(pos))
+    }
+
+    /// runs the engine's `go perft` on `pos`, with all moves played since `starting_fen`,
+    /// and returns the node count of every legal move it generates, in UCI notation
+    ///
+    /// `go perft` isn't part of the UCI protocol, but most engines support it the way Stockfish does,
+    /// with a `<move>: <nodes>` line per move, ending with `Nodes searched: <nodes>`
+    pub fn perft(
+        &mut self,
+        pos: &pos::Position,
+        starting_fen: &str,
+        depth: u32,
+    ) -> Result<Vec<(String, u64)>, EngineError> {
+        self.send_position(pos, starting_fen)?;
+        self.send(&format!("go perft {depth}"))?;
+
+        let mut divide = Vec::new();
+
+        loop {
+            let line = self.get_next()?;
+
+            if line.is_empty() {
+                return Err(EngineError::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "engine exited before finishing perft",
+                )));
+            }
+
+            if line.trim_start().starts_with("Nodes searched") {
+                return Ok(divide);
+            }
+
+            if let Some((mov, nodes)) = line.trim().split_once(':')
+                && let Ok(nodes) = nodes.trim().parse()
+            {
+                divide.push((mov.trim().to_string(), nodes));
+            }
+        }
+    }
+
+    /// sends the `position` command for `pos`, with all moves played since `starting_fen`
+    fn send_position(
+        &mut self,
+        pos: &pos::Position,
+        starting_fen: &str,
+    ) -> Result<(), EngineError> {
         let moves = pos
-            .history()
+            .moves()
             .iter()
-            .filter(|st| st.move_played.is_some())
-            .map(|st| st.move_played.unwrap().to_uci_fmt() + " ")
-            .collect::<String>();
+            .map(|mov| mov.to_uci_fmt_for(pos))
+            .collect::<Vec<String>>();
 
-        let moves = match pos.move_played() {
-            Some(mov) => String::from("moves ") + &moves + &mov.to_uci_fmt(),
-            None => moves,
+        let moves = match moves.is_empty() {
+            true => String::new(),
+            false => String::from("moves ") + &moves.join(" "),
         };
 
         if starting_fen == pos::START_FEN {
@@ -95,7 +565,6 @@ impl Engine {
         } else {
             self.send(&format!("position fen {starting_fen} {moves}"))?;
         }
-        self.send(&format!("go wtime {wtime_ms} btime {btime_ms}"))?;
 
         Ok(())
     }
@@ -103,9 +572,14 @@ impl Engine {
     /// returns the move an engine wants to play after being prompted by `Engine::request_move()`
     ///
     /// if the engine returns a null move, the function returns `Some(None)`, if no move is received, `None` is returned, else `Some(Some(Move))`
+    ///
+    /// errors reading the engine's output, e.g. `EngineError::Crashed`, are returned instead of `None`
     #[inline(always)]
-    pub fn try_get_move(&mut self, pos: &pos::Position) -> Option<Option<moves::Move>> {
-        match self.try_get(BEST_MOVE) {
+    pub fn try_get_move(
+        &mut self,
+        pos: &pos::Position,
+    ) -> Result<Option<Option<moves::Move>>, EngineError> {
+        Ok(match self.try_get(BEST_MOVE)? {
             Some(mov) => {
                 if mov.contains("none") || mov.contains("0000") {
                     Some(None)
@@ -117,6 +591,48 @@ impl Engine {
                 }
             }
             None => None,
+        })
+    }
+}
+
+impl Drop for Engine {
+    /// quits the engine, so abandoned engines don't keep running, see `Engine::quit()`
+    fn drop(&mut self) {
+        if let Ok(None) = self.exe.try_wait() {
+            let _ = self.quit(DROP_TIMEOUT);
         }
     }
 }
+
+/// starts the engine at `path`, with a thread that reads its output line by line
+fn spawn(path: &str) -> io::Result<(Child, mpsc::Receiver<io::Result<String>>)> {
+    let mut exe = Command::new(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let mut stdout_reader = BufReader::new(exe.stdout.take().unwrap());
+    let (sender, lines) = mpsc::channel();
+
+    // ends when the engine closes its stdout, or when the `Engine` is dropped
+    thread::spawn(move || {
+        loop {
+            let mut line = String::new();
+
+            match stdout_reader.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {
+                    if sender.send(Ok(line)).is_err() {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    let _ = sender.send(Err(err));
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok((exe, lines))
+}
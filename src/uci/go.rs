@@ -0,0 +1,229 @@
+// file for the search limits of the UCI `go` command
+
+use std::time::Duration;
+
+use crate::{AttackMasks, ZobristValues, color, moves, pos};
+
+/// the limits of a search, the arguments of a `go` command,
+/// e.g. `go wtime 60000 btime 60000 winc 1000 binc 1000` or `go depth 12`
+///
+/// limits that aren't set are `None`, a search without any limits runs until it's stopped
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct GoParams {
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    /// moves until the next time control
+    pub movestogo: Option<u32>,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    /// search for a mate in this many moves
+    pub mate: Option<u32>,
+    /// search exactly this long
+    pub movetime: Option<Duration>,
+    /// search until `stop`
+    pub infinite: bool,
+    /// search in pondering mode, the last move of the position is the one the engine expects
+    pub ponder: bool,
+    /// only search these moves, all legal moves if empty
+    pub searchmoves: Vec<moves::Move>,
+}
+
+/// words that start a new argument of a `go` command, ending the list of `searchmoves`
+const KEYWORDS: [&str; 12] = [
+    "searchmoves",
+    "ponder",
+    "wtime",
+    "btime",
+    "winc",
+    "binc",
+    "movestogo",
+    "depth",
+    "nodes",
+    "mate",
+    "movetime",
+    "infinite",
+];
+
+impl GoParams {
+    /// limits for a search that runs until it's stopped, add limits with the other methods, e.g.
+    /// `GoParams::new().wtime(Duration::from_secs(60)).btime(Duration::from_secs(60)).winc(Duration::from_secs(1))`
+    pub fn new() -> Self {
+        GoParams::default()
+    }
+
+    pub fn wtime(mut self, time: Duration) -> Self {
+        self.wtime = Some(time);
+        self
+    }
+
+    pub fn btime(mut self, time: Duration) -> Self {
+        self.btime = Some(time);
+        self
+    }
+
+    pub fn winc(mut self, inc: Duration) -> Self {
+        self.winc = Some(inc);
+        self
+    }
+
+    pub fn binc(mut self, inc: Duration) -> Self {
+        self.binc = Some(inc);
+        self
+    }
+
+    pub fn movestogo(mut self, moves: u32) -> Self {
+        self.movestogo = Some(moves);
+        self
+    }
+
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn nodes(mut self, nodes: u64) -> Self {
+        self.nodes = Some(nodes);
+        self
+    }
+
+    pub fn mate(mut self, moves: u32) -> Self {
+        self.mate = Some(moves);
+        self
+    }
+
+    pub fn movetime(mut self, time: Duration) -> Self {
+        self.movetime = Some(time);
+        self
+    }
+
+    pub fn infinite(mut self) -> Self {
+        self.infinite = true;
+        self
+    }
+
+    pub fn ponder(mut self) -> Self {
+        self.ponder = true;
+        self
+    }
+
+    pub fn searchmoves(mut self, moves: Vec<moves::Move>) -> Self {
+        self.searchmoves = moves;
+        self
+    }
+
+    /// parses the arguments of a `go` command, everything after `go`, for a search of `pos`
+    ///
+    /// arguments with invalid values are skipped, as are moves in `searchmoves` that aren't legal in `pos`,
+    /// negative times (which some GUIs send when a side is out of time) are read as 0
+    pub fn parse(
+        args: &str,
+        pos: &mut pos::Position,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Self {
+        let mut params = GoParams::default();
+        let mut words = args.split_whitespace().peekable();
+
+        let time = |word: Option<&str>| {
+            word.and_then(|ms| ms.parse::<i64>().ok())
+                .map(|ms| Duration::from_millis(ms.max(0) as u64))
+        };
+
+        while let Some(word) = words.next() {
+            match word {
+                "wtime" => params.wtime = time(words.next()),
+                "btime" => params.btime = time(words.next()),
+                "winc" => params.winc = time(words.next()),
+                "binc" => params.binc = time(words.next()),
+                "movetime" => params.movetime = time(words.next()),
+                "movestogo" => params.movestogo = words.next().and_then(|n| n.parse().ok()),
+                "depth" => params.depth = words.next().and_then(|n| n.parse().ok()),
+                "nodes" => params.nodes = words.next().and_then(|n| n.parse().ok()),
+                "mate" => params.mate = words.next().and_then(|n| n.parse().ok()),
+                "infinite" => params.infinite = true,
+                "ponder" => params.ponder = true,
+                "searchmoves" => {
+                    let legal = moves::gen_legal(pos, masks, zb);
+
+                    while let Some(uci) = words.next_if(|word| !KEYWORDS.contains(word)) {
+                        if let Some(&mov) = legal.iter().find(|m| m.to_uci_fmt_for(pos) == uci) {
+                            params.searchmoves.push(mov);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        params
+    }
+
+    /// writes the `go` command with these limits, `pos` is the position searched
+    /// (used to write the `searchmoves` in the right castling notation)
+    pub fn to_command(&self, pos: &pos::Position) -> String {
+        let mut cmd = String::from("go");
+
+        if !self.searchmoves.is_empty() {
+            cmd.push_str(" searchmoves");
+
+            for mov in &self.searchmoves {
+                cmd.push(' ');
+                cmd.push_str(&mov.to_uci_fmt_for(pos));
+            }
+        }
+
+        if self.ponder {
+            cmd.push_str(" ponder");
+        }
+
+        for (name, time) in [
+            ("wtime", self.wtime),
+            ("btime", self.btime),
+            ("winc", self.winc),
+            ("binc", self.binc),
+        ] {
+            if let Some(time) = time {
+                cmd.push_str(&format!(" {name} {}", time.as_millis()));
+            }
+        }
+
+        for (name, value) in [
+            ("movestogo", self.movestogo.map(u64::from)),
+            ("depth", self.depth.map(u64::from)),
+            ("nodes", self.nodes),
+            ("mate", self.mate.map(u64::from)),
+            (
+                "movetime",
+                self.movetime.map(|time| time.as_millis() as u64),
+            ),
+        ] {
+            if let Some(value) = value {
+                cmd.push_str(&format!(" {name} {value}"));
+            }
+        }
+
+        if self.infinite {
+            cmd.push_str(" infinite");
+        }
+
+        cmd
+    }
+
+    /// the time left on the clock of `side`, if the search has a clock
+    pub fn time_left(&self, side: color::Color) -> Option<Duration> {
+        match side {
+            color::WHITE => self.wtime,
+            _ => self.btime,
+        }
+    }
+
+    /// the increment of `side`, if the search has a clock with increments
+    pub fn increment(&self, side: color::Color) -> Option<Duration> {
+        match side {
+            color::WHITE => self.winc,
+            _ => self.binc,
+        }
+    }
+}
@@ -0,0 +1,247 @@
+// file for the options UCI engines declare with `option` lines during the `uci` handshake
+
+use std::fmt;
+
+/// an option declared by an engine, e.g. `option name Hash type spin default 16 min 1 max 33554432`
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct EngineOption {
+    pub name: String,
+    pub kind: OptionKind,
+}
+
+/// the type of an option, with its default value and bounds
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum OptionKind {
+    Check {
+        default: bool,
+    },
+    Spin {
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    /// one of a fixed set of strings
+    Combo {
+        default: String,
+        vars: Vec<String>,
+    },
+    /// an action without a value, e.g. `Clear Hash`
+    Button,
+    String {
+        default: String,
+    },
+}
+
+/// a value of an option, checked against its type with `EngineOption::parse_value()`
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(i64),
+    Combo(String),
+    Button,
+    String(String),
+}
+
+/// error returned when a value doesn't fit an option
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum OptionError {
+    /// the engine has no option with this name
+    Unknown(String),
+    /// the option needs a value, but none was given
+    MissingValue,
+    /// the value isn't a `true`/`false` for a check, a number for a spin, or one of the vars of a combo
+    InvalidValue(String),
+    /// the value of a spin is outside of its bounds
+    OutOfRange { value: i64, min: i64, max: i64 },
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionError::Unknown(name) => write!(f, "no option named '{name}'"),
+            OptionError::MissingValue => write!(f, "missing option value"),
+            OptionError::InvalidValue(value) => write!(f, "invalid option value '{value}'"),
+            OptionError::OutOfRange { value, min, max } => {
+                write!(f, "option value {value} is outside of {min}..={max}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OptionError {}
+
+impl EngineOption {
+    pub fn new(name: &str, kind: OptionKind) -> Self {
+        EngineOption {
+            name: name.to_string(),
+            kind,
+        }
+    }
+
+    /// parses an `option` line sent during the `uci` handshake, returns `None` for any other line
+    ///
+    /// names and string defaults may contain spaces, a missing `min` or `max` of a spin leaves it unbounded
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+
+        if words.next() != Some("option") || words.next() != Some("name") {
+            return None;
+        }
+
+        let mut name = Vec::new();
+        let mut kind = None;
+
+        for word in words.by_ref() {
+            match word {
+                "type" => {
+                    kind = words.next();
+                    break;
+                }
+                _ => name.push(word),
+            }
+        }
+
+        // every keyword after the type with the words up to the next keyword
+        let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+
+        for word in words {
+            match word {
+                "default" | "min" | "max" | "var" => fields.push((word, Vec::new())),
+                _ => fields.last_mut()?.1.push(word),
+            }
+        }
+
+        let field = |keyword| {
+            fields
+                .iter()
+                .find(|(kw, _)| *kw == keyword)
+                .map(|(_, value)| value.join(" "))
+        };
+        let default = field("default").filter(|default| default != "<empty>");
+
+        let kind = match kind? {
+            "check" => OptionKind::Check {
+                default: default? == "true",
+            },
+            "spin" => OptionKind::Spin {
+                default: default?.parse().ok()?,
+                min: field("min").map_or(Some(i64::MIN), |min| min.parse().ok())?,
+                max: field("max").map_or(Some(i64::MAX), |max| max.parse().ok())?,
+            },
+            "combo" => OptionKind::Combo {
+                default: default.unwrap_or_default(),
+                vars: fields
+                    .iter()
+                    .filter(|(kw, _)| *kw == "var")
+                    .map(|(_, var)| var.join(" "))
+                    .collect(),
+            },
+            "button" => OptionKind::Button,
+            "string" => OptionKind::String {
+                default: default.unwrap_or_default(),
+            },
+            _ => return None,
+        };
+
+        (!name.is_empty()).then(|| EngineOption::new(&name.join(" "), kind))
+    }
+
+    /// checks a typed value against the option's type and bounds,
+    /// returns the value to send, with the spelling of the matching combo var
+    pub fn validate(&self, value: &OptionValue) -> Result<OptionValue, OptionError> {
+        match (&self.kind, value) {
+            (OptionKind::Check { .. }, OptionValue::Check(_))
+            | (OptionKind::Button, OptionValue::Button)
+            | (OptionKind::String { .. }, OptionValue::String(_)) => Ok(value.clone()),
+            (&OptionKind::Spin { min, max, .. }, &OptionValue::Spin(value)) => {
+                match (min..=max).contains(&value) {
+                    true => Ok(OptionValue::Spin(value)),
+                    false => Err(OptionError::OutOfRange { value, min, max }),
+                }
+            }
+            (OptionKind::Combo { .. }, OptionValue::Combo(var)) => self.parse_value(Some(var)),
+            _ => Err(OptionError::InvalidValue(value.to_string())),
+        }
+    }
+
+    /// checks a value of a `setoption` command against the option's type and bounds,
+    /// `value` is `None` if the command doesn't have a `value` part, which is only valid for buttons
+    ///
+    /// option names and combo vars are case insensitive, like the UCI protocol says
+    pub fn parse_value(&self, value: Option<&str>) -> Result<OptionValue, OptionError> {
+        if self.kind == OptionKind::Button {
+            return Ok(OptionValue::Button);
+        }
+
+        let value = value.ok_or(OptionError::MissingValue)?;
+        let invalid = || OptionError::InvalidValue(value.to_string());
+
+        match &self.kind {
+            OptionKind::Check { .. } => match value {
+                "true" => Ok(OptionValue::Check(true)),
+                "false" => Ok(OptionValue::Check(false)),
+                _ => Err(invalid()),
+            },
+            &OptionKind::Spin { min, max, .. } => {
+                let value = value.parse().map_err(|_| invalid())?;
+
+                match (min..=max).contains(&value) {
+                    true => Ok(OptionValue::Spin(value)),
+                    false => Err(OptionError::OutOfRange { value, min, max }),
+                }
+            }
+            OptionKind::Combo { vars, .. } => vars
+                .iter()
+                .find(|var| var.eq_ignore_ascii_case(value))
+                .map(|var| OptionValue::Combo(var.clone()))
+                .ok_or_else(invalid),
+            OptionKind::String { .. } => Ok(OptionValue::String(match value {
+                "<empty>" => String::new(),
+                _ => value.to_string(),
+            })),
+            OptionKind::Button => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for EngineOption {
+    /// writes the option as the `option` line an engine sends during the `uci` handshake
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+
+        match &self.kind {
+            OptionKind::Check { default } => write!(f, "check default {default}"),
+            OptionKind::Spin { default, min, max } => {
+                write!(f, "spin default {default} min {min} max {max}")
+            }
+            OptionKind::Combo { default, vars } => {
+                write!(f, "combo default {default}")?;
+
+                for var in vars {
+                    write!(f, " var {var}")?;
+                }
+
+                Ok(())
+            }
+            OptionKind::Button => write!(f, "button"),
+            OptionKind::String { default } if default.is_empty() => {
+                write!(f, "string default <empty>")
+            }
+            OptionKind::String { default } => write!(f, "string default {default}"),
+        }
+    }
+}
+
+impl fmt::Display for OptionValue {
+    /// writes the value as it appears after `value` in a `setoption` command, buttons have no value
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionValue::Check(value) => write!(f, "{value}"),
+            OptionValue::Spin(value) => write!(f, "{value}"),
+            OptionValue::Combo(value) => write!(f, "{value}"),
+            OptionValue::Button => Ok(()),
+            OptionValue::String(value) if value.is_empty() => write!(f, "<empty>"),
+            OptionValue::String(value) => write!(f, "{value}"),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+// file for analyzing many positions with several engine processes at once
+
+use std::{
+    io,
+    sync::{Arc, Mutex, mpsc},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    AttackMasks, moves, pos,
+    uci::{self, Engine, EngineError, go::GoParams, score::BoundedScore},
+    zobrist,
+};
+
+/// how long an engine of the pool gets to answer `uci` and `isready` when the pool is created
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// a position to analyze, with the limits of the search
+///
+/// the search must end on its own, `infinite` searches never finish
+#[derive(Debug, Clone)]
+pub struct Job {
+    /// the position to analyze, with all moves played since `starting_fen`
+    pub pos: pos::Position,
+    pub starting_fen: String,
+    pub params: GoParams,
+}
+
+/// what an engine sent during the search of a job
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    /// `None` if the engine had no move to play
+    pub best: Option<moves::Move>,
+    /// every `info` line of the search, in the order they were sent
+    pub info: Vec<String>,
+}
+
+impl Analysis {
+    /// the score of the last `info` line with one, from the point of view of the side to move,
+    /// only the first line counts with `MultiPV`
+    pub fn score(&self) -> Option<BoundedScore> {
+        self.info
+            .iter()
+            .rev()
+            .filter(|line| {
+                let mut words = line
+                    .split_whitespace()
+                    .skip_while(|&word| word != "multipv");
+                words.nth(1).is_none_or(|multipv| multipv == "1")
+            })
+            .find_map(|line| BoundedScore::parse(line))
+    }
+}
+
+/// a finished job, `id` is the one returned by `EnginePool::submit()`
+#[derive(Debug)]
+pub struct JobResult {
+    pub id: usize,
+    pub job: Job,
+    pub analysis: Result<Analysis, EngineError>,
+}
+
+/// a fixed number of engine processes, each analyzing one job at a time on its own thread
+///
+/// jobs are handed to whichever engine is free first, so results arrive in the order they finish,
+/// dropping the pool waits for the running jobs and quits the engines
+pub struct EnginePool {
+    jobs: Option<mpsc::Sender<(usize, Job)>>,
+    results: mpsc::Receiver<JobResult>,
+    workers: Vec<thread::JoinHandle<()>>,
+    next_id: usize,
+}
+
+impl EnginePool {
+    /// starts `size` engines at `path` and waits until they're ready
+    pub fn new(path: &str, size: usize) -> Result<Self, EngineError> {
+        Self::with_setup(path, size, |_| Ok(()))
+    }
+
+    /// like `EnginePool::new()`, but calls `setup` for every engine after the `uci` handshake,
+    /// e.g. to set its options or `RestartPolicy`
+    pub fn with_setup(
+        path: &str,
+        size: usize,
+        setup: impl Fn(&mut Engine) -> Result<(), EngineError>,
+    ) -> Result<Self, EngineError> {
+        let (job_sender, jobs) = mpsc::channel::<(usize, Job)>();
+        let (result_sender, results) = mpsc::channel();
+        let jobs = Arc::new(Mutex::new(jobs));
+
+        let mut engines = Vec::with_capacity(size);
+
+        for _ in 0..size {
+            let mut engine = Engine::new(path)?;
+
+            engine.send(uci::UCI)?;
+            engine.wait_for(uci::UCI_OK, HANDSHAKE_TIMEOUT)?;
+            setup(&mut engine)?;
+            engine.send(uci::IS_READY)?;
+            engine.wait_for(uci::READY_OK, HANDSHAKE_TIMEOUT)?;
+
+            engines.push(engine);
+        }
+
+        let workers = engines
+            .into_iter()
+            .map(|mut engine| {
+                let jobs = Arc::clone(&jobs);
+                let results = result_sender.clone();
+
+                thread::spawn(move || {
+                    // only for checking the engine's moves, built once per worker
+                    let masks = crate::init_masks();
+
+                    loop {
+                        // the lock is only held while waiting for the next job
+                        let Ok((id, job)) = jobs.lock().unwrap().recv() else {
+                            break;
+                        };
+
+                        let analysis = analyze(&mut engine, &job, &masks);
+
+                        if results.send(JobResult { id, job, analysis }).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Ok(EnginePool {
+            jobs: Some(job_sender),
+            results,
+            workers,
+            next_id: 0,
+        })
+    }
+
+    /// the number of engines in the pool
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// queues a job for the next free engine and returns its id, ids count up from 0
+    pub fn submit(&mut self, job: Job) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        // the workers only stop once the pool is dropped
+        let _ = self.jobs.as_ref().unwrap().send((id, job));
+
+        id
+    }
+
+    /// the channel finished jobs are sent on, one `JobResult` for every submitted job
+    pub fn results(&self) -> &mpsc::Receiver<JobResult> {
+        &self.results
+    }
+
+    /// submits all `jobs` and waits for them, returns the results in the order of `jobs`
+    ///
+    /// results of jobs submitted before that weren't received yet count towards the results,
+    /// so this shouldn't be mixed with `EnginePool::submit()`
+    pub fn analyze_all(&mut self, jobs: impl IntoIterator<Item = Job>) -> Vec<JobResult> {
+        let count = jobs.into_iter().map(|job| self.submit(job)).count();
+
+        let mut results: Vec<JobResult> = self.results.iter().take(count).collect();
+        results.sort_unstable_by_key(|result| result.id);
+        results
+    }
+}
+
+impl Drop for EnginePool {
+    fn drop(&mut self) {
+        drop(self.jobs.take());
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// searches a job and collects the engine's output until `bestmove`,
+/// a best move that isn't legal in the job's position is an `InvalidData` error
+fn analyze(engine: &mut Engine, job: &Job, masks: &AttackMasks) -> Result<Analysis, EngineError> {
+    engine.go(&job.pos, &job.starting_fen, &job.params)?;
+
+    let mut info = Vec::new();
+
+    loop {
+        let line = engine.get_next()?;
+
+        if line.is_empty() {
+            return Err(EngineError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "engine exited before sending a move",
+            )));
+        }
+
+        let line = line.trim();
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("info") => info.push(line.to_string()),
+            Some(uci::BEST_MOVE) => {
+                let best = match words.next() {
+                    Some("none") | Some("0000") | None => None,
+                    Some(best) => {
+                        // the keys of the copy don't matter, so any zobrist values do
+                        let mut pos = job.pos.clone();
+                        let legal = moves::gen_legal(&mut pos, masks, &zobrist::DEFAULT_VALUES);
+
+                        match legal.iter().find(|m| m.to_uci_fmt_for(&pos) == best) {
+                            Some(&mov) => Some(mov),
+                            None => {
+                                return Err(EngineError::Io(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    format!("engine sent illegal move '{best}'"),
+                                )));
+                            }
+                        }
+                    }
+                };
+
+                return Ok(Analysis { best, info });
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,143 @@
+// file for the scores engines report in `info` lines, e.g. `info depth 12 score cp -35 upperbound pv ...`
+
+use std::{cmp::Ordering, fmt};
+
+use crate::color;
+
+/// centipawn value of a mate, see `Score::to_cp()`
+pub const MATE_SCORE: i32 = 30000;
+
+/// mates further away than this many moves count as this far in `Score::to_cp()`,
+/// so engines sending absurd distances can't overflow it or turn a mate into a centipawn score
+pub const MAX_MATE_MOVES: i32 = MATE_SCORE / 2;
+
+/// an evaluation, from the point of view of one side (the side to move, for scores sent by engines)
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Score {
+    /// centipawns, positive if the side is better
+    Cp(i32),
+    /// moves until mate, positive if the side mates, negative if it gets mated
+    ///
+    /// `Mate(0)` means the side to move is already checkmated, it's left as is by `Score::to_white()`,
+    /// since its sign can't be flipped
+    Mate(i32),
+}
+
+/// whether a score is exact or only a bound, e.g. after a fail high in an aspiration window
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum Bound {
+    #[default]
+    Exact,
+    /// the real score is at least this (`lowerbound`)
+    Lower,
+    /// the real score is at most this (`upperbound`)
+    Upper,
+}
+
+/// a score with its bound, as sent in an `info` line
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct BoundedScore {
+    pub score: Score,
+    pub bound: Bound,
+}
+
+impl Score {
+    /// the score as centipawns, mates are `MATE_SCORE` minus the moves to mate,
+    /// so faster mates score higher and being mated later scores higher
+    pub fn to_cp(self) -> i32 {
+        match self {
+            Score::Cp(cp) => cp,
+            Score::Mate(moves) if moves > 0 => MATE_SCORE - moves.min(MAX_MATE_MOVES),
+            Score::Mate(moves) => -MATE_SCORE - moves.max(-MAX_MATE_MOVES),
+        }
+    }
+
+    /// the score from the other side's point of view
+    pub fn flipped(self) -> Self {
+        match self {
+            Score::Cp(cp) => Score::Cp(cp.saturating_neg()),
+            Score::Mate(moves) => Score::Mate(moves.saturating_neg()),
+        }
+    }
+
+    /// converts a score from the point of view of `side` (usually the side to move) to white's point of view
+    pub fn to_white(self, side: color::Color) -> Self {
+        match side {
+            color::WHITE => self,
+            _ => self.flipped(),
+        }
+    }
+}
+
+impl Ord for Score {
+    /// orders scores by `Score::to_cp()`, mates rank above and below every centipawn score
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_cp().cmp(&other.to_cp())
+    }
+}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl BoundedScore {
+    /// parses the `score` of an `info` line, `None` if the line doesn't have a valid one
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace().skip_while(|&word| word != "score");
+        words.next()?;
+
+        let kind = words.next()?;
+        let value = words.next()?.parse().ok()?;
+
+        let score = match kind {
+            "cp" => Score::Cp(value),
+            "mate" => Score::Mate(value),
+            _ => return None,
+        };
+
+        let bound = match words.next() {
+            Some("lowerbound") => Bound::Lower,
+            Some("upperbound") => Bound::Upper,
+            _ => Bound::Exact,
+        };
+
+        Some(BoundedScore { score, bound })
+    }
+
+    /// converts a score from the point of view of `side` to white's point of view,
+    /// flipping the bound along with the score when `side` is black
+    pub fn to_white(self, side: color::Color) -> Self {
+        let bound = match (side, self.bound) {
+            (color::WHITE, bound) | (_, bound @ Bound::Exact) => bound,
+            (_, Bound::Lower) => Bound::Upper,
+            (_, Bound::Upper) => Bound::Lower,
+        };
+
+        BoundedScore {
+            score: self.score.to_white(side),
+            bound,
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    /// writes the score like it's written in an `info` line, e.g. `cp 35` or `mate -2`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Score::Cp(cp) => write!(f, "cp {cp}"),
+            Score::Mate(moves) => write!(f, "mate {moves}"),
+        }
+    }
+}
+
+impl fmt::Display for BoundedScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bound {
+            Bound::Exact => write!(f, "{}", self.score),
+            Bound::Lower => write!(f, "{} lowerbound", self.score),
+            Bound::Upper => write!(f, "{} upperbound", self.score),
+        }
+    }
+}
@@ -0,0 +1,328 @@
+// file for the engine side of the UCI protocol, for writing engines on top of libchess
+//
+// `UciServer` reads commands from a GUI and answers them, and calls the methods of an `Engine` for everything
+// that depends on the engine, like searching
+
+use std::{
+    io::{self, BufRead, Write},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Instant,
+};
+
+use crate::{
+    AttackMasks, ZobristValues, moves, pos,
+    uci::{
+        self,
+        go::GoParams,
+        option::{EngineOption, OptionError, OptionValue},
+    },
+};
+
+/// a search running on another thread, returning the engine and the result of sending `bestmove`
+type Searching<'scope, E> = Option<thread::ScopedJoinHandle<'scope, (E, io::Result<()>)>>;
+
+/// the name of the option GUIs set to play chess960, see `Position::set_chess960()`
+pub const CHESS_960: &str = "UCI_Chess960";
+
+/// an engine driven by a `UciServer`
+///
+/// searches run on their own thread, so the server can answer `isready` and `stop` during a search
+pub trait Engine: Send {
+    /// the name sent with `id name`
+    fn name(&self) -> &str;
+
+    /// the author sent with `id author`
+    fn author(&self) -> &str;
+
+    /// the options sent during the `uci` handshake, `setoption` commands are checked against them
+    /// before they're passed to `Engine::set_option()`
+    fn options(&self) -> Vec<EngineOption> {
+        Vec::new()
+    }
+
+    /// called for a `setoption` command of one of the engine's options, with a value that fits the option
+    fn set_option(&mut self, _name: &str, _value: &OptionValue) {}
+
+    /// called for `ucinewgame`, e.g. to clear the hash table
+    fn new_game(&mut self) {}
+
+    /// searches the position of `search` within its limits, or until `Search::should_stop()` returns true,
+    /// and returns the best move, `None` if there are no legal moves
+    fn search(&mut self, search: &Search) -> Option<moves::Move>;
+}
+
+/// a search started by a `go` command, passed to `Engine::search()`
+pub struct Search<'a> {
+    /// the position to search, with all moves played since the `position` command's starting position
+    pub pos: pos::Position,
+    pub params: GoParams,
+    pub masks: &'a AttackMasks,
+    pub zb: &'a ZobristValues,
+    /// when the `go` command was received
+    pub start: Instant,
+    stop: &'a AtomicBool,
+    send: &'a (dyn Fn(&str) + Sync),
+}
+
+impl Search<'_> {
+    /// whether the GUI sent `stop` or `quit`, the search should return its best move as soon as possible
+    #[inline(always)]
+    pub fn should_stop(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// sends an `info` line to the GUI, `info` is everything after `info`, e.g. `"depth 5 score cp 20 pv e2e4"`
+    pub fn info(&self, info: &str) {
+        (self.send)(&format!("info {info}"));
+    }
+}
+
+/// reads UCI commands from a GUI, keeps track of the position, and answers with the help of an `Engine`
+pub struct UciServer<E: Engine> {
+    /// `None` while the engine is searching on another thread
+    engine: Option<E>,
+    masks: AttackMasks,
+    zb: ZobristValues,
+    pos: pos::Position,
+    options: Vec<EngineOption>,
+}
+
+impl<E: Engine> UciServer<E> {
+    pub fn new(engine: E, masks: AttackMasks, zb: ZobristValues) -> Self {
+        let pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+        UciServer {
+            options: engine.options(),
+            engine: Some(engine),
+            masks,
+            zb,
+            pos,
+        }
+    }
+
+    /// the engine, for changing its settings between runs
+    pub fn engine(&mut self) -> &mut E {
+        self.engine.as_mut().unwrap()
+    }
+
+    /// the position of the last `position` command
+    pub fn position(&self) -> &pos::Position {
+        &self.pos
+    }
+
+    /// answers commands from stdin on stdout until `quit` or the end of the input
+    pub fn run(&mut self) -> io::Result<()> {
+        self.run_with(io::stdin().lock(), io::stdout())
+    }
+
+    /// answers commands from `input` on `output` until `quit` or the end of the input,
+    /// unknown commands are ignored, and problems with commands (e.g. an illegal move in `position`)
+    /// are reported with `info string`
+    pub fn run_with(&mut self, input: impl BufRead, output: impl Write + Send) -> io::Result<()> {
+        let output = Mutex::new(output);
+        let stop = AtomicBool::new(false);
+        let send = |line: &str| {
+            let mut output = output.lock().unwrap();
+
+            writeln!(output, "{line}").and_then(|_| output.flush())
+        };
+
+        let send_ignoring_errors = |line: &str| {
+            let _ = send(line);
+        };
+
+        // borrowed separately, so searches can hold on to the masks and zobrist values
+        // while other commands change the position
+        let UciServer {
+            engine: slot,
+            masks,
+            zb,
+            pos,
+            options,
+        } = self;
+        let (masks, zb) = (&*masks, &*zb);
+
+        thread::scope(|scope| {
+            let mut searching = None;
+
+            for line in input.lines() {
+                let line = line?;
+                let (cmd, args) = line
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((line.trim(), ""));
+
+                // the only commands answered during a search
+                match cmd {
+                    uci::IS_READY => {
+                        send(uci::READY_OK)?;
+                        continue;
+                    }
+                    uci::STOP => {
+                        stop.store(true, Ordering::Relaxed);
+                        finish(&mut searching, slot)?;
+                        continue;
+                    }
+                    uci::QUIT => {
+                        stop.store(true, Ordering::Relaxed);
+                        return finish(&mut searching, slot);
+                    }
+                    _ => {}
+                }
+
+                finish(&mut searching, slot)?;
+
+                let engine = slot.as_mut().unwrap();
+
+                match cmd {
+                    uci::UCI => {
+                        send(&format!("id name {}", engine.name()))?;
+                        send(&format!("id author {}", engine.author()))?;
+
+                        for option in options.iter() {
+                            send(&option.to_string())?;
+                        }
+
+                        send(uci::UCI_OK)?;
+                    }
+                    uci::NEW_GAME => engine.new_game(),
+                    "setoption" => {
+                        if let Err(err) = set_option(args, engine, pos, options) {
+                            send(&format!("info string {err}"))?;
+                        }
+                    }
+                    "position" => {
+                        if let Err(err) = set_position(args, pos, masks, zb) {
+                            send(&format!("info string {err}"))?;
+                        }
+                    }
+                    "go" => {
+                        let start = Instant::now();
+                        let mut pos = pos.clone();
+                        let params = GoParams::parse(args, &mut pos, masks, zb);
+                        let mut engine = slot.take().unwrap();
+                        let (stop, send) = (&stop, &send);
+
+                        stop.store(false, Ordering::Relaxed);
+
+                        searching = Some(scope.spawn(move || {
+                            let search = Search {
+                                pos,
+                                params,
+                                masks,
+                                zb,
+                                start,
+                                stop,
+                                send: &send_ignoring_errors,
+                            };
+
+                            let best = engine.search(&search);
+                            let best = match best {
+                                Some(mov) => mov.to_uci_fmt_for(&search.pos),
+                                None => String::from("0000"),
+                            };
+
+                            (engine, send(&format!("{} {best}", uci::BEST_MOVE)))
+                        }));
+                    }
+                    _ => {}
+                }
+            }
+
+            stop.store(true, Ordering::Relaxed);
+            finish(&mut searching, slot)
+        })
+    }
+}
+
+/// handles the arguments of a `setoption` command, `name <id> [value <x>]`
+fn set_option<E: Engine>(
+    args: &str,
+    engine: &mut E,
+    pos: &mut pos::Position,
+    options: &[EngineOption],
+) -> Result<(), OptionError> {
+    let args = args.trim().strip_prefix("name").unwrap_or(args).trim();
+    let (name, value) = match args.split_once(" value") {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (args, None),
+    };
+
+    if name.eq_ignore_ascii_case(CHESS_960) {
+        let chess960 = value == Some("true");
+
+        pos.set_chess960(chess960);
+    }
+
+    let option = options
+        .iter()
+        .find(|option| option.name.eq_ignore_ascii_case(name));
+
+    let Some(option) = option else {
+        // GUIs send `UCI_Chess960` to engines that don't declare it, it's handled above
+        return match name.eq_ignore_ascii_case(CHESS_960) {
+            true => Ok(()),
+            false => Err(OptionError::Unknown(name.to_string())),
+        };
+    };
+
+    let value = option.parse_value(value)?;
+    engine.set_option(&option.name, &value);
+
+    Ok(())
+}
+
+/// handles the arguments of a `position` command, `[startpos | fen <fen>] [moves <move1> ... <movei>]`
+fn set_position(
+    args: &str,
+    pos: &mut pos::Position,
+    masks: &AttackMasks,
+    zb: &ZobristValues,
+) -> Result<(), String> {
+    let (setup, moves) = args.split_once("moves").unwrap_or((args, ""));
+    let setup = setup.trim();
+
+    let fen = match setup.split_once(char::is_whitespace) {
+        _ if setup == "startpos" => pos::START_FEN,
+        Some(("fen", fen)) => fen.trim(),
+        _ => return Err(format!("invalid position '{setup}'")),
+    };
+
+    if let Err(issues) = pos::validate_fen(fen) {
+        return Err(format!("invalid fen '{fen}': {}", issues[0]));
+    }
+
+    let mut new = pos::Position::from_fen(fen, zb);
+    new.set_chess960(pos.is_chess960());
+
+    for uci in moves.split_whitespace() {
+        let legal = moves::gen_legal(&mut new, masks, zb);
+        let mov = legal.iter().find(|m| m.to_uci_fmt_for(&new) == uci);
+
+        match mov {
+            Some(&mov) => new.make_move(mov, zb),
+            None => return Err(format!("illegal move '{uci}'")),
+        }
+    }
+
+    *pos = new;
+
+    Ok(())
+}
+
+/// waits for the search in `searching` to finish, if there is one, and puts the engine back into `engine`
+fn finish<E>(searching: &mut Searching<'_, E>, engine: &mut Option<E>) -> io::Result<()> {
+    match searching.take() {
+        Some(handle) => {
+            let (searched, result) = handle.join().unwrap();
+            *engine = Some(searched);
+
+            result
+        }
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,266 @@
+// file for making engines play at a reduced strength, e.g. for human vs engine games
+
+use std::io;
+
+use rand::Rng;
+
+use crate::{AttackMasks, ZobristValues, moves, pos, uci, uci::score::Score};
+
+/// the lowest Elo the fallback handicap is calibrated for
+pub const MIN_HANDICAP_ELO: u32 = 600;
+
+/// the highest Elo the fallback handicap is calibrated for, higher targets get the same handicap,
+/// which no longer randomizes moves but still caps the nodes and depth of the search
+pub const MAX_HANDICAP_ELO: u32 = 2400;
+
+/// how an engine is made to play weaker
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum StrengthLimit {
+    /// the engine's own `UCI_LimitStrength` and `UCI_Elo` options
+    UciElo(u32),
+    /// limits on the search, and a random choice between the engine's best lines
+    /// for engines that don't support `UCI_Elo`
+    Handicap(Handicap),
+}
+
+/// search limits and move randomization that approximate a lower Elo, see `uci::strength::handicap_for_elo()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Handicap {
+    /// the maximum number of nodes searched per move
+    pub nodes: u64,
+    /// the maximum search depth
+    pub depth: u32,
+    /// the number of lines the engine is asked for (`MultiPV`)
+    pub multipv: u32,
+    /// how many centipawns worse than the best line a randomly chosen line may be
+    pub max_cp_loss: i32,
+}
+
+/// the range of the engine's `UCI_Elo` option, parsed from its `option` line
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct EloRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+/// a line of play reported by the engine with `info ... multipv <n> score ... pv ...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PvLine {
+    pub multipv: u32,
+    /// the score in centipawns, see `Score::to_cp()` for how mates are scored
+    pub score: i32,
+    /// the first move of the line, in UCI format
+    pub first_move: String,
+}
+
+/// a rough mapping from a target Elo to search limits, clamped to `MIN_HANDICAP_ELO..=MAX_HANDICAP_ELO`
+///
+/// the calibration is approximate, the resulting strength depends a lot on the engine and hardware
+pub fn handicap_for_elo(elo: u32) -> Handicap {
+    let elo = elo.clamp(MIN_HANDICAP_ELO, MAX_HANDICAP_ELO);
+    let steps = elo - MIN_HANDICAP_ELO;
+    let max_cp_loss = ((MAX_HANDICAP_ELO - elo) / 6) as i32;
+
+    Handicap {
+        nodes: 100 << (steps / 150),
+        depth: 1 + steps / 200,
+        multipv: if max_cp_loss > 0 { 4 } else { 1 },
+        max_cp_loss,
+    }
+}
+
+/// chooses how to limit an engine to `elo`, using `UCI_Elo` if the engine supports it with a range
+/// containing `elo`, and a `Handicap` otherwise
+pub fn limit_for_elo(elo: u32, uci_elo: Option<EloRange>) -> StrengthLimit {
+    match uci_elo {
+        Some(range) if (range.min..=range.max).contains(&elo) => StrengthLimit::UciElo(elo),
+        _ => StrengthLimit::Handicap(handicap_for_elo(elo)),
+    }
+}
+
+/// parses an `option name UCI_Elo type spin ... min <n> max <n>` line sent during the `uci` handshake,
+/// returns `None` for any other line
+pub fn parse_elo_option(line: &str) -> Option<EloRange> {
+    let mut words = line.split_whitespace();
+
+    if words.next() != Some("option")
+        || words.next() != Some("name")
+        || words.next() != Some("UCI_Elo")
+    {
+        return None;
+    }
+
+    let mut min = None;
+    let mut max = None;
+
+    while let Some(word) = words.next() {
+        match word {
+            "min" => min = words.next().and_then(|n| n.parse().ok()),
+            "max" => max = words.next().and_then(|n| n.parse().ok()),
+            _ => {}
+        }
+    }
+
+    Some(EloRange {
+        min: min?,
+        max: max?,
+    })
+}
+
+/// parses an `info` line with a score and a pv, lines without `multipv` count as the first line
+pub fn parse_pv_line(line: &str) -> Option<PvLine> {
+    let mut words = line.split_whitespace();
+
+    if words.next() != Some("info") {
+        return None;
+    }
+
+    let mut multipv = 1;
+    let mut score = None;
+
+    while let Some(word) = words.next() {
+        match word {
+            "multipv" => multipv = words.next()?.parse().ok()?,
+            "score" => {
+                let kind = words.next()?;
+                let value = words.next()?.parse().ok()?;
+
+                score = Some(match kind {
+                    "mate" => Score::Mate(value).to_cp(),
+                    _ => Score::Cp(value).to_cp(),
+                });
+            }
+            "pv" => {
+                return Some(PvLine {
+                    multipv,
+                    score: score?,
+                    first_move: words.next()?.to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// picks the first move of a random line whose score is at most `max_cp_loss` worse than the best one
+pub fn pick_line<'a, R: Rng + ?Sized>(
+    lines: &'a [PvLine],
+    max_cp_loss: i32,
+    rng: &mut R,
+) -> Option<&'a PvLine> {
+    let best = lines.iter().map(|line| line.score).max()?;
+    let candidates = lines
+        .iter()
+        .filter(|line| line.score >= best.saturating_sub(max_cp_loss))
+        .collect::<Vec<&PvLine>>();
+
+    Some(candidates[rng.random_range(0..candidates.len())])
+}
+
+impl uci::Engine {
+    /// sends the options that limit the engine's strength, should be sent before `ucinewgame`
+    pub fn set_strength(&mut self, limit: StrengthLimit) -> Result<(), uci::EngineError> {
+        match limit {
+            StrengthLimit::UciElo(elo) => {
+                self.send("setoption name UCI_LimitStrength value true")?;
+                self.send(&format!("setoption name UCI_Elo value {elo}"))
+            }
+            StrengthLimit::Handicap(handicap) => {
+                self.send("setoption name UCI_LimitStrength value false")?;
+                self.send(&format!(
+                    "setoption name MultiPV value {}",
+                    handicap.multipv
+                ))
+            }
+        }
+    }
+
+    /// like `Engine::request_move()`, but with the search limits of `limit` on top of the clock
+    pub fn request_limited_move(
+        &mut self,
+        pos: &pos::Position,
+        starting_fen: &str,
+        wtime_ms: u128,
+        btime_ms: u128,
+        limit: StrengthLimit,
+    ) -> Result<(), uci::EngineError> {
+        self.send_position(pos, starting_fen)?;
+
+        match limit {
+            StrengthLimit::UciElo(_) => self.send(&format!("go wtime {wtime_ms} btime {btime_ms}")),
+            StrengthLimit::Handicap(handicap) => self.send(&format!(
+                "go wtime {wtime_ms} btime {btime_ms} nodes {} depth {}",
+                handicap.nodes, handicap.depth
+            )),
+        }
+    }
+
+    /// reads the engine's output until `bestmove`, and returns the move to play after a request
+    /// with `Engine::request_limited_move()`, `None` if the engine has no move to play
+    ///
+    /// with a `Handicap`, the move is picked at random from the lines the engine reported,
+    /// see `uci::strength::pick_line()`, otherwise it's the engine's best move
+    ///
+    /// fails with an error of kind `InvalidData` if the chosen move isn't legal in `pos`
+    pub fn get_limited_move<R: Rng + ?Sized>(
+        &mut self,
+        pos: &pos::Position,
+        limit: StrengthLimit,
+        rng: &mut R,
+        masks: &AttackMasks,
+        zb: &ZobristValues,
+    ) -> Result<Option<moves::Move>, uci::EngineError> {
+        let mut lines: Vec<PvLine> = Vec::new();
+
+        loop {
+            let line = self.get_next()?;
+
+            if line.is_empty() {
+                return Err(uci::EngineError::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "engine exited before sending a move",
+                )));
+            }
+
+            if let Some(pv) = parse_pv_line(line) {
+                // deeper iterations replace the lines of shallower ones
+                match lines.iter_mut().find(|l| l.multipv == pv.multipv) {
+                    Some(existing) => *existing = pv,
+                    None => lines.push(pv),
+                }
+
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+
+            if words.next() != Some(uci::BEST_MOVE) {
+                continue;
+            }
+
+            let best = match words.next() {
+                Some("none") | Some("0000") | None => return Ok(None),
+                Some(best) => best.to_string(),
+            };
+
+            let chosen = match limit {
+                StrengthLimit::Handicap(handicap) => pick_line(&lines, handicap.max_cp_loss, rng)
+                    .map_or(best, |line| line.first_move.clone()),
+                StrengthLimit::UciElo(_) => best,
+            };
+
+            let mut pos = pos.clone();
+            let legal = moves::gen_legal(&mut pos, masks, zb);
+
+            return match legal.iter().find(|m| m.to_uci_fmt_for(&pos) == chosen) {
+                Some(&mov) => Ok(Some(mov)),
+                None => Err(uci::EngineError::Io(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("engine sent illegal move '{chosen}'"),
+                ))),
+            };
+        }
+    }
+}
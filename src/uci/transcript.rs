@@ -0,0 +1,211 @@
+// file for recording UCI sessions and replaying them against an engine
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use crate::uci;
+
+/// which side of the session a line came from
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Direction {
+    /// sent by the gui to the engine
+    Sent,
+    /// received by the gui from the engine
+    Received,
+}
+
+/// a single line of a UCI session
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Entry {
+    /// time since the recording started
+    pub elapsed: Duration,
+    pub direction: Direction,
+    /// the line, without the trailing newline
+    pub line: String,
+}
+
+/// a recorded UCI session
+///
+/// as text, every entry is a line in the format `<milliseconds> <'>' or '<'> <line>`,
+/// `>` being a command sent to the engine and `<` a reply from it, e.g. `"15 > isready"`
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct Transcript {
+    pub entries: Vec<Entry>,
+}
+
+/// error returned by `Transcript::replay()`
+#[derive(Debug)]
+pub enum ReplayError {
+    /// communicating with the engine failed
+    Io(uci::EngineError),
+    /// the engine closed its output before sending an expected reply
+    Eof { entry: usize, expected: String },
+    /// the engine sent a different reply than the one recorded
+    Mismatch {
+        entry: usize,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// error returned when parsing a transcript from text fails, contains the offending line number
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ParseTranscriptError(pub usize);
+
+/// the replies `Transcript::replay()` checks by default,
+/// other replies (e.g. `info` lines) usually change from run to run
+pub const DEFAULT_CHECKED: &[&str] = &[uci::UCI_OK, uci::READY_OK, uci::BEST_MOVE];
+
+impl Transcript {
+    pub fn new() -> Self {
+        Transcript {
+            entries: Vec::new(),
+        }
+    }
+
+    /// adds a line to the end of the transcript
+    pub fn push(&mut self, elapsed: Duration, direction: Direction, line: &str) {
+        self.entries.push(Entry {
+            elapsed,
+            direction,
+            line: line.trim_end_matches(['\n', '\r']).to_string(),
+        });
+    }
+
+    /// replays the gui side of the session against `engine`,
+    /// sending every recorded command in order, and checking the recorded replies
+    /// whose first word is in `checked` against what the engine actually sends
+    ///
+    /// before a checked reply, any engine output with a different first word is skipped,
+    /// recorded replies that aren't checked are ignored
+    pub fn replay(&self, engine: &mut uci::Engine, checked: &[&str]) -> Result<(), ReplayError> {
+        for (i, entry) in self.entries.iter().enumerate() {
+            match entry.direction {
+                Direction::Sent => engine.send(&entry.line).map_err(ReplayError::Io)?,
+                Direction::Received => {
+                    let token = entry.line.split_whitespace().next().unwrap_or("");
+
+                    if !checked.contains(&token) {
+                        continue;
+                    }
+
+                    loop {
+                        let line = engine.get_next().map_err(ReplayError::Io)?;
+
+                        if line.is_empty() {
+                            return Err(ReplayError::Eof {
+                                entry: i,
+                                expected: entry.line.clone(),
+                            });
+                        }
+
+                        let line = line.trim();
+
+                        if line.split_whitespace().next() == Some(token) {
+                            if line != entry.line.trim() {
+                                return Err(ReplayError::Mismatch {
+                                    entry: i,
+                                    expected: entry.line.clone(),
+                                    actual: line.to_string(),
+                                });
+                            }
+
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Entry {
+    /// writes the entry as a line of a transcript, without the newline
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.elapsed.as_millis(),
+            match self.direction {
+                Direction::Sent => '>',
+                Direction::Received => '<',
+            },
+            self.line
+        )
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{entry}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Transcript {
+    type Err = ParseTranscriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut transcript = Transcript::new();
+
+        for (i, line) in s.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(3, ' ');
+
+            let millis: u64 = parts
+                .next()
+                .and_then(|m| m.parse().ok())
+                .ok_or(ParseTranscriptError(i + 1))?;
+
+            let direction = match parts.next() {
+                Some(">") => Direction::Sent,
+                Some("<") => Direction::Received,
+                _ => return Err(ParseTranscriptError(i + 1)),
+            };
+
+            transcript.push(
+                Duration::from_millis(millis),
+                direction,
+                parts.next().unwrap_or(""),
+            );
+        }
+
+        Ok(transcript)
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "engine i/o failed: {err}"),
+            ReplayError::Eof { entry, expected } => {
+                write!(
+                    f,
+                    "entry {entry}: engine exited before sending '{expected}'"
+                )
+            }
+            ReplayError::Mismatch {
+                entry,
+                expected,
+                actual,
+            } => write!(f, "entry {entry}: expected '{expected}', got '{actual}'"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl fmt::Display for ParseTranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid transcript entry on line {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseTranscriptError {}
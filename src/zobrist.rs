@@ -1,27 +1,128 @@
-use rand::Rng;
+use crate::{
+    ZobristValues, color,
+    piece::{
+        self,
+        bb::{self, BitboardUtil},
+    },
+    pos,
+};
 
-use crate::{ZobristValues, color, piece::bb, pos};
+pub mod polyglot;
 
 pub type Key = u64;
 
-/// used to generate all random values needed to create a zobrist key
-pub(crate) fn init_zb_values(zb: &mut ZobristValues) {
-    let mut rng = rand::rng();
+/// the seed `init()` generates zobrist values from without the `rand-zobrist` feature, see `init_with_seed()`
+pub const DEFAULT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
 
-    zb.black_to_move = rng.random();
-    zb.wk_castle = rng.random();
-    zb.wq_castle = rng.random();
-    zb.bk_castle = rng.random();
-    zb.bq_castle = rng.random();
+/// the zobrist values generated from `DEFAULT_SEED`, built at compile time
+pub static DEFAULT_VALUES: ZobristValues = ZobristValues::from_seed(DEFAULT_SEED);
 
-    for file in zb.ep_files.iter_mut() {
-        *file = rng.random();
+/// the zobrist values of Polyglot, built at compile time, see `init_polyglot()`
+pub static POLYGLOT_VALUES: ZobristValues = ZobristValues::polyglot();
+
+/// the number of random values needed to create a zobrist key
+const VALUE_COUNT: usize = 781;
+
+impl ZobristValues {
+    /// generates the zobrist values from `seed` with a splitmix64 generator, at compile time when used in a const,
+    /// e.g. `const ZB: ZobristValues = ZobristValues::from_seed(7);`
+    ///
+    /// the generator is part of the library, unlike the RNGs of `rand`, so the values never change between versions
+    pub const fn from_seed(seed: u64) -> Self {
+        let mut values = [0; VALUE_COUNT];
+        let mut state = seed;
+        let mut i = 0;
+
+        while i < VALUE_COUNT {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            values[i] = z ^ (z >> 31);
+
+            i += 1;
+        }
+
+        Self::from_values(&values)
     }
 
-    for piece in zb.piece_sq.iter_mut() {
-        for sq in piece.iter_mut() {
-            *sq = rng.random();
+    /// the zobrist values of Polyglot, see `zobrist::polyglot::RANDOM64`
+    pub const fn polyglot() -> Self {
+        let random = &polyglot::RANDOM64;
+
+        let mut zb = ZobristValues {
+            // Polyglot hashes white to move, which is the same as hashing it from the start and toggling it every move
+            base: random[780],
+            black_to_move: random[780],
+            wk_castle: random[768],
+            wq_castle: random[769],
+            bk_castle: random[770],
+            bq_castle: random[771],
+            ep_files: [0; 8],
+            piece_sq: [[0; 64]; 12],
+        };
+
+        let mut file = 0;
+
+        while file < 8 {
+            zb.ep_files[file] = random[772 + file];
+            file += 1;
         }
+
+        // pieces are indexed from white pawns to white kings, then black pawns to black kings,
+        // Polyglot alternates black and white for every piece type
+        let mut piece = 0;
+
+        while piece < 12 {
+            let kind = 2 * (piece % 6) + (piece < 6) as usize;
+            let mut sq = 0;
+
+            while sq < 64 {
+                zb.piece_sq[piece][sq] = random[64 * kind + sq];
+                sq += 1;
+            }
+
+            piece += 1;
+        }
+
+        zb
+    }
+
+    /// draws all random values needed to create a zobrist key from `next_value`
+    #[cfg(feature = "rand-zobrist")]
+    pub(crate) fn from_fn(mut next_value: impl FnMut() -> u64) -> Self {
+        Self::from_values(&std::array::from_fn(|_| next_value()))
+    }
+
+    /// takes the side to move, castling rights, en passant files, and pieces, in this order, from `values`
+    const fn from_values(values: &[u64; VALUE_COUNT]) -> Self {
+        let mut zb = ZobristValues {
+            base: 0,
+            black_to_move: values[0],
+            wk_castle: values[1],
+            wq_castle: values[2],
+            bk_castle: values[3],
+            bq_castle: values[4],
+            ep_files: [0; 8],
+            piece_sq: [[0; 64]; 12],
+        };
+
+        let mut i = 0;
+
+        while i < 8 {
+            zb.ep_files[i] = values[5 + i];
+            i += 1;
+        }
+
+        let mut i = 0;
+
+        while i < 12 * 64 {
+            zb.piece_sq[i / 64][i % 64] = values[13 + i];
+            i += 1;
+        }
+
+        zb
     }
 }
 
@@ -32,7 +133,7 @@ pub(crate) fn init_zb_values(zb: &mut ZobristValues) {
 /// the function takes into account the locations and types of all pieces on the board,
 /// the side to move, en passant files, and castling rights
 pub fn hash(pos: &pos::Position, zb: &ZobristValues) -> Key {
-    let mut key = 0;
+    let mut key = zb.base;
 
     for sq in 0..64 {
         if pos.is_occupied(sq) {
@@ -66,3 +167,19 @@ pub fn hash(pos: &pos::Position, zb: &ZobristValues) -> Key {
 
     key
 }
+
+/// creates and returns a key that only hashes the pawns of `pos`, see `Position::pawn_key()`
+pub fn pawn_hash(pos: &pos::Position, zb: &ZobristValues) -> Key {
+    let mut key = 0;
+
+    for color in [color::WHITE, color::BLACK] {
+        let pawn = piece::PAWN | color;
+        let mut pawns = pos.piece_bb(pawn);
+
+        while pawns != bb::EMPTY {
+            key ^= zb.piece_sq[bb::p_to_idx(pawn)][pawns.serialize_once()];
+        }
+    }
+
+    key
+}
@@ -0,0 +1,22 @@
+#!/bin/sh
+# minimal UCI engine used by the tests, it always plays e2e4
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name fake engine"
+            echo "option name Hash type spin default 16 min 1 max 1024"
+            echo "option name Clear Hash type button"
+            echo "option name Style type combo default Normal var Solid var Normal var Risky"
+            echo "option name SyzygyPath type string default <empty>"
+            echo "uciok"
+            ;;
+        isready) echo "readyok" ;;
+        go*)
+            echo "info depth 1 score cp 20 pv e2e4"
+            echo "bestmove e2e4"
+            ;;
+        # exits without being told to quit, like an engine that segfaults
+        crash) exit 3 ;;
+        quit) exit 0 ;;
+    esac
+done
@@ -0,0 +1,3 @@
+#!/bin/sh
+# UCI engine used by the tests that never answers and ignores quit, it only exits when killed
+exec sleep 600
@@ -0,0 +1,41 @@
+#!/bin/sh
+# UCI engine used by the tests for `go perft`, it only knows the first two plies from the starting position
+# and has a made up bug, after 1. Nf3 it thinks black's king can already move to e7
+white="a2a3 a2a4 b1a3 b1c3 b2b3 b2b4 c2c3 c2c4 d2d3 d2d4 e2e3 e2e4 f2f3 f2f4 g1f3 g1h3 g2g3 g2g4 h2h3 h2h4"
+black="a7a5 a7a6 b7b5 b7b6 b8a6 b8c6 c7c5 c7c6 d7d5 d7d6 e7e5 e7e6 f7f5 f7f6 g7g5 g7g6 g8f6 g8h6 h7h5 h7h6"
+position=""
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name perft engine"
+            echo "uciok"
+            ;;
+        isready) echo "readyok" ;;
+        position*) position="$line" ;;
+        "go perft 1")
+            case "$position" in
+                "position startpos") moves="$white" ;;
+                "position startpos moves g1f3") moves="$black e8e7" ;;
+                *) moves="$black" ;;
+            esac
+            nodes=0
+            for move in $moves; do
+                echo "$move: 1"
+                nodes=$((nodes + 1))
+            done
+            echo
+            echo "Nodes searched: $nodes"
+            ;;
+        "go perft 2")
+            for move in $white; do
+                case "$move" in
+                    g1f3) echo "$move: 21" ;;
+                    *) echo "$move: 20" ;;
+                esac
+            done
+            echo
+            echo "Nodes searched: 401"
+            ;;
+        quit) exit 0 ;;
+    esac
+done
@@ -3,7 +3,14 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 use std::time::Instant;
 
-use libchess::{moves, perft, piece::bb::BitboardUtil, pos};
+use libchess::{
+    adjudication, chess960, color, dataset, db, epd, game_log, moves, perft, pgn,
+    piece::{self, bb},
+    pos, report, rules, similarity,
+    square_table::SquareTable,
+    termination::Termination,
+    testpos, uci, zobrist,
+};
 
 #[test]
 fn test_main() {
@@ -15,3 +22,3324 @@ fn test_main() {
     // perft::test_epd("perftsuite.epd", 6, 200, 0, &masks, &zb);
     println!("perft took {}s", timer.elapsed().as_secs_f32());
 }
+
+#[test]
+fn test_rules() {
+    let (masks, zb) = libchess::init();
+
+    let mut pos = pos::Position::from_fen(rules::PAWNS_ONLY_FEN, &zb);
+    assert_eq!(
+        moves::gen_legal_with(&mut pos, &rules::PAWNS_ONLY, &masks, &zb).len(),
+        16
+    );
+
+    let mut pos = pos::Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", &zb);
+    assert_eq!(moves::gen_legal(&mut pos, &masks, &zb).len(), 26);
+    assert_eq!(
+        moves::gen_legal_with(&mut pos, &rules::NO_CASTLING, &masks, &zb).len(),
+        24
+    );
+
+    let mut pos = pos::Position::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", &zb);
+    assert_eq!(
+        moves::gen_legal_with(&mut pos, &rules::QUEEN_PROMOTIONS_ONLY, &masks, &zb).len(),
+        6
+    );
+
+    let antichess = rules::Rules {
+        castling: false,
+        check_rules: false,
+        promotions: moves::ANTICHESS_PROMOTIONS,
+        ..rules::STANDARD
+    };
+    let moves = moves::gen_legal_with(&mut pos, &antichess, &masks, &zb);
+    assert_eq!(moves.len(), 10);
+    assert!(moves.contains(&moves::Move::from_str_move("a7a8k", &pos)));
+
+    let mut pos = pos::Position::from_fen(rules::PAWNS_AND_KINGS_FEN, &zb);
+    assert_eq!(
+        moves::gen_legal_with(&mut pos, &rules::PAWNS_AND_KINGS, &masks, &zb).len(),
+        18
+    );
+
+    // the pawn on d3 guards e2, the king may only step there without check rules
+    let mut pos = pos::Position::from_fen("4k3/8/8/8/8/3p4/8/4K3 w - - 0 1", &zb);
+    let into_check = moves::Move::from_str_move("e1e2", &pos);
+    let moves = moves::gen_legal_with(&mut pos, &rules::PAWNS_AND_KINGS, &masks, &zb);
+    assert_eq!(moves.len(), 4);
+    assert!(!moves.contains(&into_check));
+    let moves = moves::gen_legal_with(&mut pos, &rules::NO_CHECKS, &masks, &zb);
+    assert_eq!(moves.len(), 5);
+    assert!(moves.contains(&into_check));
+
+    // without check rules the king can be captured, and the game goes on without it
+    let mut pos = pos::Position::from_fen("4k3/4R3/8/8/8/8/p7/4K3 w - - 0 1", &zb);
+    let take_king = moves::Move::from_str_move("e7e8", &pos);
+    assert_eq!(
+        take_king.type_of(),
+        moves::MoveType::Capture(piece::BLACK_KING)
+    );
+    assert!(moves::gen_legal_with(&mut pos, &rules::NO_CHECKS, &masks, &zb).contains(&take_king));
+
+    pos.make_move(take_king, &zb);
+    assert_eq!(pos.piece_bb(piece::BLACK_KING), bb::EMPTY);
+    let moves = moves::gen_legal_with(&mut pos, &rules::NO_CHECKS, &masks, &zb);
+    assert_eq!(moves.len(), 4);
+    assert!(moves.iter().all(|m| m.from_sq() == pos::str_to_sq("a2")));
+
+    pos.unmake_move();
+    assert_eq!(pos.to_fen(), "4k3/4R3/8/8/8/8/p7/4K3 w - - 0 1");
+}
+
+#[test]
+fn test_regions() {
+    assert_eq!(
+        bb::rect(
+            pos::make_sq(pos::FILE_C, pos::RANK_3),
+            pos::make_sq(pos::FILE_F, pos::RANK_6)
+        ),
+        bb::EXTENDED_CENTER
+    );
+    assert_eq!(
+        bb::rect(
+            pos::make_sq(pos::FILE_E, pos::RANK_5),
+            pos::make_sq(pos::FILE_D, pos::RANK_4)
+        ),
+        bb::CENTER
+    );
+    assert_eq!(bb::enemy_half(color::WHITE), bb::BLACK_HALF);
+    assert_eq!((bb::LIGHT_SQUARES | bb::DARK_SQUARES).count_ones(), 64);
+    assert_eq!(bb::LIGHT_SQUARES & bb::square_bb(0), bb::EMPTY);
+}
+
+#[test]
+fn test_see() {
+    let (masks, zb) = libchess::init();
+
+    let pos = pos::Position::from_fen("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", &zb);
+    assert_eq!(pos.see(moves::Move::from_str_move("e1e5", &pos), &masks), 1);
+
+    let pos = pos::Position::from_fen(
+        "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+        &zb,
+    );
+    assert_eq!(
+        pos.see(moves::Move::from_str_move("d3e5", &pos), &masks),
+        -2
+    );
+}
+
+#[test]
+fn test_zobrist_check() {
+    let (masks, zb) = libchess::init();
+    let mut pos = pos::Position::from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        &zb,
+    );
+
+    let report = perft::zobrist_check(&mut pos, 3, &masks, &zb);
+    assert!(report.is_ok(), "{report:?}");
+    assert_eq!(report.nodes, 1 + 48 + 2039 + 97862);
+
+    // the pawn key ignores everything but pawns
+    let a = pos::Position::from_fen("4k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1", &zb);
+    let b = pos::Position::from_fen("r2qk3/pp6/8/8/8/8/PP6/2N1K3 b - - 0 1", &zb);
+    let c = pos::Position::from_fen("4k3/pp6/8/8/8/8/P1P5/4K3 w - - 0 1", &zb);
+
+    assert_eq!(a.pawn_key(), b.pawn_key());
+    assert_ne!(a.pawn_key(), c.pawn_key());
+    assert_eq!(a.mirrored(&zb).pawn_key(), a.pawn_key());
+}
+
+#[test]
+fn test_is_legal() {
+    let (masks, zb) = libchess::init();
+
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    ] {
+        let mut root = pos::Position::from_fen(fen, &zb);
+
+        for &first in moves::gen_legal(&mut root, &masks, &zb).iter() {
+            let mut pos = root.clone();
+            pos.make_move(first, &zb);
+            let legal = moves::gen_legal(&mut pos, &masks, &zb);
+            let side = pos.side_to_move();
+
+            for from in 0..64 {
+                for to in 0..64 {
+                    let mut types = vec![
+                        moves::MoveType::Normal,
+                        moves::MoveType::PawnTwoUp,
+                        moves::MoveType::EnPassant,
+                        moves::MoveType::KingSideCastle,
+                        moves::MoveType::QueenSideCastle,
+                        moves::MoveType::Promotion(piece::QUEEN | side),
+                        moves::MoveType::Promotion(piece::KNIGHT | side),
+                    ];
+                    if pos.is_occupied(to) {
+                        types.push(moves::MoveType::Capture(pos.piece_on(to)));
+                        types.push(moves::MoveType::PromoCapture(
+                            piece::ROOK | side,
+                            pos.piece_on(to),
+                        ));
+                    }
+
+                    for type_of in types {
+                        let mov = moves::Move::new(from, to, type_of);
+                        assert_eq!(
+                            pos.is_legal(mov, &masks, &zb),
+                            legal.contains(&mov),
+                            "{mov:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_checkers_pinned() {
+    let (masks, zb) = libchess::init();
+
+    let pos = pos::Position::from_fen("4k3/8/1q6/8/4r3/8/3NB3/4K3 w - - 0 1", &zb);
+    assert_eq!(pos.checkers(&masks), bb::EMPTY);
+    assert_eq!(pos.pinned(&masks), bb::square_bb(pos::str_to_sq("e2")));
+
+    let pos = pos::Position::from_fen("4k3/8/8/8/1b2r3/3n4/3NB3/4K3 w - - 0 1", &zb);
+    assert_eq!(pos.checkers(&masks), bb::square_bb(pos::str_to_sq("d3")));
+    assert_eq!(
+        pos.pinned(&masks),
+        bb::square_bb(pos::str_to_sq("e2")) | bb::square_bb(pos::str_to_sq("d2"))
+    );
+}
+
+#[test]
+fn test_seeded_init() {
+    use libchess::rand::{SeedableRng, rngs::StdRng};
+
+    let (_, zb_a) = libchess::init_with_rng(&mut StdRng::seed_from_u64(7));
+    let (_, zb_b) = libchess::init_with_rng(&mut StdRng::seed_from_u64(7));
+
+    assert_eq!(
+        pos::Position::from_fen(pos::START_FEN, &zb_a).key(),
+        pos::Position::from_fen(pos::START_FEN, &zb_b).key()
+    );
+
+    let (_, zb_a) = libchess::init_with_seed(7);
+    let (_, zb_b) = libchess::init_with_seed(7);
+    let (_, zb_c) = libchess::init_with_seed(zobrist::DEFAULT_SEED);
+
+    let key = |zb| pos::Position::from_fen(testpos::kiwipete().fen, zb).key();
+    assert_eq!(key(&zb_a), key(&zb_b));
+    assert_ne!(key(&zb_a), key(&zb_c));
+    assert_eq!(key(&zb_c), 0xb4f0_3280_2577_2e87);
+}
+
+#[test]
+fn test_position_db() {
+    let (masks, zb) = libchess::init();
+    let mut root = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    let mut builder = db::PositionDbBuilder::new();
+    let mut positions = Vec::new();
+
+    for (i, &mov) in moves::gen_legal(&mut root, &masks, &zb).iter().enumerate() {
+        let mut pos = root.clone();
+        pos.make_move(mov, &zb);
+        builder.insert(&pos, i as u64);
+        positions.push(pos);
+    }
+
+    let path = std::env::temp_dir().join(format!("libchess_test_{}.db", std::process::id()));
+    builder.write(&path).unwrap();
+    let db = db::PositionDb::open(&path).unwrap();
+
+    assert_eq!(db.len(), 20);
+    for (i, pos) in positions.iter().enumerate() {
+        assert_eq!(db.probe(pos), Some(i as u64));
+    }
+    assert_eq!(db.probe(&root), None);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_likely_draw() {
+    let (_, zb) = libchess::init();
+
+    let pattern = |fen: &str| {
+        adjudication::likely_draw(&pos::Position::from_fen(fen, &zb)).map(|draw| draw.pattern)
+    };
+
+    assert_eq!(
+        pattern("k7/8/8/P7/8/8/3B4/4K3 w - - 0 1"),
+        Some(adjudication::DrawPattern::WrongRookPawn)
+    );
+    assert_eq!(pattern("k7/8/8/P7/8/8/4B3/4K3 w - - 0 1"), None);
+    assert_eq!(
+        pattern("8/4k3/8/1p1p1p1p/1P1P1P1P/8/4K3/8 w - - 0 1"),
+        Some(adjudication::DrawPattern::BlockedPawns)
+    );
+    assert_eq!(pattern("8/4k3/8/p6p/P6P/8/4K3/8 w - - 0 1"), None);
+    assert_eq!(pattern(pos::START_FEN), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_uci_transcript() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_engine.sh");
+
+    let mut engine = uci::Engine::new(path).unwrap();
+    engine.start_recording();
+    engine.send(uci::UCI).unwrap();
+    while engine.try_get(uci::UCI_OK).unwrap().is_none() {}
+    engine.send("go depth 1").unwrap();
+    while engine.try_get(uci::BEST_MOVE).unwrap().is_none() {}
+    let recorded = engine.stop_recording().unwrap();
+
+    let parsed: uci::transcript::Transcript = recorded.to_string().parse().unwrap();
+    assert_eq!(parsed.to_string(), recorded.to_string());
+
+    let mut engine = uci::Engine::new(path).unwrap();
+    parsed
+        .replay(&mut engine, uci::transcript::DEFAULT_CHECKED)
+        .unwrap();
+
+    let edited: uci::transcript::Transcript = recorded
+        .to_string()
+        .replace("bestmove e2e4", "bestmove d2d4")
+        .parse()
+        .unwrap();
+    let mut engine = uci::Engine::new(path).unwrap();
+    assert!(matches!(
+        edited.replay(&mut engine, uci::transcript::DEFAULT_CHECKED),
+        Err(uci::transcript::ReplayError::Mismatch { .. })
+    ));
+}
+
+#[test]
+fn test_promotion_filtering() {
+    let (masks, zb) = libchess::init();
+    let mut pos = pos::Position::from_fen("1n5k/P1P5/8/8/8/8/8/K7 w - - 0 1", &zb);
+
+    let all = moves::gen_legal(&mut pos, &masks, &zb).len();
+    let queens = moves::gen_legal_promos(&mut pos, moves::QUEEN_PROMOTIONS, &masks, &zb).len();
+    assert_eq!(all - queens, 12);
+
+    assert_eq!(
+        moves::gen_promotions(&mut pos, moves::ALL_PROMOTIONS, &masks, &zb).len(),
+        16
+    );
+    assert_eq!(
+        moves::gen_promotions(&mut pos, moves::QUEEN_PROMOTIONS, &masks, &zb).len(),
+        4
+    );
+}
+
+#[test]
+fn test_lazy_legal_moves() {
+    let (masks, zb) = libchess::init();
+    let mut root = pos::Position::from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        &zb,
+    );
+
+    for &mov in moves::gen_legal(&mut root, &masks, &zb).iter() {
+        let mut pos = root.clone();
+        pos.make_move(mov, &zb);
+
+        let eager = moves::gen_legal(&mut pos, &masks, &zb);
+        let lazy: Vec<moves::Move> = moves::legal_moves(&mut pos, &masks, &zb).collect();
+        assert_eq!(eager.as_slice(), lazy.as_slice());
+    }
+}
+
+#[test]
+fn test_move_list() {
+    let sq = pos::str_to_sq;
+    let mov = |from, to| moves::Move::new(sq(from), sq(to), moves::MoveType::Normal);
+
+    let mut list = moves::MoveList::new();
+    assert!(list.is_empty());
+    assert_eq!(format!("{list:?}"), "[]");
+
+    list.push(mov("e2", "e4"));
+    list.push(mov("g1", "f3"));
+    list.push(mov("b1", "c3"));
+    assert_eq!((list.len(), list[1]), (3, mov("g1", "f3")));
+    assert!(list.contains(&mov("b1", "c3")));
+
+    list.retain(|m| m.from_sq() != sq("g1"));
+    assert_eq!(list.as_slice(), [mov("e2", "e4"), mov("b1", "c3")]);
+    assert_eq!(
+        list.clone().into_iter().collect::<Vec<_>>(),
+        list.iter().copied().collect::<Vec<_>>()
+    );
+
+    list.clear();
+    assert!(list.is_empty());
+
+    // the list holds exactly `MAX_MOVES` moves
+    for _ in 0..moves::MAX_MOVES {
+        list.push(mov("a1", "a2"));
+    }
+    assert_eq!(list.len(), moves::MAX_MOVES);
+    assert_eq!(list.into_iter().count(), moves::MAX_MOVES);
+}
+
+#[test]
+fn test_gen_legal_into() {
+    let (masks, zb) = libchess::init();
+
+    let mut kiwipete = pos::Position::from_fen(testpos::kiwipete().fen, &zb);
+    let mut kings = pos::Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1", &zb);
+    let mut buf = moves::MoveList::new();
+
+    moves::gen_legal_into(&mut kiwipete, &mut buf, &masks, &zb);
+    assert_eq!(buf.len(), 48);
+    assert_eq!(
+        buf.as_slice(),
+        moves::gen_legal(&mut kiwipete, &masks, &zb).as_slice()
+    );
+
+    // the moves of the previous position are gone
+    moves::gen_legal_into(&mut kings, &mut buf, &masks, &zb);
+    assert_eq!(buf.len(), 5);
+    assert_eq!(
+        buf.as_slice(),
+        moves::gen_legal(&mut kings, &masks, &zb).as_slice()
+    );
+}
+
+#[test]
+fn test_mvv_lva() {
+    let (masks, zb) = libchess::init();
+    let mut pos = pos::Position::from_fen("4k3/4p3/8/3q4/2P1Q3/8/8/4K3 w - - 0 1", &zb);
+    let score = |uci| moves::score_mvv_lva(moves::Move::from_str_move(uci, &pos), &pos);
+
+    // PxQ > QxQ > QxP > quiet moves
+    assert!(score("c4d5") > score("e4d5"));
+    assert!(score("e4d5") > score("e4e7"));
+    assert!(score("e4e7") > 0);
+    assert_eq!(score("e1d2"), 0);
+
+    let mut legal = moves::gen_legal(&mut pos, &masks, &zb);
+    moves::sort_mvv_lva(&mut legal, &pos);
+    assert_eq!(
+        legal[..3]
+            .iter()
+            .map(|m| m.to_uci_fmt())
+            .collect::<Vec<_>>(),
+        ["c4d5", "e4d5", "e4e7"]
+    );
+    assert!(
+        legal[3..]
+            .iter()
+            .all(|&m| moves::score_mvv_lva(m, &pos) == 0)
+    );
+}
+
+#[test]
+fn test_gives_check() {
+    let (masks, zb) = libchess::init();
+
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+    ] {
+        let mut root = pos::Position::from_fen(fen, &zb);
+
+        for &first in moves::gen_legal(&mut root, &masks, &zb).iter() {
+            let mut pos = root.clone();
+            pos.make_move(first, &zb);
+
+            for &mov in moves::gen_legal(&mut pos, &masks, &zb).iter() {
+                let gives_check = pos.gives_check(mov, &masks);
+                pos.make_move(mov, &zb);
+                assert_eq!(gives_check, pos.is_check(&masks), "{fen} {mov:?}");
+                pos.unmake_move();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_between_and_line() {
+    let (masks, _) = libchess::init();
+    let sq = pos::str_to_sq;
+
+    assert_eq!(
+        masks.between(sq("a1"), sq("h8")),
+        bb::MAIN_DIAG_MASK & !bb::square_bb(sq("a1")) & !bb::square_bb(sq("h8"))
+    );
+    assert_eq!(masks.between(sq("e1"), sq("e2")), bb::EMPTY);
+    assert_eq!(masks.between(sq("b1"), sq("c3")), bb::EMPTY);
+    assert_eq!(masks.line(sq("b1"), sq("c3")), bb::EMPTY);
+    assert_eq!(masks.line(sq("c4"), sq("f4")), bb::RANK_4_MASK);
+    assert_eq!(masks.line(sq("d2"), sq("d7")), bb::FILE_D_MASK);
+
+    for a in 0..64 {
+        for b in 0..64 {
+            assert_eq!(masks.between(a, b), masks.between(b, a));
+            assert_eq!(masks.line(a, b), masks.line(b, a));
+            assert_eq!(masks.between(a, b) & !masks.line(a, b), bb::EMPTY);
+        }
+    }
+}
+
+#[test]
+fn test_xray_attacks() {
+    let (masks, zb) = libchess::init();
+    let sq = pos::str_to_sq;
+
+    // white rook skewers the black king to the queen, white bishop pins the knight
+    let pos = pos::Position::from_fen("3q4/8/3k4/8/3R4/5n2/8/K6B w - - 0 1", &zb);
+
+    assert_eq!(
+        bb::xray_rook_attacks(sq("d4"), pos.occupied_bb(), pos.occupied_bb(), &masks),
+        bb::square_bb(sq("d7")) | bb::square_bb(sq("d8"))
+    );
+    assert_eq!(
+        bb::xray_bishop_attacks(sq("h1"), pos.occupied_bb(), pos.occupied_bb(), &masks),
+        bb::square_bb(sq("e4"))
+            | bb::square_bb(sq("d5"))
+            | bb::square_bb(sq("c6"))
+            | bb::square_bb(sq("b7"))
+            | bb::square_bb(sq("a8"))
+    );
+    // blockers that aren't in the way don't change anything
+    assert_eq!(
+        bb::xray_rook_attacks(sq("d4"), pos.occupied_bb(), bb::square_bb(sq("f3")), &masks),
+        bb::EMPTY
+    );
+}
+
+#[test]
+fn test_to_fen() {
+    let (_, zb) = libchess::init();
+
+    for fen in [
+        pos::START_FEN,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 57",
+    ] {
+        assert_eq!(pos::Position::from_fen(fen, &zb).to_fen(), fen);
+    }
+
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    for (uci, fen) in [
+        (
+            "e2e4",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+        ),
+        (
+            "e7e5",
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+        ),
+        (
+            "g1f3",
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+        ),
+    ] {
+        pos.make_move(moves::Move::from_str_move(uci, &pos), &zb);
+        assert_eq!(pos.to_fen(), fen);
+    }
+}
+
+#[test]
+fn test_diff_diagram() {
+    let (_, zb) = libchess::init();
+
+    // the black pawn on d4 can capture en passant, otherwise the square wouldn't be kept
+    let a = pos::Position::from_fen(
+        "rnbqkbnr/ppp1pppp/8/8/3p4/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &zb,
+    );
+    let b = pos::Position::from_fen(
+        "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        &zb,
+    );
+
+    let diagram = a.diff_diagram(&b);
+
+    assert!(diagram.contains("4 . . . p .*. . .    4 . . . p P*. . . "));
+    assert!(diagram.contains("2 P P P P P*P P P    2 P P P P .*P P P "));
+    assert!(diagram.contains("squares: e4 e2\n"));
+    assert!(diagram.contains("side to move: w | b\n"));
+    assert!(diagram.contains("en passant: - | e3\n"));
+    assert!(!diagram.contains("castling"));
+
+    assert!(!a.diff_diagram(&a).contains('*'));
+}
+
+#[test]
+fn test_outcome() {
+    let (masks, zb) = libchess::init();
+
+    for (fen, outcome) in [
+        (pos::START_FEN, None),
+        (
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+            Some(pos::Outcome::Checkmate {
+                winner: color::BLACK,
+            }),
+        ),
+        (
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+            Some(pos::Outcome::Stalemate),
+        ),
+        (
+            "8/8/4k3/8/8/3NK3/8/8 w - - 0 1",
+            Some(pos::Outcome::InsufficientMaterial),
+        ),
+        (
+            "8/8/4k3/8/8/3RK3/8/8 b - - 100 80",
+            Some(pos::Outcome::FiftyMoveRule),
+        ),
+        // mate on the move that reaches the limit still counts
+        (
+            "R6k/8/6K1/8/8/8/8/8 b - - 100 80",
+            Some(pos::Outcome::Checkmate {
+                winner: color::WHITE,
+            }),
+        ),
+    ] {
+        assert_eq!(
+            pos::Position::from_fen(fen, &zb).outcome(&masks, &zb),
+            outcome,
+            "{fen}"
+        );
+    }
+
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    for uci in [
+        "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8",
+    ] {
+        assert_eq!(pos.outcome(&masks, &zb), None);
+        pos.make_move(moves::Move::from_str_move(uci, &pos), &zb);
+    }
+
+    let snapshot = pos.snapshot(&masks, &zb);
+
+    assert_eq!(snapshot.outcome, Some(pos::Outcome::Repetition));
+    assert!(snapshot.outcome.unwrap().is_draw());
+    assert_eq!(snapshot.fullmove, 5);
+    assert_eq!(pos.fullmove_number(), 5);
+    assert_eq!(pos.is_draw(&masks, &zb), Some(pos::DrawReason::Repetition));
+
+    // the number only changes after black's moves
+    pos.unmake_move();
+    assert_eq!(pos.fullmove_number(), 4);
+    pos.unmake_move();
+    assert_eq!(pos.fullmove_number(), 4);
+
+    for (fen, reason) in [
+        (pos::START_FEN, None),
+        (
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+            Some(pos::DrawReason::Stalemate),
+        ),
+        (
+            "8/8/4k3/8/8/3RK3/8/8 b - - 100 80",
+            Some(pos::DrawReason::FiftyMoveRule),
+        ),
+        ("R6k/8/6K1/8/8/8/8/8 b - - 100 80", None),
+    ] {
+        assert_eq!(
+            pos::Position::from_fen(fen, &zb).is_draw(&masks, &zb),
+            reason,
+            "{fen}"
+        );
+    }
+}
+
+const CHESS960_EPD: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/chess960.epd");
+
+#[test]
+fn test_chess960() {
+    let (masks, zb) = libchess::init();
+
+    // all 960 positions from https://www.chessprogramming.org/Chess960_Perft_Results,
+    // see `test_chess960_deep()` for depth 4
+    let suite = perft::test_epd(CHESS960_EPD, 3, 960, 0, &masks, &zb);
+    assert_eq!(suite.positions.len(), 960);
+    assert!(suite.is_ok(), "{:?}", suite.failures().collect::<Vec<_>>());
+
+    // the king only moves a single square, and the rook jumps over it
+    let mut pos = pos::Position::from_fen("4k3/8/8/8/8/8/8/4RK1R w K - 0 1", &zb);
+    let castle = moves::gen_legal(&mut pos, &masks, &zb)
+        .iter()
+        .copied()
+        .find(|m| m.type_of() == moves::MoveType::KingSideCastle)
+        .unwrap();
+
+    pos.make_move(castle, &zb);
+    assert_eq!(pos.to_fen(), "4k3/8/8/8/8/8/8/4RRK1 b - - 1 1");
+
+    // the king stays on c8, but the rook leaving b8 would uncover the queen
+    let mut pos = pos::Position::from_fen("Qrk5/8/8/8/8/8/8/4K3 b b - 0 1", &zb);
+    assert!(
+        moves::gen_legal(&mut pos, &masks, &zb)
+            .iter()
+            .all(|m| m.type_of() != moves::MoveType::QueenSideCastle)
+    );
+}
+
+#[test]
+#[ignore = "slow, run with --release -- --ignored"]
+fn test_chess960_deep() {
+    let (masks, zb) = libchess::init();
+
+    let suite = perft::test_epd(CHESS960_EPD, 4, 960, 0, &masks, &zb);
+    assert_eq!(suite.positions.len(), 960);
+    assert!(suite.is_ok(), "{:?}", suite.failures().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_mobility_and_trapped() {
+    let (masks, zb) = libchess::init();
+
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+    assert_eq!(pos.mobility(color::WHITE, &masks), 4);
+    assert_eq!(pos.mobility_diff(&masks), 0);
+
+    pos.make_move(moves::Move::from_str_move("e2e4", &pos), &zb);
+    assert_eq!(pos.mobility_diff(&masks), 10);
+
+    // the bishop that took on a7 can't get out anymore
+    let pos = pos::Position::from_fen("r3k3/B1p5/1p6/8/8/8/8/4K3 b - - 0 1", &zb);
+    assert_eq!(
+        pos.trapped_pieces(color::WHITE, &masks),
+        bb::square_bb(pos::str_to_sq("a7"))
+    );
+    assert_eq!(pos.trapped_pieces(color::BLACK, &masks), bb::EMPTY);
+
+    // with the c pawn gone, the bishop escapes by taking on b6
+    let pos = pos::Position::from_fen("r3k3/B7/1p6/8/8/8/8/4K3 b - - 0 1", &zb);
+    assert_eq!(pos.trapped_pieces(color::WHITE, &masks), bb::EMPTY);
+}
+
+#[test]
+fn test_shredder_and_xfen() {
+    let (masks, zb) = libchess::init();
+
+    let shredder = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+    let mut pos = pos::Position::from_fen(shredder, &zb);
+
+    assert_eq!(pos.to_shredder_fen(), shredder);
+    assert_eq!(
+        pos.to_fen(),
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9"
+    );
+    assert_eq!(perft::perft(&mut pos, 3, false, &masks, &zb), 12189);
+
+    // the king side castling rook isn't the outermost one, so X-FEN has to name its file
+    let xfen = "4k3/8/8/8/8/8/8/R2K1RR1 w FQ - 0 1";
+    let pos = pos::Position::from_fen(xfen, &zb);
+
+    assert_eq!(pos.castling_rook(pos::WK_CASTLE), pos::str_to_sq("f1"));
+    assert_eq!(pos.to_fen(), xfen);
+    assert_eq!(pos.to_shredder_fen(), "4k3/8/8/8/8/8/8/R2K1RR1 w FA - 0 1");
+    assert_eq!(pos::Position::from_fen(&pos.to_shredder_fen(), &zb), pos);
+}
+
+#[test]
+fn test_dataset_normalize() {
+    let (masks, zb) = libchess::init();
+
+    let input = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1 [0.5]
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 3 7 [1.0]
+
+rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3
+not a fen at all
+8/8/8/8/8/8/8/8 w - - 0 1
+";
+
+    let mut output = Vec::new();
+    let stats = dataset::normalize(
+        input.as_bytes(),
+        &mut output,
+        dataset::NormalizeOptions::default(),
+        &masks,
+        &zb,
+    )
+    .unwrap();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - [0.5]
+rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6
+"
+    );
+    assert_eq!(
+        stats,
+        dataset::NormalizeStats {
+            read: 5,
+            written: 2,
+            duplicates: 1,
+            invalid: 2,
+        }
+    );
+
+    let (line, _) = dataset::normalize_line(
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3 bm exf6;",
+        true,
+        &masks,
+        &zb,
+    )
+    .unwrap();
+    assert_eq!(
+        line,
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3 bm exf6;"
+    );
+
+    // a pawn on the back rank used to make the en passant check panic in move generation
+    assert_eq!(
+        dataset::normalize_line("4k2P/8/8/3pP3/8/8/8/4K3 w - d6 0 1", true, &masks, &zb),
+        None
+    );
+}
+
+#[test]
+fn test_validate() {
+    let (masks, zb) = libchess::init();
+
+    for (fen, result) in [
+        (pos::START_FEN, Ok(())),
+        (
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            Ok(()),
+        ),
+        (
+            "4k3/8/8/8/8/8/8/4K2K w - - 0 1",
+            Err(pos::InvalidPosition::KingCount(color::WHITE, 2)),
+        ),
+        (
+            "4k3/8/8/8/8/8/8/8 w - - 0 1",
+            Err(pos::InvalidPosition::KingCount(color::WHITE, 0)),
+        ),
+        (
+            "P3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            Err(pos::InvalidPosition::PawnOnBackRank(pos::str_to_sq("a8"))),
+        ),
+        (
+            "4k3/8/8/8/8/8/4P3/4K3 b - e3 0 1",
+            Err(pos::InvalidPosition::EnPassantSquare(pos::str_to_sq("e3"))),
+        ),
+        // from_fen drops castling rights without a rook
+        ("4k3/8/8/8/8/8/8/4KR2 w Q - 0 1", Ok(())),
+        (
+            "4k3/4Q3/8/8/8/8/8/4K3 w - - 0 1",
+            Err(pos::InvalidPosition::OpponentInCheck),
+        ),
+    ] {
+        assert_eq!(
+            pos::Position::from_fen(fen, &zb).validate(&masks),
+            result,
+            "{fen}"
+        );
+    }
+}
+
+#[test]
+fn test_mirror_and_flip() {
+    let (masks, zb) = libchess::init();
+
+    assert_eq!(bb::flip_vertical(bb::RANK_1_MASK), bb::RANK_8_MASK);
+    assert_eq!(bb::mirror_horizontal(bb::FILE_A_MASK), bb::FILE_H_MASK);
+
+    let start = pos::Position::from_fen(pos::START_FEN, &zb);
+    assert_eq!(
+        start.mirrored(&zb).to_fen(),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"
+    );
+
+    let kiwipete = pos::Position::from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        &zb,
+    );
+    let mut mirrored = kiwipete.mirrored(&zb);
+    assert_eq!(
+        mirrored.to_fen(),
+        "r3k2r/pppbbppp/2n2q1P/1P2p3/3pn3/BN2PNP1/P1PPQPB1/R3K2R b KQkq - 0 1"
+    );
+    assert_eq!(mirrored.key(), zobrist::hash(&mirrored, &zb));
+    assert_eq!(mirrored.mirrored(&zb).key(), kiwipete.key());
+
+    // the material of both sides is swapped too
+    let material =
+        pos::Position::from_fen("4k3/8/8/8/8/8/PPPP4/RN2K3 w - - 0 1", &zb).mirrored(&zb);
+    assert_eq!(
+        material.material_key(),
+        pos::Position::from_fen(&material.to_fen(), &zb).material_key()
+    );
+    assert_eq!(
+        pos::material_count(material.material_key(), piece::BLACK_ROOK),
+        1
+    );
+    assert_eq!(perft::perft(&mut mirrored, 3, false, &masks, &zb), 97862);
+
+    // mirroring keeps the en passant square pointing at the pawn that can be captured
+    let ep = pos::Position::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1", &zb);
+    assert_eq!(
+        ep.mirrored(&zb).to_fen(),
+        "4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1"
+    );
+    assert_eq!(
+        ep.flipped_horizontal(&zb).to_fen(),
+        "3k4/8/8/8/3Pp3/8/8/3K4 b - d3 0 1"
+    );
+
+    let mut flipped = kiwipete.flipped_horizontal(&zb);
+    assert_eq!(flipped.castle_rights(), pos::NO_CASTLING);
+    assert_eq!(flipped.key(), zobrist::hash(&flipped, &zb));
+    assert_eq!(
+        flipped.flipped_horizontal(&zb).to_fen(),
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1"
+    );
+    // without castling rights, mirroring doesn't change the number of moves
+    let mut no_castling = pos::Position::from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1",
+        &zb,
+    );
+    assert_eq!(
+        perft::perft(&mut flipped, 3, false, &masks, &zb),
+        perft::perft(&mut no_castling, 3, false, &masks, &zb)
+    );
+}
+
+#[test]
+fn test_castling_info() {
+    let (masks, zb) = libchess::init();
+    let sq = pos::str_to_sq;
+    let squares = |names: &[&str]| {
+        names
+            .iter()
+            .fold(bb::EMPTY, |b, &n| b | bb::square_bb(sq(n)))
+    };
+
+    let pos = pos::Position::from_fen(pos::START_FEN, &zb);
+    let info = pos.castling_info(pos::BQ_CASTLE, &masks).unwrap();
+
+    assert_eq!(
+        (info.king_from, info.king_to, info.rook_from, info.rook_to),
+        (sq("e8"), sq("c8"), sq("a8"), sq("d8"))
+    );
+    assert_eq!(info.king_path, squares(&["d8", "c8"]));
+    assert_eq!(info.rook_path, squares(&["b8", "c8", "d8"]));
+    assert_eq!(info.empty_squares(), squares(&["b8", "c8", "d8"]));
+
+    // chess960, king on b1 with the castling rook on a1
+    let pos = pos::Position::from_fen("4k3/8/8/8/8/8/8/RK5R w KQ - 0 1", &zb);
+    let info = pos.castling_info(pos::WQ_CASTLE, &masks).unwrap();
+
+    assert_eq!(
+        (info.king_from, info.king_to, info.rook_from, info.rook_to),
+        (sq("b1"), sq("c1"), sq("a1"), sq("d1"))
+    );
+    assert_eq!(info.king_path, squares(&["c1"]));
+    assert_eq!(info.empty_squares(), squares(&["c1", "d1"]));
+
+    assert_eq!(pos.castling_info(pos::BK_CASTLE, &masks), None);
+}
+
+#[test]
+fn test_adjudicator() {
+    use adjudication::{AdjudicationError, Adjudicator, AdjudicatorOptions, Event, Status};
+    use std::time::Duration;
+
+    let (masks, zb) = libchess::init();
+    let options = AdjudicatorOptions {
+        claims_required: true,
+        ..Default::default()
+    };
+
+    let mut game = Adjudicator::new(
+        pos::Position::from_fen(pos::START_FEN, &zb),
+        options,
+        &masks,
+        &zb,
+    );
+
+    for uci in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"] {
+        let mov = moves::Move::from_str_move(uci, game.position());
+        assert_eq!(
+            game.push(Event::Move(mov), &masks, &zb),
+            Ok(Status::Ongoing)
+        );
+    }
+
+    assert_eq!(
+        game.push(Event::ClaimDraw(color::BLACK), &masks, &zb),
+        Err(AdjudicationError::InvalidClaim)
+    );
+
+    let mov = moves::Move::from_str_move("f6g8", game.position());
+    assert_eq!(
+        game.push(Event::Move(mov), &masks, &zb),
+        Ok(Status::DrawClaimable(Termination::Repetition))
+    );
+
+    let mov = moves::Move::from_str_move("e2e5", game.position());
+    assert_eq!(
+        game.push(Event::Move(mov), &masks, &zb),
+        Err(AdjudicationError::IllegalMove(mov))
+    );
+
+    game.push(Event::ClaimDraw(color::WHITE), &masks, &zb)
+        .unwrap();
+    assert_eq!(game.verdict().map(|v| v.winner), Some(None));
+    assert_eq!(
+        game.push(Event::DrawAgreed, &masks, &zb),
+        Err(AdjudicationError::GameOver)
+    );
+
+    // running out of time against a bare king is a draw
+    let mut game = Adjudicator::new(
+        pos::Position::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1", &zb),
+        AdjudicatorOptions::default(),
+        &masks,
+        &zb,
+    );
+    let out_of_time = |side| Event::Clock {
+        side,
+        remaining: Duration::ZERO,
+    };
+
+    assert_eq!(
+        game.push(out_of_time(color::WHITE), &masks, &zb)
+            .map(|_| game.verdict().unwrap().termination),
+        Ok(Termination::InsufficientMaterial)
+    );
+
+    let mut game = Adjudicator::new(
+        pos::Position::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1", &zb),
+        AdjudicatorOptions::default(),
+        &masks,
+        &zb,
+    );
+    game.push(out_of_time(color::BLACK), &masks, &zb).unwrap();
+    assert_eq!(game.verdict().unwrap().winner, Some(color::WHITE));
+    assert_eq!(
+        game.verdict().unwrap().termination,
+        Termination::TimeForfeit
+    );
+}
+
+#[test]
+fn test_fivefold_and_75_moves() {
+    let (masks, zb) = libchess::init();
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    for i in 0..4 {
+        assert_eq!(pos.is_3_rep(), i >= 2);
+        assert!(!pos.is_5_rep());
+
+        for uci in shuffle {
+            pos.make_move(moves::Move::from_str_move(uci, &pos), &zb);
+        }
+    }
+
+    assert!(pos.is_5_rep());
+    assert_eq!(pos.repetition_count(), 5);
+
+    // a single repetition after the root of a search, but not before it
+    let root = pos.ply();
+    assert!(!pos.has_repeated_once_since_root(root));
+
+    for uci in ["g1f3", "g8f6"] {
+        pos.make_move(moves::Move::from_str_move(uci, &pos), &zb);
+    }
+
+    assert!(!pos.has_repeated_once_since_root(root));
+    assert!(pos.has_repeated_once_since_root(root - 4));
+
+    for uci in ["f3g1", "f6g8"] {
+        pos.make_move(moves::Move::from_str_move(uci, &pos), &zb);
+    }
+
+    assert!(pos.has_repeated_once_since_root(root));
+    assert_eq!(pos.repetition_count(), 6);
+
+    // a pawn move resets everything
+    pos.make_move(moves::Move::from_str_move("e2e4", &pos), &zb);
+    assert_eq!(pos.repetition_count(), 1);
+
+    let pos = pos::Position::from_fen("8/8/4k3/8/8/3RK3/8/8 b - - 149 120", &zb);
+    assert!(!pos.is_75_move_rule());
+
+    let mut game = adjudication::Adjudicator::new(
+        pos,
+        adjudication::AdjudicatorOptions {
+            claims_required: true,
+            ..Default::default()
+        },
+        &masks,
+        &zb,
+    );
+    assert_eq!(
+        game.status(),
+        adjudication::Status::DrawClaimable(Termination::FiftyMoveRule)
+    );
+
+    let mov = moves::Move::from_str_move("e6e7", game.position());
+    game.push(adjudication::Event::Move(mov), &masks, &zb)
+        .unwrap();
+    assert!(game.position().is_75_move_rule());
+    assert_eq!(
+        game.verdict().map(|v| v.termination),
+        Some(Termination::FiftyMoveRule)
+    );
+}
+
+#[test]
+fn test_square_table() {
+    let sq = pos::str_to_sq;
+    let mut table = SquareTable::from_fn(|square| pos::rank_of(square) as i32 * 10);
+
+    table[sq("e4")] = -5;
+
+    assert_eq!(table[sq("a8")], 70);
+    assert_eq!(table.mirrored()[sq("e5")], -5);
+    assert_eq!(table.mirrored().mirrored(), table);
+    assert_eq!(table.rank(pos::RANK_4)[pos::FILE_E as usize], -5);
+    assert_eq!(table.ranks().next_back(), Some(&[70; 8]));
+
+    let text = table.to_string();
+    assert_eq!(text.lines().next(), Some("8  70 70 70 70 70 70 70 70"));
+    assert_eq!(text.lines().nth(4), Some("4  30 30 30 30 -5 30 30 30"));
+    assert_eq!(text.lines().last(), Some("   a  b  c  d  e  f  g  h"));
+
+    let table = SquareTable::new(0.5);
+    assert!(format!("{table:.2}").starts_with("8  0.50 0.50"));
+}
+
+#[test]
+fn test_unmake_restores_position() {
+    let (masks, zb) = libchess::init();
+
+    fn check(
+        pos: &mut pos::Position,
+        depth: usize,
+        masks: &libchess::AttackMasks,
+        zb: &libchess::ZobristValues,
+    ) {
+        if depth == 0 {
+            return;
+        }
+
+        for &mov in moves::gen_legal(pos, masks, zb).iter() {
+            let before = pos.clone();
+
+            pos.make_move(mov, zb);
+            check(pos, depth - 1, masks, zb);
+            pos.unmake_move();
+
+            assert_eq!(*pos, before, "{}", mov.to_uci_fmt());
+        }
+    }
+
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9",
+    ] {
+        check(&mut pos::Position::from_fen(fen, &zb), 3, &masks, &zb);
+    }
+
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+    for uci in ["e2e4", "d7d5", "e4d5", "g8f6"] {
+        pos.make_move(moves::Move::from_str_move(uci, &pos), &zb);
+    }
+
+    assert_eq!(pos.history().len(), 4);
+    assert_eq!(
+        pos.history_i()[2].move_played.map(|m| m.to_uci_fmt()),
+        Some("d7d5".to_string())
+    );
+    assert_eq!(
+        pos.from_ply(3).to_fen(),
+        "rnbqkbnr/ppp1pppp/8/3P4/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2"
+    );
+
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+    for uci in [
+        "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8", "e2e4",
+    ] {
+        pos.make_move(moves::Move::from_str_move(uci, &pos), &zb);
+    }
+
+    // the starting position occurred for the third time at ply 8
+    assert!(pos.from_ply(8).is_3_rep());
+    assert_eq!(pos.from_ply(8).history().len(), 8);
+    assert_eq!(
+        pos.from_ply_with(8, pos::BranchHistory::Clear)
+            .repetition_count(),
+        1
+    );
+
+    let mut full = pos.from_ply_with(8, pos::BranchHistory::Full);
+    assert_eq!(full.history().len(), 8);
+    while !full.history().is_empty() {
+        full.unmake_move();
+    }
+    assert_eq!(full.to_fen(), pos::START_FEN);
+}
+
+#[test]
+fn test_suggest_repairs() {
+    let (masks, zb) = libchess::init();
+
+    // black is to move but gives check, with a stale en passant square,
+    // and a castling right while the king is off the back rank
+    let mut pos = pos::Position::from_fen("4k3/8/8/8/8/8/r3K3/7R b H e3 0 1", &zb);
+    let repairs = pos.suggest_repairs(&masks, &zb).unwrap();
+
+    assert_eq!(
+        repairs,
+        vec![
+            pos::Repair::ClearEnPassant,
+            pos::Repair::DropCastlingRight(pos.castle_rights()),
+            pos::Repair::SwitchSideToMove,
+        ]
+    );
+
+    pos.apply_repairs(&repairs, &zb);
+    assert_eq!(pos.validate(&masks), Ok(()));
+    assert_eq!(pos.key(), zobrist::hash(&pos, &zb));
+    assert_eq!(pos.to_fen(), "4k3/8/8/8/8/8/r3K3/7R w - - 0 1");
+
+    let pos = pos::Position::from_fen("4k3/8/8/8/8/8/8/8 w - - 0 1", &zb);
+    assert_eq!(
+        pos.suggest_repairs(&masks, &zb),
+        Err(pos::InvalidPosition::KingCount(color::WHITE, 0))
+    );
+}
+
+#[test]
+fn test_game_log() {
+    use adjudication::{AdjudicatorOptions, Event};
+    use game_log::{GameLog, LogEntry};
+    use std::time::Duration;
+
+    let (masks, zb) = libchess::init();
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+    let mut log = GameLog::new(pos::START_FEN);
+    let mut file = Vec::new();
+
+    GameLog::write_header(&mut file, pos::START_FEN).unwrap();
+
+    for (i, uci) in ["f2f3", "e7e5", "g2g4", "d8h4"].iter().enumerate() {
+        let mov = moves::Move::from_str_move(uci, &pos);
+        pos.make_move(mov, &zb);
+
+        let entry = LogEntry {
+            elapsed: Duration::from_millis(1000 * i as u64),
+            event: Event::Move(mov),
+        };
+
+        GameLog::write_entry(&mut file, &entry).unwrap();
+        log.push(entry.elapsed, entry.event);
+    }
+
+    let text = String::from_utf8(file).unwrap();
+    assert_eq!(text, log.to_string());
+    assert!(text.ends_with("2000 move g2g4\n3000 move d8h4\n"));
+
+    // a line cut off while writing is ignored
+    let parsed = GameLog::parse(&(text.clone() + "4000 resi"), &masks, &zb).unwrap();
+    assert_eq!(parsed, log);
+
+    let game = parsed
+        .replay(AdjudicatorOptions::default(), &masks, &zb)
+        .unwrap();
+    assert_eq!(game.position().to_fen(), pos.to_fen());
+    assert_eq!(
+        game.verdict().map(|v| (v.winner, v.termination)),
+        Some((Some(color::BLACK), Termination::Checkmate))
+    );
+
+    let text = "fen 4k3/8/8/8/8/8/8/R3K3 w - - 0 1\n10 clock b 0\n20 move a1a8\n";
+    let log = GameLog::parse(text, &masks, &zb).unwrap();
+    assert_eq!(log.to_string(), text);
+    assert!(
+        log.replay(AdjudicatorOptions::default(), &masks, &zb)
+            .is_err()
+    );
+
+    assert_eq!(
+        GameLog::parse(
+            "fen 4k3/8/8/8/8/8/8/R3K3 w - - 0 1\n10 move a1a2\n20 move a2a1\n",
+            &masks,
+            &zb
+        ),
+        Err(game_log::ParseGameLogError::IllegalMove(3))
+    );
+
+    // corrupted logs are errors, not panics
+    for (text, err) in [
+        ("fen garbage\n", game_log::ParseGameLogError::InvalidFen),
+        (
+            "fen 4k3/8/8/8/8/8/8/R3K3 w - - 0 1\n0 move z9z9\n",
+            game_log::ParseGameLogError::IllegalMove(2),
+        ),
+    ] {
+        assert_eq!(GameLog::parse(text, &masks, &zb), Err(err));
+    }
+
+    assert_eq!(
+        GameLog::new("garbage")
+            .replay(AdjudicatorOptions::default(), &masks, &zb)
+            .err(),
+        Some(adjudication::AdjudicationError::InvalidFen)
+    );
+}
+
+#[test]
+fn test_material_key() {
+    let (_, zb) = libchess::init();
+
+    let krk = pos::material_key_from_str("KRvK").unwrap();
+    let pos = pos::Position::from_fen("8/8/4k3/8/8/3RK3/8/8 b - - 0 1", &zb);
+
+    assert_eq!(pos.material_key(), krk);
+    assert_eq!(
+        pos.material_key(),
+        pos::Position::from_fen("R7/8/8/8/8/8/8/k3K3 w - - 0 1", &zb).material_key()
+    );
+    assert_eq!(pos::material_count(krk, piece::WHITE_ROOK), 1);
+    assert_eq!(pos::material_count(krk, piece::BLACK_ROOK), 0);
+    assert_eq!(pos::material_key_from_str("KXvK"), None);
+    assert_eq!(pos::material_key_from_str("KRK"), None);
+
+    let start = pos::Position::from_fen(pos::START_FEN, &zb);
+    assert_eq!(
+        pos::material_count(start.material_key(), piece::BLACK_PAWN),
+        8
+    );
+
+    // promotions and captures keep the key up to date, and unmaking restores it
+    let mut pos = pos::Position::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", &zb);
+    let before = pos.material_key();
+
+    pos.make_move(moves::Move::from_str_move("a7b8q", &pos), &zb);
+    assert_eq!(
+        pos.material_key(),
+        pos::material_key_from_str("KQvK").unwrap()
+    );
+    pos.unmake_move();
+    assert_eq!(pos.material_key(), before);
+}
+
+#[test]
+fn test_similarity() {
+    let (_, zb) = libchess::init();
+
+    let start = pos::Position::from_fen(pos::START_FEN, &zb);
+    assert_eq!(
+        similarity::distance(&start, &start),
+        similarity::Distance::default()
+    );
+
+    // after 1. e4 only the e pawn is somewhere else
+    let e4 = pos::Position::from_fen(
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+        &zb,
+    );
+    let distance = similarity::distance(&start, &e4);
+    assert_eq!(
+        (distance.pawns, distance.pieces, distance.material),
+        (2, 0, 0)
+    );
+    assert_eq!(distance.total(), 2 * similarity::PAWN_WEIGHT);
+
+    // no piece stands on the same square in both positions
+    let pawns = pos::Position::from_fen("4k3/8/8/8/8/8/PPPPPPPP/4K3 w - - 0 1", &zb);
+    let rook = pos::Position::from_fen("k7/8/8/8/8/8/8/R6K w - - 0 1", &zb);
+    let distance = similarity::distance(&pawns, &rook);
+    assert_eq!(
+        (distance.pawns, distance.pieces, distance.material),
+        (8, 5, 3)
+    );
+
+    let mut index = similarity::SimilarityIndex::new();
+    for pos in [&rook, &e4, &start] {
+        index.push(pos);
+    }
+
+    let similar = index.find_similar(&start, 2);
+    assert_eq!(
+        similar.iter().map(|&(i, _)| i).collect::<Vec<_>>(),
+        vec![2, 1]
+    );
+    assert_eq!(similar[0].1.total(), 0);
+}
+
+#[test]
+fn test_draw_offers() {
+    use adjudication::{AdjudicationError, Adjudicator, AdjudicatorOptions, Event};
+
+    let (masks, zb) = libchess::init();
+    let mut game = Adjudicator::new(
+        pos::Position::from_fen(pos::START_FEN, &zb),
+        AdjudicatorOptions::default(),
+        &masks,
+        &zb,
+    );
+    let play = |game: &mut Adjudicator, uci| {
+        let mov = moves::Move::from_str_move(uci, game.position());
+        game.push(Event::Move(mov), &masks, &zb).unwrap();
+    };
+
+    // white moves and then offers, black declines by moving
+    play(&mut game, "e2e4");
+    game.push(Event::OfferDraw(color::WHITE), &masks, &zb)
+        .unwrap();
+    assert_eq!(game.draw_offer(), Some(color::WHITE));
+    play(&mut game, "e7e5");
+    assert_eq!(game.draw_offer(), None);
+
+    assert_eq!(
+        game.push(Event::AcceptDraw(color::BLACK), &masks, &zb),
+        Err(AdjudicationError::NoDrawOffer)
+    );
+
+    // an offer made before moving stays open
+    game.push(Event::OfferDraw(color::WHITE), &masks, &zb)
+        .unwrap();
+    play(&mut game, "g1f3");
+    assert_eq!(game.draw_offer(), Some(color::WHITE));
+
+    game.push(Event::DeclineDraw(color::BLACK), &masks, &zb)
+        .unwrap();
+    assert_eq!(game.draw_offer(), None);
+
+    game.push(Event::OfferDraw(color::BLACK), &masks, &zb)
+        .unwrap();
+    game.push(Event::AcceptDraw(color::WHITE), &masks, &zb)
+        .unwrap();
+    assert_eq!(
+        game.verdict().map(|v| (v.winner, v.termination)),
+        Some((None, Termination::Agreement))
+    );
+}
+
+#[test]
+fn test_strength_limit() {
+    use libchess::rand::{SeedableRng, rngs::StdRng};
+    use uci::strength::{self, EloRange, StrengthLimit};
+
+    let weak = strength::handicap_for_elo(800);
+    let strong = strength::handicap_for_elo(2200);
+    assert!(weak.nodes < strong.nodes && weak.depth < strong.depth);
+    assert!(weak.max_cp_loss > strong.max_cp_loss);
+    assert_eq!(strength::handicap_for_elo(3000).max_cp_loss, 0);
+
+    let range =
+        strength::parse_elo_option("option name UCI_Elo type spin default 1350 min 1320 max 3190");
+    assert_eq!(
+        range,
+        Some(EloRange {
+            min: 1320,
+            max: 3190
+        })
+    );
+    assert_eq!(
+        strength::limit_for_elo(1500, range),
+        StrengthLimit::UciElo(1500)
+    );
+    assert_eq!(
+        strength::limit_for_elo(1000, range),
+        StrengthLimit::Handicap(strength::handicap_for_elo(1000))
+    );
+
+    let lines = [
+        "info depth 8 multipv 1 score cp 50 pv e2e4 e7e5",
+        "info depth 8 multipv 2 score cp 30 pv d2d4 d7d5",
+        "info depth 8 multipv 3 score cp -200 pv g2g4 e7e5",
+        "info depth 8 multipv 4 score mate -3 pv f2f3 e7e5",
+    ]
+    .iter()
+    .map(|line| strength::parse_pv_line(line).unwrap())
+    .collect::<Vec<strength::PvLine>>();
+
+    assert_eq!(lines[3].score, -29997);
+
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..20 {
+        let line = strength::pick_line(&lines, 50, &mut rng).unwrap();
+        assert!(["e2e4", "d2d4"].contains(&line.first_move.as_str()));
+    }
+
+    #[cfg(unix)]
+    {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_engine.sh");
+        let (masks, zb) = libchess::init();
+        let pos = pos::Position::from_fen(pos::START_FEN, &zb);
+        let limit = StrengthLimit::Handicap(weak);
+
+        let mut engine = uci::Engine::new(path).unwrap();
+        engine.set_strength(limit).unwrap();
+        engine
+            .request_limited_move(&pos, pos::START_FEN, 1000, 1000, limit)
+            .unwrap();
+        let mov = engine
+            .get_limited_move(&pos, limit, &mut rng, &masks, &zb)
+            .unwrap();
+        assert_eq!(mov.map(|m| m.to_uci_fmt()), Some("e2e4".to_string()));
+
+        // the fake engine always plays e2e4, which isn't legal here
+        let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1";
+        let pos = pos::Position::from_fen(fen, &zb);
+        engine
+            .request_limited_move(&pos, fen, 1000, 1000, limit)
+            .unwrap();
+        let err = engine
+            .get_limited_move(&pos, limit, &mut rng, &masks, &zb)
+            .unwrap_err();
+        assert_eq!(
+            std::io::Error::from(err).kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+}
+
+#[test]
+fn test_try_make_move() {
+    let (masks, zb) = libchess::init();
+    let sq = pos::str_to_sq;
+    let mut pos = pos::Position::from_fen("4k3/8/8/8/8/4r3/3P4/4K2R w K - 0 1", &zb);
+    let fen = pos.to_fen();
+
+    for (mov, err) in [
+        (
+            moves::Move::new(sq("a1"), sq("a2"), moves::MoveType::Normal),
+            pos::IllegalMove::NoPiece(sq("a1")),
+        ),
+        (
+            moves::Move::new(sq("e3"), sq("e2"), moves::MoveType::Normal),
+            pos::IllegalMove::NoPiece(sq("e3")),
+        ),
+        (
+            moves::Move::new(sq("d2"), sq("e3"), moves::MoveType::Normal),
+            pos::IllegalMove::WrongMoveType(moves::MoveType::Capture(piece::BLACK_ROOK)),
+        ),
+        (
+            moves::Move::new(sq("h1"), sq("a8"), moves::MoveType::Normal),
+            pos::IllegalMove::Unreachable,
+        ),
+        (
+            moves::Move::new(sq("e1"), sq("g1"), moves::MoveType::KingSideCastle),
+            pos::IllegalMove::Unreachable,
+        ),
+        (
+            moves::Move::new(sq("d2"), sq("d3"), moves::MoveType::Normal),
+            pos::IllegalMove::LeavesKingInCheck,
+        ),
+    ] {
+        assert_eq!(
+            pos.try_make_move(mov, &masks, &zb),
+            Err(err),
+            "{}",
+            mov.to_uci_fmt()
+        );
+        assert_eq!(pos.to_fen(), fen);
+    }
+
+    let capture = moves::Move::new(
+        sq("d2"),
+        sq("e3"),
+        moves::MoveType::Capture(piece::BLACK_ROOK),
+    );
+    assert_eq!(pos.try_make_move(capture, &masks, &zb), Ok(()));
+    assert_eq!(pos.to_fen(), "4k3/8/8/8/8/4P3/8/4K2R b K - 0 1");
+}
+
+#[test]
+fn test_batch_attacks() {
+    let (masks, _) = libchess::init();
+
+    // one bitboard per square, and one with two pieces that also isn't part of a full simd vector
+    let mut pieces = (0..64).map(bb::square_bb).collect::<Vec<bb::Bitboard>>();
+    pieces.push(bb::square_bb(0) | bb::square_bb(63));
+
+    let expected = |f: &dyn Fn(pos::Square) -> bb::Bitboard| {
+        (0..64)
+            .map(f)
+            .chain(std::iter::once(f(0) | f(63)))
+            .collect::<Vec<bb::Bitboard>>()
+    };
+
+    let mut attacks = vec![bb::EMPTY; pieces.len()];
+
+    bb::batch::knight_attacks(&pieces, &mut attacks);
+    assert_eq!(attacks, expected(&|sq| masks.knight_attacks(sq)));
+
+    bb::batch::king_attacks(&pieces, &mut attacks);
+    assert_eq!(attacks, expected(&|sq| masks.king_attacks(sq)));
+
+    for occupied in [bb::EMPTY, 0xffff00000000ffff, 0x0042180024008100] {
+        let occupied = occupied | pieces[64];
+
+        bb::batch::rook_attacks(&pieces, occupied, &mut attacks);
+        assert_eq!(attacks, expected(&|sq| masks.rook_attacks_rt(sq, occupied)));
+
+        bb::batch::bishop_attacks(&pieces, occupied, &mut attacks);
+        assert_eq!(
+            attacks,
+            expected(&|sq| masks.bishop_attacks_rt(sq, occupied))
+        );
+
+        bb::batch::queen_attacks(&pieces, occupied, &mut attacks);
+        assert_eq!(
+            attacks,
+            expected(&|sq| masks.queen_attacks_rt(sq, occupied))
+        );
+
+        let mut counts = vec![0; attacks.len()];
+        bb::batch::popcounts(&attacks, !occupied, &mut counts);
+        assert_eq!(
+            counts,
+            attacks
+                .iter()
+                .map(|a| (a & !occupied).count_ones())
+                .collect::<Vec<u32>>()
+        );
+        assert_eq!(
+            bb::batch::popcount_sum(&attacks, !occupied),
+            counts.iter().sum::<u32>()
+        );
+    }
+}
+
+#[test]
+fn test_piece_lists() {
+    let (_, zb) = libchess::init();
+    let pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    assert_eq!(pos.piece_count(piece::WHITE_PAWN), 8);
+    assert_eq!(pos.piece_count(piece::BLACK_QUEEN), 1);
+    assert_eq!(
+        pos.pieces(piece::BLACK_KNIGHT)
+            .collect::<Vec<pos::Square>>(),
+        vec![pos::str_to_sq("b8"), pos::str_to_sq("g8")]
+    );
+    assert_eq!(pos.king_square(color::WHITE), Some(pos::str_to_sq("e1")));
+    assert_eq!(pos.king_square(color::BLACK), Some(pos::str_to_sq("e8")));
+    assert_eq!(pos::Position::blank().king_square(color::WHITE), None);
+}
+
+#[test]
+fn test_bytes() {
+    let (_, zb) = libchess::init();
+
+    for fen in [
+        pos::START_FEN,
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        "8/8/4k3/8/2p5/8/3P4/4K3 b - d3 57 300",
+    ] {
+        let pos = pos::Position::from_fen(fen, &zb);
+        let decoded = pos::Position::from_bytes(&pos.to_bytes(), &zb).unwrap();
+
+        assert_eq!(decoded, pos);
+        assert_eq!(decoded.key(), pos.key());
+        assert_eq!(decoded.material_key(), pos.material_key());
+    }
+
+    let mut bytes = pos::Position::from_fen(pos::START_FEN, &zb).to_bytes();
+    bytes[8] |= 0x7;
+    assert_eq!(pos::Position::from_bytes(&bytes, &zb), None);
+}
+
+#[test]
+fn test_testpos() {
+    let (masks, zb) = libchess::init();
+
+    for test in testpos::all() {
+        let mut pos = test.position(&zb);
+
+        assert_eq!(pos.is_check(&masks), test.in_check, "{}", test.name);
+        assert_eq!(pos.outcome(&masks, &zb), test.outcome, "{}", test.name);
+
+        // only the shallow depths, the deeper ones take too long for a debug build
+        for (depth, &nodes) in test
+            .perft
+            .iter()
+            .enumerate()
+            .take_while(|(_, n)| **n < 50000)
+        {
+            assert_eq!(
+                perft::perft(&mut pos, depth as i32 + 1, false, &masks, &zb) as u64,
+                nodes,
+                "{} depth {}",
+                test.name,
+                depth + 1
+            );
+        }
+    }
+}
+
+#[test]
+fn test_dfrc() {
+    let (masks, zb) = libchess::init();
+
+    assert_eq!(chess960::fen(chess960::STANDARD_INDEX), pos::START_FEN);
+    assert_eq!(
+        chess960::back_rank(0).map(piece::as_char),
+        ['b', 'b', 'q', 'n', 'n', 'r', 'k', 'r']
+    );
+
+    let mut seen = std::collections::HashSet::new();
+    for index in 0..chess960::NUM_POSITIONS {
+        assert!(seen.insert(chess960::back_rank(index)));
+    }
+
+    for (white, black) in [(0, 959), (518, 105), (300, 301)] {
+        let fen = chess960::dfrc_fen(white, black);
+        let pos = pos::Position::from_fen(&fen, &zb);
+
+        assert_eq!(pos.to_fen(), fen);
+        assert_eq!(chess960::start_indices(&pos), Some((white, black)));
+        assert_eq!(
+            pos::Position::from_fen(&pos.to_shredder_fen(), &zb).to_fen(),
+            fen
+        );
+
+        // only kings and rooks on the back ranks, so both sides can castle right away (unless a rook
+        // is in the way of the other),
+        // and the position with colors swapped has to have the same perft results
+        let (board, rest) = fen.split_once(' ').unwrap();
+        let board = board
+            .chars()
+            .map(|ch| match ch {
+                'b' | 'n' | 'q' | 'B' | 'N' | 'Q' => '1',
+                ch => ch,
+            })
+            .collect::<String>();
+        let open = format!("{board} {rest}");
+        let mut pos = pos::Position::from_fen(&open, &zb);
+        let mut mirrored = pos.mirrored(&zb);
+
+        assert_eq!(
+            perft::perft(&mut pos, 3, false, &masks, &zb),
+            perft::perft(&mut mirrored, 3, false, &masks, &zb)
+        );
+        assert!(
+            moves::gen_legal(&mut pos, &masks, &zb)
+                .iter()
+                .any(|m| matches!(
+                    m.type_of(),
+                    moves::MoveType::KingSideCastle | moves::MoveType::QueenSideCastle
+                ))
+        );
+    }
+}
+
+#[test]
+fn test_reporters() {
+    let (masks, zb) = libchess::init();
+
+    let path = std::env::temp_dir().join("libchess_test_reporters.epd");
+    std::fs::write(
+        &path,
+        "4k3/8/8/8/8/8/8/4K2R w K - 0 1 ;D1 15 ;D2 66\n8/8/8/8/8/8/8/K1k5 w - - 0 1 ;D1 1\n",
+    )
+    .unwrap();
+    let path = path.to_str().unwrap();
+
+    let mut events = Vec::new();
+    perft::test_epd_with(path, 2, 10, 0, &masks, &zb, &mut |event: &report::Event| {
+        if let report::Event::PerftResult { actual, .. } = *event {
+            events.push(actual);
+        }
+    });
+    assert_eq!(events, vec![15, 66, 1]);
+
+    let mut json = report::JsonLinesReporter::new(Vec::new());
+    perft::test_epd_with(path, 1, 1, 0, &masks, &zb, &mut json);
+    assert_eq!(
+        String::from_utf8(json.into_inner().unwrap()).unwrap(),
+        concat!(
+            "{\"event\":\"perft_position\",\"fen\":\"4k3/8/8/8/8/8/8/4K2R w K - 0 1\"}\n",
+            "{\"event\":\"perft_result\",\"fen\":\"4k3/8/8/8/8/8/8/4K2R w K - 0 1\",",
+            "\"depth\":1,\"expected\":15,\"actual\":15}\n",
+            "{\"event\":\"perft_suite_finished\",\"passed\":1,\"failed\":0}\n",
+        )
+    );
+}
+
+#[test]
+fn test_key_after() {
+    let (masks, zb) = libchess::init();
+
+    fn check(
+        pos: &mut pos::Position,
+        depth: u32,
+        masks: &libchess::AttackMasks,
+        zb: &libchess::ZobristValues,
+    ) {
+        if depth == 0 {
+            return;
+        }
+
+        for &mov in moves::gen_legal(pos, masks, zb).iter() {
+            let expected = pos.key_after(mov, zb);
+
+            pos.make_move(mov, zb);
+            assert_eq!(pos.key(), expected, "{}", mov.to_uci_fmt());
+            check(pos, depth - 1, masks, zb);
+            pos.unmake_move();
+        }
+    }
+
+    for test in [
+        testpos::kiwipete(),
+        testpos::position_4(),
+        testpos::castling_rights_lost(),
+        testpos::ep_discovered_check(),
+    ] {
+        check(&mut test.position(&zb), 3, &masks, &zb);
+    }
+
+    let mut pos = pos::Position::from_fen(
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        &zb,
+    );
+    check(&mut pos, 3, &masks, &zb);
+}
+
+#[test]
+fn test_from_pieces() {
+    let (_, zb) = libchess::init();
+    let sq = pos::str_to_sq;
+
+    let pos = pos::Position::from_pieces(
+        [
+            (sq("e1"), piece::WHITE_KING),
+            (sq("h1"), piece::WHITE_ROOK),
+            (sq("e8"), piece::BLACK_KING),
+            (sq("d4"), piece::BLACK_PAWN),
+            (sq("e4"), piece::WHITE_PAWN),
+        ],
+        color::BLACK,
+        pos::WK_CASTLE | pos::WQ_CASTLE,
+        Some(sq("e3")),
+        &zb,
+    );
+
+    let fen = "4k3/8/8/8/3pP3/8/8/4K2R b K e3 0 1";
+    assert_eq!(pos.to_fen(), fen);
+    assert_eq!(pos, pos::Position::from_fen(fen, &zb));
+    assert_eq!(pos.key(), pos::Position::from_fen(fen, &zb).key());
+
+    // without the pawn on d4 nothing can capture en passant
+    let pos = pos::Position::from_pieces(
+        [
+            (sq("e1"), piece::WHITE_KING),
+            (sq("e8"), piece::BLACK_KING),
+            (sq("e4"), piece::WHITE_PAWN),
+        ],
+        color::BLACK,
+        pos::NO_CASTLING,
+        Some(sq("e3")),
+        &zb,
+    );
+    assert_eq!(pos.ep_square(), None);
+}
+
+#[test]
+fn test_san() {
+    let (masks, zb) = libchess::init();
+
+    for (fen, uci, san) in [
+        (pos::START_FEN, "g1f3", "Nf3"),
+        (pos::START_FEN, "e2e4", "e4"),
+        ("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1", "b1d2", "Nbd2"),
+        ("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3", "R1a3"),
+        ("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a1b2", "Qa1b2"),
+        (
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "e5f6",
+            "exf6",
+        ),
+        (
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+            "e4d5",
+            "exd5",
+        ),
+        ("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", "b7b8q", "b8=Q+"),
+        ("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", "b7b8n", "b8=N"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8", "O-O-O"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1", "O-O"),
+        (
+            "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2",
+            "d8h4",
+            "Qh4#",
+        ),
+    ] {
+        let mut pos = pos::Position::from_fen(fen, &zb);
+        let mov = moves::Move::from_str_move(uci, &pos);
+
+        assert_eq!(mov.to_san(&mut pos, &masks, &zb), san);
+        assert_eq!(moves::Move::from_san(san, &mut pos, &masks, &zb), Ok(mov));
+        assert_eq!(pos.to_fen(), fen);
+    }
+
+    let mut pos = pos::Position::from_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1", &zb);
+    for (san, result) in [
+        ("Nbd2!?", Ok("b1d2")),
+        ("N1d2", Ok("b1d2")),
+        ("Nb1xd2", Ok("b1d2")),
+        ("Nd2", Err(moves::SanError::Ambiguous)),
+        ("Nd3", Err(moves::SanError::NoMatch)),
+        ("O-O", Err(moves::SanError::NoMatch)),
+        ("Nz9", Err(moves::SanError::Syntax)),
+        ("", Err(moves::SanError::Syntax)),
+    ] {
+        assert_eq!(
+            moves::Move::from_san(san, &mut pos, &masks, &zb).map(|m| m.to_uci_fmt()),
+            result.map(String::from),
+            "{san}"
+        );
+    }
+}
+
+#[test]
+fn test_export_pgn() {
+    let (masks, zb) = libchess::init();
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    for mov in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+        pos.make_move(moves::Move::from_str_move(mov, &pos), &zb);
+    }
+
+    let pgn = pgn::export_pgn(
+        &pos,
+        &[("White", "a \"b\""), ("Annotator", "c")],
+        &masks,
+        &zb,
+    );
+
+    assert_eq!(
+        pgn,
+        "[Event \"?\"]\n[Site \"?\"]\n[Date \"?\"]\n[Round \"?\"]\n[White \"a \\\"b\\\"\"]\n\
+         [Black \"?\"]\n[Result \"0-1\"]\n[Termination \"normal\"]\n[Annotator \"c\"]\n\n\
+         1. f3 e5 2. g4 Qh4# 0-1\n"
+    );
+
+    // a termination given by the caller wins over the one from the position
+    let pgn = pgn::export_pgn(&pos, &[("Termination", "time forfeit")], &masks, &zb);
+    assert_eq!(pgn.matches("[Termination ").count(), 1);
+    assert!(pgn.contains("[Termination \"time forfeit\"]\n"));
+
+    let mut pos = pos::Position::from_fen(testpos::kiwipete().fen, &zb);
+    pos.make_move(moves::Move::from_str_move("e1g1", &pos), &zb);
+    pos.make_move(moves::Move::from_str_move("e8c8", &pos), &zb);
+    pos.make_move(moves::Move::from_str_move("e5f7", &pos), &zb);
+
+    let pgn = pgn::export_pgn(&pos, &[], &masks, &zb);
+
+    assert!(!pgn.contains("[Termination "));
+    assert!(pgn.contains("[SetUp \"1\"]\n"));
+    assert!(pgn.contains(&format!("[FEN \"{}\"]\n", testpos::kiwipete().fen)));
+    assert!(pgn.ends_with("\n1. O-O O-O-O 2. Nxf7 *\n"));
+
+    // the history of a position from a ply only goes back to the last irreversible move
+    let branch = pos.from_ply(3);
+    assert!(branch.history().is_empty());
+
+    let pgn = pgn::export_pgn(&branch, &[], &masks, &zb);
+    assert!(pgn.contains(&format!("[FEN \"{}\"]\n", branch.to_fen())));
+    assert!(pgn.ends_with("\n\n*\n"));
+}
+
+#[test]
+fn test_termination() {
+    for (termination, tag, by_rules) in [
+        (Termination::Checkmate, "normal", true),
+        (Termination::FiftyMoveRule, "normal", true),
+        (Termination::VariantWin, "normal", true),
+        (Termination::Resignation, "normal", false),
+        (Termination::Agreement, "normal", false),
+        (Termination::TimeForfeit, "time forfeit", false),
+        (Termination::AdjudicatedTablebase, "adjudication", false),
+        (Termination::Abandonment, "abandoned", false),
+        (Termination::RulesInfraction, "rules infraction", false),
+    ] {
+        assert_eq!(termination.pgn_tag(), tag, "{termination}");
+        assert_eq!(termination.is_by_rules(), by_rules, "{termination}");
+    }
+}
+
+#[test]
+fn test_parse_pgn() {
+    let (masks, zb) = libchess::init();
+
+    let pgn = r#"% exported by hand
+[Event "a \"quoted\" event"]
+[White "w"]
+[Result "1-0"]
+
+{start} 1. e4 $1 e5 (1... c5 2. Nf3 (2. c3) d6 {sicilian}) 2.Nf3 Nc6!? ; line comment
+3. Bb5 a6 4... 1-0
+
+[Event "broken"]
+
+1. e4 e4 *
+
+[FEN "4k3/8/8/8/8/8/8/R3K3 b Q - 0 40"]
+
+40... Kd7 41. O-O-O+ *
+"#;
+
+    let games = pgn::games(pgn, &masks, &zb).collect::<Vec<_>>();
+    assert_eq!(games.len(), 3);
+
+    let game = games[0].as_ref().unwrap();
+    assert_eq!(game.tag("Event"), Some("a \"quoted\" event"));
+    assert_eq!(game.result, "1-0");
+    assert_eq!(game.mainline.comments, ["start"]);
+
+    let sans = game.mainline.moves.iter().map(|node| node.san.as_str());
+    assert_eq!(
+        sans.collect::<Vec<_>>(),
+        ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]
+    );
+
+    let e5 = &game.mainline.moves[1];
+    assert_eq!(game.mainline.moves[0].nags, [1]);
+    assert_eq!(e5.variations.len(), 1);
+    assert_eq!(e5.variations[0].moves.len(), 3);
+    assert_eq!(e5.variations[0].moves[1].variations[0].moves[0].san, "c3");
+    assert_eq!(e5.variations[0].moves[2].comments, ["sicilian"]);
+    assert_eq!(game.mainline.moves[3].nags, [5]);
+    assert_eq!(game.mainline.moves[3].comments, ["line comment"]);
+
+    let pos = game.position(&zb);
+    assert_eq!(
+        pos.to_fen(),
+        "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4"
+    );
+
+    assert!(matches!(
+        games[1],
+        Err(pgn::PgnError::InvalidMove { ref san, err: moves::SanError::NoMatch }) if san == "e4"
+    ));
+
+    let game = games[2].as_ref().unwrap();
+    assert_eq!(game.result, "*");
+    assert_eq!(
+        game.position(&zb).to_fen(),
+        "8/3k4/8/8/8/8/8/2KR4 b - - 2 41"
+    );
+
+    // a bad FEN tag only fails its own game
+    let games = pgn::games(
+        "[FEN \"8/8/8/8 w - - 0 1\"]\n\n1. e4 *\n\n[Event \"good\"]\n\n1. d4 *\n",
+        &masks,
+        &zb,
+    )
+    .collect::<Vec<_>>();
+    assert_eq!(games.len(), 2);
+    assert_eq!(
+        games[0],
+        Err(pgn::PgnError::InvalidFen(String::from("8/8/8/8 w - - 0 1")))
+    );
+    assert_eq!(games[1].as_ref().unwrap().mainline.moves[0].san, "d4");
+
+    // exported games parse back to the same moves
+    let exported = pgn::export_pgn(&game.position(&zb), &[], &masks, &zb);
+    assert_eq!(
+        pgn::parse_pgn(&exported, &masks, &zb).unwrap().mainline,
+        game.mainline
+    );
+
+    assert_eq!(
+        pgn::parse_pgn("1. e4 (1. d4", &masks, &zb),
+        Err(pgn::PgnError::Unterminated)
+    );
+    assert_eq!(
+        pgn::parse_pgn("(1. e4)", &masks, &zb),
+        Err(pgn::PgnError::Unexpected(String::from("(")))
+    );
+}
+
+#[test]
+fn test_epd() {
+    let (masks, zb) = libchess::init();
+
+    let mut record = epd::Epd::parse(
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5 Bc4; am a3;\
+         id \"test; 1\"; ce -15; hmvc 2; fmvn 3; c0 \"\"; noop;",
+        &zb,
+    )
+    .unwrap();
+
+    assert_eq!(record.pos.rule50(), 2);
+    assert_eq!(record.pos.fullmove_number(), 3);
+    assert_eq!(record.id(), Some("test; 1"));
+    assert_eq!(record.centipawn_eval(), Some(-15));
+    assert_eq!(record.direct_mate(), None);
+    assert_eq!(record.op("c0"), Some(&[String::new()][..]));
+    assert_eq!(record.op("noop"), Some(&[][..]));
+
+    let bm = record.best_moves(&masks, &zb).unwrap();
+    assert_eq!(
+        bm.iter().map(|m| m.to_uci_fmt()).collect::<Vec<_>>(),
+        ["f1b5", "f1c4"]
+    );
+    assert_eq!(record.avoid_moves(&masks, &zb).unwrap().len(), 1);
+
+    record.set_op("dm", vec![String::from("3")]);
+    record.remove_op("noop");
+    assert_eq!(record.direct_mate(), Some(3));
+
+    let written = record.to_epd();
+    assert_eq!(
+        written,
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5 Bc4; am a3; \
+         id \"test; 1\"; ce -15; hmvc 2; fmvn 3; c0 \"\"; dm 3;"
+    );
+    assert_eq!(epd::Epd::parse(&written, &zb).unwrap(), record);
+
+    // perft suite records, with clocks and operations starting with a semicolon
+    for line in [
+        "4k3/8/8/8/8/8/8/4K2R w K - 0 1 ;D1 15 ;D2 66",
+        "4k3/8/8/8/8/8/8/4K2R w K - 0 1; D2 66; D1 15",
+    ] {
+        let record = epd::Epd::parse(line, &zb).unwrap();
+        assert_eq!(record.perft_results(), [(1, 15), (2, 66)]);
+        assert_eq!(record.op_value::<i64>("D2"), Some(66));
+    }
+
+    assert_eq!(
+        epd::Epd::parse("4k3/8/8/8/8/8/8/4K2R w K -", &zb)
+            .unwrap()
+            .ops,
+        []
+    );
+    assert_eq!(
+        epd::Epd::parse("4k3/8/8/8/8/8/8/4K2R w K", &zb),
+        Err(epd::EpdError::InvalidPosition)
+    );
+    assert_eq!(
+        epd::Epd::parse("4k3/8/8/8/8/8/8/4K2R w K - 1x 2;", &zb),
+        Err(epd::EpdError::InvalidOpcode(String::from("1x")))
+    );
+    assert_eq!(
+        epd::Epd::parse("4k3/8/8/8/8/8/8/4K2R w K - id \"a;", &zb),
+        Err(epd::EpdError::UnterminatedString)
+    );
+}
+
+#[test]
+fn test_validate_fen() {
+    use pos::{FenIssue, FenIssueKind};
+
+    for test in testpos::all() {
+        assert_eq!(pos::validate_fen(test.fen), Ok(()), "{}", test.name);
+    }
+
+    assert_eq!(
+        pos::validate_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9"),
+        Ok(())
+    );
+
+    let issues = |fen| pos::validate_fen(fen).unwrap_err();
+    let issue = |at, kind| FenIssue { at, kind };
+
+    assert_eq!(
+        issues("rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+        [issue(9, FenIssueKind::SquareCount(pos::RANK_7, 9))]
+    );
+    assert_eq!(
+        issues("rnbqkbnr/ppppXppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 0"),
+        [
+            issue(13, FenIssueKind::InvalidChar('X')),
+            issue(44, FenIssueKind::InvalidChar('x')),
+            issue(55, FenIssueKind::InvalidClock),
+        ]
+    );
+    assert_eq!(
+        issues("4k3/8/8/8/8/8/8/4K3 w KQkq e3"),
+        [
+            issue(29, FenIssueKind::MissingField("halfmove clock")),
+            issue(29, FenIssueKind::MissingField("fullmove number")),
+            issue(22, FenIssueKind::CastlingWithoutRook('K')),
+            issue(23, FenIssueKind::CastlingWithoutRook('Q')),
+            issue(24, FenIssueKind::CastlingWithoutRook('k')),
+            issue(25, FenIssueKind::CastlingWithoutRook('q')),
+            issue(27, FenIssueKind::ImpossibleEnPassant),
+        ]
+    );
+    assert_eq!(
+        issues("4k3/8/8/8/8/8/8/R3K2R w KKQ - 0 1 extra"),
+        [
+            issue(34, FenIssueKind::TooManyFields),
+            issue(25, FenIssueKind::DuplicateCastlingRight('K')),
+            issue(26, FenIssueKind::DuplicateCastlingRight('Q')),
+        ]
+    );
+    assert_eq!(
+        issues("8/4k3/8/8/8/8/8/R3K3 w k - 0 1"),
+        [issue(23, FenIssueKind::CastlingWithoutKing('k'))]
+    );
+
+    let errors = issues("P3k3/8/8/8/8/8/8/8/8 b - z9 -1 1");
+
+    assert!(errors.contains(&issue(0, FenIssueKind::PawnOnBackRank)));
+    assert!(errors.contains(&issue(0, FenIssueKind::RankCount(9))));
+    assert!(errors.contains(&issue(0, FenIssueKind::KingCount(color::WHITE, 0))));
+    assert!(errors.contains(&issue(25, FenIssueKind::InvalidEnPassant)));
+    assert!(errors.contains(&issue(28, FenIssueKind::InvalidClock)));
+    assert_eq!(errors[0].to_string(), "at 0: 9 ranks instead of 8");
+}
+
+#[test]
+fn test_chess960_uci() {
+    let (masks, zb) = libchess::init();
+
+    for (fen, standard, chess960) in [
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1", "e1h1"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8", "e8a8"),
+        // the king castles onto the rook's square, or just next to it
+        ("1r4kr/8/8/8/8/8/8/1R3KR1 w GBhb - 0 1", "f1g1", "f1g1"),
+        ("1r4kr/8/8/8/8/8/8/1R3KR1 w GBhb - 0 1", "f1c1", "f1b1"),
+        ("1r4kr/6p1/8/8/8/8/8/1R3KR1 b GBhb - 0 1", "g8g8", "g8h8"),
+    ] {
+        let mut pos = pos::Position::from_fen(fen, &zb);
+        let castles: Vec<moves::Move> = moves::gen_legal(&mut pos, &masks, &zb)
+            .iter()
+            .copied()
+            .filter(|m| m.to_uci_fmt() == standard)
+            .filter(|m| {
+                matches!(
+                    m.type_of(),
+                    moves::MoveType::KingSideCastle | moves::MoveType::QueenSideCastle
+                )
+            })
+            .collect();
+
+        assert_eq!(castles.len(), 1, "{fen} {standard}");
+        assert_eq!(castles[0].to_uci_fmt_for(&pos), standard);
+
+        pos.set_chess960(true);
+        assert_eq!(castles[0].to_uci_fmt_for(&pos), chess960);
+        assert_eq!(moves::Move::from_str_move(chess960, &pos), castles[0]);
+    }
+
+    // in chess960 mode a king moving two squares isn't castling
+    let mut pos = pos::Position::from_fen("4k3/8/8/8/8/8/8/R3K2R w - - 0 1", &zb);
+    pos.set_chess960(true);
+
+    assert_eq!(
+        moves::Move::from_str_move("e1g1", &pos).type_of(),
+        moves::MoveType::Normal
+    );
+}
+
+#[test]
+fn test_lan() {
+    let (masks, zb) = libchess::init();
+
+    for (fen, uci, lan) in [
+        (pos::START_FEN, "g1f3", "Ng1-f3"),
+        (pos::START_FEN, "e2e4", "e2-e4"),
+        ("3rk3/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7d8q", "e7xd8=Q+"),
+        (
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "e5f6",
+            "e5xf6",
+        ),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8", "O-O-O"),
+        (
+            "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2",
+            "d8h4",
+            "Qd8-h4#",
+        ),
+    ] {
+        let mut pos = pos::Position::from_fen(fen, &zb);
+        let mov = moves::Move::from_str_move(uci, &pos);
+
+        assert_eq!(mov.to_lan(&mut pos, &masks, &zb), lan);
+        assert_eq!(pos.to_fen(), fen);
+    }
+}
+
+#[test]
+fn test_iccf() {
+    let (masks, zb) = libchess::init();
+
+    for (fen, uci, iccf) in [
+        (pos::START_FEN, "e2e4", "5254"),
+        (pos::START_FEN, "g1f3", "7163"),
+        ("3rk3/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7d8q", "57481"),
+        ("3r2k1/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8n", "57584"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8", "5838"),
+    ] {
+        let mut pos = pos::Position::from_fen(fen, &zb);
+        let mov = moves::Move::from_str_move(uci, &pos);
+
+        assert_eq!(mov.to_iccf(), iccf);
+        assert_eq!(
+            moves::Move::from_iccf(iccf, &mut pos, &masks, &zb),
+            Some(mov)
+        );
+    }
+
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    for iccf in ["5255", "525", "52545", "5294", "e2e4", ""] {
+        assert_eq!(moves::Move::from_iccf(iccf, &mut pos, &masks, &zb), None);
+    }
+
+    // castling lands the king on g1 just like Kg1, so in chess960 it's written as the king moving to the rook
+    let mut pos = pos::Position::from_fen("4k3/8/8/8/8/8/8/5K1R w K - 0 1", &zb);
+    let legal = moves::gen_legal(&mut pos, &masks, &zb);
+    let castle = legal
+        .iter()
+        .copied()
+        .find(|m| m.type_of() == moves::MoveType::KingSideCastle)
+        .unwrap();
+    let king_move = moves::Move::from_str_move("f1g1", &pos);
+    assert_eq!(castle.to_iccf(), king_move.to_iccf());
+    assert_eq!(moves::Move::from_iccf("6171", &mut pos, &masks, &zb), None);
+
+    pos.set_chess960(true);
+    assert_eq!(castle.to_iccf_for(&pos), "6181");
+    assert_eq!(king_move.to_iccf_for(&pos), "6171");
+    for mov in [castle, king_move] {
+        assert_eq!(
+            moves::Move::from_iccf(&mov.to_iccf_for(&pos), &mut pos, &masks, &zb),
+            Some(mov)
+        );
+    }
+}
+
+#[test]
+fn test_figurine_san() {
+    let (masks, zb) = libchess::init();
+
+    for (fen, uci, styles) in [
+        (pos::START_FEN, "g1f3", ["♘f3", "♘f3", "♞f3"]),
+        (pos::START_FEN, "e2e4", ["e4", "e4", "e4"]),
+        (
+            "4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1",
+            "b1d2",
+            ["♘bd2", "♘bd2", "♞bd2"],
+        ),
+        (
+            "4k3/1P6/8/8/8/8/K7/8 w - - 0 1",
+            "b7b8q",
+            ["b8=♕+", "b8=♕+", "b8=♛+"],
+        ),
+        (
+            "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1",
+            "e8c8",
+            ["O-O-O", "O-O-O", "O-O-O"],
+        ),
+        (
+            "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2",
+            "d8h4",
+            ["♛h4#", "♕h4#", "♛h4#"],
+        ),
+    ] {
+        let mut pos = pos::Position::from_fen(fen, &zb);
+        let mov = moves::Move::from_str_move(uci, &pos);
+
+        for (style, figurine) in [
+            moves::FigurineStyle::Colored,
+            moves::FigurineStyle::Outline,
+            moves::FigurineStyle::Solid,
+        ]
+        .into_iter()
+        .zip(styles)
+        {
+            assert_eq!(mov.to_figurine_san(&mut pos, style, &masks, &zb), figurine);
+        }
+    }
+}
+
+#[test]
+fn test_display() {
+    let zb = libchess::init().1;
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+    let mov = moves::Move::from_str_move("e2e4", &pos);
+
+    assert_eq!(format!("{mov}"), "e2e4");
+    assert_eq!(
+        format!(
+            "{}{}",
+            piece::display(piece::WHITE_KING),
+            piece::display(piece::BLACK_QUEEN)
+        ),
+        "Kq"
+    );
+    assert_eq!(pos.to_string(), pos::START_FEN);
+
+    pos.make_move(mov, &zb);
+
+    let diagram = format!("{pos:#}");
+    let lines: Vec<&str> = diagram.lines().collect();
+
+    assert_eq!(lines.len(), 18);
+    assert_eq!(lines[1], "| r | n | b | q | k | b | n | r | 8");
+    assert_eq!(lines[9], "|   |   |   |   | P |   |   |   | 4");
+    assert_eq!(lines[17], "  a   b   c   d   e   f   g   h");
+
+    let debug = format!("{pos:?}");
+
+    assert!(debug.starts_with(&diagram));
+    assert!(debug.contains(&format!("fen: {pos}\n")));
+}
+
+#[test]
+fn test_parse_square_and_piece() {
+    assert_eq!(pos::parse_square("a1"), Ok(0));
+    assert_eq!(pos::parse_square("e4"), Ok(pos::str_to_sq("e4")));
+    assert_eq!(pos::parse_square("h8"), Ok(63));
+    assert_eq!(pos::parse_square("e"), Err(pos::ParseSquareError::Length));
+    assert_eq!(pos::parse_square("e44"), Err(pos::ParseSquareError::Length));
+    assert_eq!(
+        pos::parse_square("i4"),
+        Err(pos::ParseSquareError::File('i'))
+    );
+    assert_eq!(
+        pos::parse_square("E4"),
+        Err(pos::ParseSquareError::File('E'))
+    );
+    assert_eq!(
+        pos::parse_square("e9"),
+        Err(pos::ParseSquareError::Rank('9'))
+    );
+
+    assert_eq!(piece::parse("N"), Ok(piece::WHITE_KNIGHT));
+    assert_eq!(piece::parse("q"), Ok(piece::BLACK_QUEEN));
+    assert_eq!(piece::parse("x"), Err(piece::ParsePieceError::Char('x')));
+    assert_eq!(piece::parse("Nf"), Err(piece::ParsePieceError::Length));
+    assert_eq!(piece::parse(""), Err(piece::ParsePieceError::Length));
+
+    let parsed = || -> Result<(pos::Square, piece::Piece), Box<dyn std::error::Error>> {
+        Ok((pos::parse_square("d5")?, piece::parse("z")?))
+    };
+
+    assert_eq!(
+        parsed().unwrap_err().to_string(),
+        "invalid piece character 'z'"
+    );
+}
+
+#[test]
+fn test_uci_server() {
+    use uci::{
+        go::GoParams,
+        option::{EngineOption, OptionKind, OptionValue},
+        server::{Engine, Search, UciServer},
+    };
+
+    struct FirstMove {
+        hash: i64,
+        games: u32,
+    }
+
+    impl Engine for FirstMove {
+        fn name(&self) -> &str {
+            "first move"
+        }
+
+        fn author(&self) -> &str {
+            "libchess"
+        }
+
+        fn options(&self) -> Vec<EngineOption> {
+            let kind = OptionKind::Spin {
+                default: 16,
+                min: 1,
+                max: 1024,
+            };
+
+            vec![EngineOption::new("Hash", kind)]
+        }
+
+        fn set_option(&mut self, name: &str, value: &OptionValue) {
+            if let ("Hash", &OptionValue::Spin(hash)) = (name, value) {
+                self.hash = hash;
+            }
+        }
+
+        fn new_game(&mut self) {
+            self.games += 1;
+        }
+
+        fn search(&mut self, search: &Search) -> Option<moves::Move> {
+            let mut pos = search.pos.clone();
+
+            search.info("depth 1");
+            match search.params.searchmoves.first() {
+                Some(&mov) => Some(mov),
+                None => moves::gen_legal(&mut pos, search.masks, search.zb)
+                    .first()
+                    .copied(),
+            }
+        }
+    }
+
+    let (masks, zb) = libchess::init();
+    let engine = FirstMove { hash: 16, games: 0 };
+    let mut server = UciServer::new(engine, masks, zb);
+
+    let input = "uci\n\
+        isready\n\
+        setoption name hash value 64\n\
+        setoption name Hash value 4096\n\
+        setoption name Threads value 2\n\
+        ucinewgame\n\
+        position startpos moves e2e4 e7e5 e1e1\n\
+        position startpos moves e2e4 e7e5\n\
+        go wtime -20 btime 1000 searchmoves g1f3 b1c3 depth 3\n\
+        quit\n";
+
+    let mut output = Vec::new();
+    server.run_with(input.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "id name first move",
+            "id author libchess",
+            "option name Hash type spin default 16 min 1 max 1024",
+            "uciok",
+            "readyok",
+            "info string option value 4096 is outside of 1..=1024",
+            "info string no option named 'Threads'",
+            "info string illegal move 'e1e1'",
+            "info depth 1",
+            "bestmove g1f3",
+        ]
+    );
+
+    assert_eq!(server.engine().hash, 64);
+    assert_eq!(server.engine().games, 1);
+    assert_eq!(server.position().fullmove_number(), 2);
+
+    let (masks, zb) = libchess::init();
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+    let params = GoParams::parse(
+        "wtime -20 btime 1000 searchmoves g1f3 b1c3 depth 3",
+        &mut pos,
+        &masks,
+        &zb,
+    );
+    assert_eq!(
+        params.time_left(color::WHITE),
+        Some(std::time::Duration::ZERO)
+    );
+    assert_eq!(params.searchmoves.len(), 2);
+    assert_eq!(
+        params.to_command(&pos),
+        "go searchmoves g1f3 b1c3 wtime 0 btime 1000 depth 3"
+    );
+}
+
+#[test]
+fn test_engine_options() {
+    use uci::option::{EngineOption, OptionError, OptionKind, OptionValue};
+
+    let lines = [
+        "option name Hash type spin default 16 min 1 max 1024",
+        "option name Clear Hash type button",
+        "option name Ponder type check default false",
+        "option name Style type combo default Normal var Solid var Normal var Risky",
+        "option name SyzygyPath type string default <empty>",
+    ];
+
+    let options: Vec<EngineOption> = lines
+        .iter()
+        .map(|line| EngineOption::parse(line).unwrap())
+        .collect();
+
+    assert_eq!(options[1].name, "Clear Hash");
+    assert_eq!(
+        options[3].kind,
+        OptionKind::Combo {
+            default: "Normal".to_string(),
+            vars: vec![
+                "Solid".to_string(),
+                "Normal".to_string(),
+                "Risky".to_string()
+            ],
+        }
+    );
+    for (option, line) in options.iter().zip(lines) {
+        assert_eq!(option.to_string(), line);
+    }
+
+    assert_eq!(EngineOption::parse("id name fake engine"), None);
+    assert_eq!(
+        EngineOption::parse("option name Hash type spin default big"),
+        None
+    );
+
+    assert_eq!(
+        options[0].validate(&OptionValue::Spin(2048)),
+        Err(OptionError::OutOfRange {
+            value: 2048,
+            min: 1,
+            max: 1024
+        })
+    );
+    assert_eq!(
+        options[3].validate(&OptionValue::Combo("risky".to_string())),
+        Ok(OptionValue::Combo("Risky".to_string()))
+    );
+    assert!(options[2].validate(&OptionValue::Spin(1)).is_err());
+
+    #[cfg(unix)]
+    {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_engine.sh");
+        let mut engine = uci::Engine::new(path).unwrap();
+        engine.send(uci::UCI).unwrap();
+        while engine.try_get(uci::UCI_OK).unwrap().is_none() {}
+
+        let names: Vec<&str> = engine.options().iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, ["Hash", "Clear Hash", "Style", "SyzygyPath"]);
+
+        engine.start_recording();
+        engine.set_option("hash", &OptionValue::Spin(64)).unwrap();
+        engine
+            .set_option("Clear Hash", &OptionValue::Button)
+            .unwrap();
+        let err = engine
+            .set_option("Threads", &OptionValue::Spin(2))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            uci::EngineError::Option(OptionError::Unknown(_))
+        ));
+        assert!(engine.set_option("Hash", &OptionValue::Spin(0)).is_err());
+
+        let sent = engine.stop_recording().unwrap().to_string();
+        assert!(sent.contains("setoption name Hash value 64"));
+        assert!(sent.contains("setoption name Clear Hash\n"));
+        assert!(!sent.contains("Threads"));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_engine_timeouts() {
+    use std::{io::ErrorKind, time::Duration};
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_engine.sh");
+    let timeout = Duration::from_millis(100);
+
+    let mut engine = uci::Engine::new(path).unwrap();
+    let err = engine.get_next_timeout(timeout).unwrap_err();
+    assert_eq!(std::io::Error::from(err).kind(), ErrorKind::TimedOut);
+
+    // the fake engine ignores `ucinewgame`, so `readyok` never comes
+    engine.send(uci::NEW_GAME).unwrap();
+    let err = engine.wait_for(uci::READY_OK, timeout).unwrap_err();
+    assert_eq!(std::io::Error::from(err).kind(), ErrorKind::TimedOut);
+
+    engine.send(uci::UCI).unwrap();
+    engine.send(uci::IS_READY).unwrap();
+    let line = engine
+        .wait_for(uci::READY_OK, Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(line.trim(), uci::READY_OK);
+    assert_eq!(engine.options().len(), 4);
+
+    engine.send("quit").unwrap();
+    let err = engine
+        .wait_for(uci::READY_OK, Duration::from_secs(5))
+        .unwrap_err();
+    assert_eq!(std::io::Error::from(err).kind(), ErrorKind::UnexpectedEof);
+    assert_eq!(engine.get_next_timeout(timeout).unwrap(), "");
+}
+
+#[test]
+fn test_go_params() {
+    use std::time::Duration;
+    use uci::go::GoParams;
+
+    let (masks, zb) = libchess::init();
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    let params = GoParams::new()
+        .wtime(Duration::from_secs(60))
+        .btime(Duration::from_secs(55))
+        .winc(Duration::from_millis(500))
+        .binc(Duration::from_millis(500))
+        .movestogo(20);
+    let cmd = params.to_command(&pos);
+    assert_eq!(
+        cmd,
+        "go wtime 60000 btime 55000 winc 500 binc 500 movestogo 20"
+    );
+    assert_eq!(
+        GoParams::parse(cmd.strip_prefix("go").unwrap(), &mut pos, &masks, &zb),
+        params
+    );
+
+    let e4 = moves::Move::from_san("e4", &mut pos, &masks, &zb).unwrap();
+    let analysis = GoParams::new()
+        .searchmoves(vec![e4])
+        .depth(12)
+        .nodes(100000)
+        .mate(3)
+        .movetime(Duration::from_secs(2))
+        .infinite();
+    assert_eq!(
+        analysis.to_command(&pos),
+        "go searchmoves e2e4 depth 12 nodes 100000 mate 3 movetime 2000 infinite"
+    );
+
+    #[cfg(unix)]
+    {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_engine.sh");
+        let mut engine = uci::Engine::new(path).unwrap();
+
+        engine.start_recording();
+        engine
+            .go(&pos, pos::START_FEN, &GoParams::new().depth(1))
+            .unwrap();
+        let mov = loop {
+            if let Some(mov) = engine.try_get_move(&pos).unwrap() {
+                break mov;
+            }
+        };
+        assert_eq!(mov, Some(e4));
+
+        let sent = engine.stop_recording().unwrap().to_string();
+        assert!(sent.contains("go depth 1\n"));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_engine_quit() {
+    use std::{io::ErrorKind, time::Duration};
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_engine.sh");
+    let hung = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/hung_engine.sh");
+
+    let mut engine = uci::Engine::new(path).unwrap();
+    let status = engine.quit(Duration::from_secs(5)).unwrap();
+    assert!(status.success());
+    assert_eq!(engine.quit(Duration::ZERO).unwrap(), status);
+    assert_eq!(
+        std::io::Error::from(engine.send(uci::IS_READY).unwrap_err()).kind(),
+        ErrorKind::BrokenPipe
+    );
+
+    let timer = Instant::now();
+    let mut engine = uci::Engine::new(hung).unwrap();
+    let status = engine.quit(Duration::from_millis(100)).unwrap();
+    assert!(!status.success());
+    assert!(timer.elapsed() < Duration::from_secs(5));
+
+    let timer = Instant::now();
+    drop(uci::Engine::new(hung).unwrap());
+    assert!(timer.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_engine_crash_restart() {
+    use std::time::Duration;
+    use uci::{EngineError, RestartPolicy, option::OptionValue};
+
+    let (_, zb) = libchess::init();
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_engine.sh");
+    let timeout = Duration::from_secs(5);
+
+    let mut engine = uci::Engine::new(path).unwrap();
+    engine.send("crash").unwrap();
+    match engine.get_next() {
+        Err(EngineError::Crashed { status, restarted }) => {
+            assert_eq!(status.and_then(|status| status.code()), Some(3));
+            assert!(!restarted);
+        }
+        _ => panic!("crash not detected"),
+    }
+
+    // a crash isn't mistaken for a line that doesn't contain the move yet
+    let mut engine = uci::Engine::new(path).unwrap();
+    engine.send("crash").unwrap();
+    assert!(matches!(
+        engine.try_get_move(&pos::Position::from_fen(pos::START_FEN, &zb)),
+        Err(EngineError::Crashed { .. })
+    ));
+
+    let mut engine = uci::Engine::new(path).unwrap();
+    engine.set_restart_policy(RestartPolicy::Restart { max_restarts: 1 });
+    engine.send(uci::UCI).unwrap();
+    engine.wait_for(uci::UCI_OK, timeout).unwrap();
+    engine.set_option("Hash", &OptionValue::Spin(64)).unwrap();
+    engine.send("position startpos moves e2e4").unwrap();
+
+    engine.start_recording();
+    engine.send("crash").unwrap();
+    assert!(matches!(
+        engine.wait_for(uci::BEST_MOVE, timeout),
+        Err(EngineError::Crashed {
+            restarted: true,
+            ..
+        })
+    ));
+    assert_eq!(engine.restarts(), 1);
+
+    let sent = engine.stop_recording().unwrap().to_string();
+    assert!(sent.contains("> setoption name Hash value 64\n"));
+    assert!(sent.contains("> position startpos moves e2e4\n"));
+
+    engine.send("go depth 1").unwrap();
+    assert!(engine.wait_for(uci::BEST_MOVE, timeout).is_ok());
+
+    engine.send("crash").unwrap();
+    assert!(matches!(
+        engine.get_next_timeout(timeout),
+        Err(EngineError::Crashed {
+            restarted: false,
+            ..
+        })
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_engine_hooks() {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_engine.sh");
+    let log_path = std::env::temp_dir().join(format!("libchess_hooks_{}.log", std::process::id()));
+
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let received = Arc::new(Mutex::new(Vec::new()));
+
+    let mut engine = uci::Engine::new(path).unwrap();
+    let hook_sent = Arc::clone(&sent);
+    engine.on_send(move |_, line| hook_sent.lock().unwrap().push(line.to_string()));
+    let hook_received = Arc::clone(&received);
+    engine.on_receive(move |elapsed, line| {
+        assert!(elapsed < Duration::from_secs(60));
+        hook_received.lock().unwrap().push(line.to_string());
+    });
+    engine.log_to(std::fs::File::create(&log_path).unwrap());
+
+    engine.send(uci::IS_READY).unwrap();
+    engine
+        .wait_for(uci::READY_OK, Duration::from_secs(5))
+        .unwrap();
+    engine.send("go depth 1").unwrap();
+    engine
+        .wait_for(uci::BEST_MOVE, Duration::from_secs(5))
+        .unwrap();
+    drop(engine);
+
+    assert_eq!(
+        *sent.lock().unwrap(),
+        ["isready", "go depth 1", "stop", "quit"]
+    );
+    assert_eq!(
+        *received.lock().unwrap(),
+        [
+            "readyok",
+            "info depth 1 score cp 20 pv e2e4",
+            "bestmove e2e4"
+        ]
+    );
+
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    std::fs::remove_file(&log_path).unwrap();
+    let log: uci::transcript::Transcript = log.parse().unwrap();
+    assert_eq!(log.entries.len(), 7);
+    assert_eq!(log.entries[1].line, "readyok");
+    assert_eq!(
+        log.entries[1].direction,
+        uci::transcript::Direction::Received
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_engine_pool() {
+    use uci::{
+        go::GoParams,
+        option::OptionValue,
+        pool::{EnginePool, Job},
+    };
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_engine.sh");
+    let (masks, zb) = libchess::init();
+
+    let mut pool = EnginePool::with_setup(path, 3, |engine| {
+        engine.set_option("Hash", &OptionValue::Spin(32))
+    })
+    .unwrap();
+    assert_eq!(pool.size(), 3);
+
+    let jobs = (0..10).map(|_| Job {
+        pos: pos::Position::from_fen(pos::START_FEN, &zb),
+        starting_fen: pos::START_FEN.to_string(),
+        params: GoParams::new().depth(1),
+    });
+
+    let results = pool.analyze_all(jobs);
+    assert_eq!(results.len(), 10);
+
+    let mut start = pos::Position::from_fen(pos::START_FEN, &zb);
+    let e4 = moves::Move::from_san("e4", &mut start, &masks, &zb).unwrap();
+
+    for (i, result) in results.iter().enumerate() {
+        let analysis = result.analysis.as_ref().unwrap();
+        assert_eq!(result.id, i);
+        assert_eq!(analysis.best, Some(e4));
+        assert_eq!(analysis.info, ["info depth 1 score cp 20 pv e2e4"]);
+        assert_eq!(
+            analysis.score().map(|score| score.score),
+            Some(uci::score::Score::Cp(20))
+        );
+    }
+
+    let id = pool.submit(results[0].job.clone());
+    assert_eq!(id, 10);
+    assert_eq!(pool.results().recv().unwrap().id, 10);
+
+    // the fake engine's e2e4 isn't legal here, which is an error instead of a panic on the worker
+    let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1";
+    let results = pool.analyze_all([Job {
+        pos: pos::Position::from_fen(fen, &zb),
+        starting_fen: fen.to_string(),
+        params: GoParams::new().depth(1),
+    }]);
+    match &results[0].analysis {
+        Err(uci::EngineError::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::InvalidData),
+        other => panic!("expected an illegal move error, got {other:?}"),
+    }
+
+    assert!(EnginePool::new("/nonexistent/engine", 2).is_err());
+}
+
+#[test]
+fn test_score() {
+    use uci::score::{Bound, BoundedScore, MATE_SCORE, MAX_MATE_MOVES, Score};
+
+    let parse = |line| BoundedScore::parse(line).unwrap();
+
+    let score = parse("info depth 12 seldepth 16 score cp -35 upperbound nodes 1000 pv e7e5");
+    assert_eq!(score.score, Score::Cp(-35));
+    assert_eq!(score.bound, Bound::Upper);
+    assert_eq!(
+        score.to_white(color::BLACK),
+        parse("info score cp 35 lowerbound")
+    );
+    assert_eq!(score.to_white(color::WHITE), score);
+
+    let mated = parse("info depth 20 score mate -3 pv e1e2");
+    assert_eq!(mated.bound, Bound::Exact);
+    assert_eq!(mated.score.to_white(color::BLACK), Score::Mate(3));
+    assert_eq!(mated.score.to_cp(), -MATE_SCORE + 3);
+    assert_eq!(mated.to_string(), "mate -3");
+    assert_eq!(score.to_string(), "cp -35 upperbound");
+
+    assert!(Score::Mate(1) > Score::Mate(5));
+    assert!(Score::Mate(5) > Score::Cp(5000));
+    assert!(Score::Mate(-5) > Score::Mate(-1));
+    assert!(Score::Cp(-5000) > Score::Mate(-10));
+    assert_eq!(Score::Mate(0).to_white(color::BLACK), Score::Mate(0));
+
+    // distances no engine could mean don't overflow, and stay mates
+    let far = parse("info score mate -2147483648");
+    assert_eq!(far.score.to_cp(), -MATE_SCORE + MAX_MATE_MOVES);
+    assert_eq!(far.score.flipped(), Score::Mate(i32::MAX));
+    assert_eq!(Score::Mate(i32::MAX).to_cp(), MATE_SCORE - MAX_MATE_MOVES);
+    assert!(Score::Cp(5000) > far.score);
+
+    assert_eq!(BoundedScore::parse("info depth 1 nodes 20"), None);
+    assert_eq!(BoundedScore::parse("info score wdl 500 300 200"), None);
+}
+
+#[test]
+fn test_perft_result() {
+    let (masks, zb) = libchess::init();
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    let result = perft::perft_result(&mut pos, 3, &masks, &zb);
+    assert_eq!(result.total, 8902);
+    assert_eq!(result.divide.len(), 20);
+    assert_eq!(
+        result.divide.iter().map(|&(_, nodes)| nodes).sum::<u64>(),
+        8902
+    );
+
+    let (e4, nodes) = result
+        .divide
+        .iter()
+        .find(|(mov, _)| mov.to_uci_fmt() == "e2e4")
+        .unwrap();
+    assert_eq!(*nodes, 600);
+    assert!(result.to_string().contains(&format!("{e4}: 600\n")));
+    assert!(result.to_string().ends_with("searched 8902 nodes"));
+
+    let leaf = perft::perft_result(&mut pos, 0, &masks, &zb);
+    assert_eq!(leaf.total, 1);
+    assert!(leaf.divide.is_empty());
+    assert_eq!(pos.to_fen(), pos::START_FEN);
+}
+
+#[test]
+fn test_bulk_counting() {
+    let (masks, zb) = libchess::init();
+
+    for test in testpos::all() {
+        let mut pos = test.position(&zb);
+
+        for depth in 0..=2 {
+            assert_eq!(
+                perft::perft_full(&mut pos, depth, &masks, &zb),
+                perft::perft(&mut pos, depth, false, &masks, &zb) as u64,
+                "{} depth {depth}",
+                test.name
+            );
+        }
+    }
+
+    let mut pos = testpos::kiwipete().position(&zb);
+    assert_eq!(perft::perft_full(&mut pos, 3, &masks, &zb), 97862);
+    assert_eq!(perft::perft(&mut pos, 1, false, &masks, &zb), 48);
+}
+
+#[test]
+fn test_perft_parallel() {
+    let (masks, zb) = libchess::init();
+    let mut pos = testpos::kiwipete().position(&zb);
+
+    let expected = perft::perft_result(&mut pos, 3, &masks, &zb);
+
+    for threads in [0, 1, 3, 64] {
+        assert_eq!(
+            perft::perft_parallel(&pos, 3, threads, &masks, &zb),
+            expected
+        );
+    }
+
+    assert_eq!(perft::perft_parallel(&pos, 0, 4, &masks, &zb).total, 1);
+
+    let mate = pos::Position::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", &zb);
+    assert_eq!(perft::perft_parallel(&mate, 2, 4, &masks, &zb).total, 0);
+}
+
+#[test]
+fn test_divide() {
+    let (masks, zb) = libchess::init();
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    let divide = perft::divide(&mut pos, 2, &masks, &zb);
+    let uci: Vec<String> = divide.iter().map(|(mov, _)| mov.to_uci_fmt()).collect();
+
+    let mut sorted = uci.clone();
+    sorted.sort();
+    assert_eq!(uci, sorted);
+    assert_eq!(uci[0], "a2a3");
+    assert_eq!(uci[19], "h2h4");
+    assert!(divide.iter().all(|&(_, nodes)| nodes == 20));
+
+    let mut pos = pos::Position::from_fen("k7/8/8/8/8/8/8/R3K2R w KQ - 0 1", &zb);
+    let uci: Vec<String> = perft::divide(&mut pos, 1, &masks, &zb)
+        .iter()
+        .map(|(mov, _)| mov.to_uci_fmt())
+        .collect();
+    assert!(uci.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(uci.contains(&"e1g1".to_string()));
+}
+
+#[test]
+fn test_perft_progress_and_stop() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let (masks, zb) = libchess::init();
+    let mut pos = testpos::kiwipete().position(&zb);
+    let stop = AtomicBool::new(false);
+
+    let mut reported = Vec::new();
+    let result = perft::perft_with(
+        &mut pos,
+        2,
+        &stop,
+        |mov, nodes, _| reported.push((mov, nodes)),
+        &masks,
+        &zb,
+    )
+    .unwrap();
+    assert_eq!(result.total, 2039);
+    assert_eq!(reported, result.divide);
+
+    let mut searched = 0;
+    let stopped = perft::perft_with(
+        &mut pos,
+        3,
+        &stop,
+        |_, _, _| {
+            searched += 1;
+            if searched == 5 {
+                stop.store(true, Ordering::Relaxed);
+            }
+        },
+        &masks,
+        &zb,
+    );
+    assert_eq!(stopped, None);
+    assert_eq!(searched, 5);
+    assert_eq!(pos.to_fen(), testpos::kiwipete().fen);
+}
+
+#[test]
+fn test_suite_report() {
+    let (masks, zb) = libchess::init();
+
+    let path = std::env::temp_dir().join("libchess_test_suite_report.epd");
+    std::fs::write(
+        &path,
+        "4k3/8/8/8/8/8/8/4K2R w K - 0 1 ;D1 15 ;D2 67\n8/8/8/8/8/8/8/K1k5 w - - 0 1 ;D1 1\n",
+    )
+    .unwrap();
+    let path = path.to_str().unwrap();
+
+    let suite = perft::test_epd(path, 2, 10, 0, &masks, &zb);
+    assert_eq!(suite.positions.len(), 2);
+    assert_eq!((suite.passed(), suite.failed()), (2, 1));
+    assert!(!suite.is_ok());
+
+    let failures: Vec<_> = suite.failures().collect();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, "4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+    assert_eq!((failures[0].1.depth, failures[0].1.actual), (2, 66));
+
+    let text = suite.to_string();
+    assert!(text.contains("  depth 2: expected 67, actual 66 in "));
+    assert!(text.contains("(-1), failed\n"));
+    assert!(text.starts_with("4k3/8/8/8/8/8/8/4K2R w K - 0 1\n  depth 1: expected 15, actual 15"));
+
+    let mut events = Vec::new();
+    suite.report_to(&mut |event: &report::Event| {
+        if let report::Event::PerftSuiteFinished { passed, failed } = *event {
+            events.push((passed, failed));
+        }
+    });
+    assert_eq!(events, [(2, 1)]);
+
+    let suite = perft::test_epd(path, 1, 1, 0, &masks, &zb);
+    assert!(suite.is_ok());
+    assert_eq!(suite.positions.len(), 1);
+}
+
+#[test]
+fn test_bench() {
+    let (masks, zb) = libchess::init();
+
+    let report = perft::bench(&perft::BENCH_POSITIONS, 2, &masks, &zb);
+    assert_eq!(report.entries.len(), 6);
+    assert_eq!(report.entries[1].nodes, 2039);
+    assert_eq!(
+        report.nodes(),
+        report.entries.iter().map(|entry| entry.nodes).sum::<u64>()
+    );
+    assert_eq!(
+        perft::bench(&perft::BENCH_POSITIONS, 2, &masks, &zb).nodes(),
+        report.nodes()
+    );
+
+    for (entry, fen) in report.entries.iter().zip(perft::BENCH_POSITIONS) {
+        assert_eq!(entry.fen, fen);
+    }
+
+    let text = report.to_string();
+    assert_eq!(text.lines().count(), 7);
+    assert!(text.contains(&format!("depth 2, total: {} nodes in ", report.nodes())));
+}
+
+#[test]
+fn test_perft_bisect() {
+    use std::time::Duration;
+
+    let (masks, zb) = libchess::init();
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/perft_engine.sh");
+
+    let mut engine = uci::Engine::new(path).unwrap();
+    engine.send(uci::UCI).unwrap();
+    engine
+        .wait_for(uci::UCI_OK, Duration::from_secs(5))
+        .unwrap();
+
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    let divide = engine.perft(&pos, pos::START_FEN, 2).unwrap();
+    assert_eq!(divide.len(), 20);
+    assert!(divide.contains(&("g1f3".to_string(), 21)));
+
+    assert_eq!(
+        perft::bisect(&mut pos, pos::START_FEN, 1, &mut engine, &masks, &zb).unwrap(),
+        None
+    );
+
+    let divergence = perft::bisect(&mut pos, pos::START_FEN, 2, &mut engine, &masks, &zb)
+        .unwrap()
+        .unwrap();
+    let nf3 = moves::Move::from_str_move("g1f3", &pos);
+
+    assert_eq!(divergence.path, vec![nf3]);
+    assert_eq!(
+        divergence.mismatch,
+        perft::Mismatch::Missing("e8e7".to_string())
+    );
+
+    pos.make_move(nf3, &zb);
+    assert_eq!(divergence.fen, pos.to_fen());
+    pos.unmake_move();
+    assert_eq!(pos.ply(), 0);
+
+    assert!(
+        divergence
+            .to_string()
+            .ends_with("e8e7 is only generated by the reference engine")
+    );
+}
+
+#[test]
+fn test_polyglot_keys() {
+    let (_, zb) = libchess::init_polyglot();
+
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+    assert_eq!(pos.key(), 0x463b_9618_1691_fc9c);
+
+    // the keys from the Polyglot book format specification
+    for (moves, key) in [
+        ("e2e4", 0x823c_9b50_fd11_4196),
+        ("e2e4 d7d5", 0x0756_b944_61c5_0fb0),
+        ("e2e4 d7d5 e4e5", 0x662f_afb9_65db_29d4),
+        ("e2e4 d7d5 e4e5 f7f5", 0x22a4_8b5a_8e47_ff78),
+        ("e2e4 d7d5 e4e5 f7f5 e1e2", 0x652a_607c_a3f2_42c1),
+        ("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7", 0x00fd_d303_c946_bdd9),
+        ("a2a4 b7b5 h2h4 b5b4 c2c4", 0x3c81_23ea_7b06_7637),
+        ("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3", 0x5c3f_9b82_9b27_9560),
+    ] {
+        let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+        for mov in moves.split_whitespace() {
+            pos.make_move(moves::Move::from_str_move(mov, &pos), &zb);
+        }
+
+        assert_eq!(pos.key(), key, "{moves}");
+        assert_eq!(pos::Position::from_fen(&pos.to_fen(), &zb).key(), key);
+    }
+
+    // FENs always have an en passant square after a double push, but it's only hashed if a pawn can capture
+    for (fen, key) in [
+        (
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            0x823c_9b50_fd11_4196,
+        ),
+        (
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+            0x0756_b944_61c5_0fb0,
+        ),
+        (
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            0x22a4_8b5a_8e47_ff78,
+        ),
+        (
+            "rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3",
+            0x3c81_23ea_7b06_7637,
+        ),
+    ] {
+        let from_fen = pos::Position::from_fen(fen, &zb);
+        assert_eq!(from_fen.key(), key, "{fen}");
+        assert_eq!(
+            pos::Position::from_bytes(&from_fen.to_bytes(), &zb)
+                .unwrap()
+                .key(),
+            key
+        );
+    }
+
+    pos.make_move(moves::Move::from_str_move("g1f3", &pos), &zb);
+    pos.unmake_move();
+    assert_eq!(pos.key(), 0x463b_9618_1691_fc9c);
+}
+
+#[test]
+fn test_const_zobrist() {
+    use libchess::ZobristValues;
+
+    const ZB: ZobristValues = ZobristValues::from_seed(7);
+    static POLYGLOT: ZobristValues = ZobristValues::polyglot();
+
+    let (_, zb) = libchess::init_with_seed(7);
+    let key = |zb| pos::Position::from_fen(testpos::kiwipete().fen, zb).key();
+
+    assert_eq!(key(&ZB), key(&zb));
+    assert_eq!(key(&zobrist::DEFAULT_VALUES), 0xb4f0_3280_2577_2e87);
+    assert_eq!(
+        pos::Position::from_fen(pos::START_FEN, &POLYGLOT).key(),
+        0x463b_9618_1691_fc9c
+    );
+    assert_eq!(
+        key(&zobrist::POLYGLOT_VALUES),
+        key(&libchess::init_polyglot().1)
+    );
+}
+
+#[test]
+fn test_ep_square_only_when_capturable() {
+    let (_, zb) = libchess::init();
+
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    for (uci, ep) in [
+        ("e2e4", None),
+        ("a7a6", None),
+        ("e4e5", None),
+        ("d7d5", Some("d6")),
+        ("h2h4", None),
+        ("f7f5", Some("f6")),
+    ] {
+        let mov = moves::Move::from_str_move(uci, &pos);
+        let key = pos.key_after(mov, &zb);
+
+        pos.make_move(mov, &zb);
+        assert_eq!(pos.ep_square(), ep.map(pos::str_to_sq), "{uci}");
+        assert_eq!(pos.key(), key, "{uci}");
+        assert_eq!(pos.key(), zobrist::hash(&pos, &zb), "{uci}");
+    }
+
+    // a double pawn push nobody can capture leaves the same position as any other way of getting there
+    let mut pos = pos::Position::from_fen(pos::START_FEN, &zb);
+
+    for uci in [
+        "e2e4", "g8f6", "g1f3", "f6g8", "f3g1", "g8f6", "g1f3", "f6g8", "f3g1",
+    ] {
+        pos.make_move(moves::Move::from_str_move(uci, &pos), &zb);
+    }
+
+    assert!(pos.is_3_rep());
+}